@@ -116,7 +116,7 @@ mod std_a_star {
         let mut available: BinaryHeap<Reverse<(usize, usize, Position)>> = BinaryHeap::new();
         // Position to minimal total cost. Used to decide is need to enter new val into heap
         let mut remembered_nodes: FxHashMap<Position, usize> = FxHashMap::default();
-        available.push(Reverse((0 + calc_heuristic(start), 0, start)));
+        available.push(Reverse((calc_heuristic(start), 0, start)));
         while let Some(Reverse((_, current_cost, current_pos))) = available.pop() {
             if current_pos == target {
                 return Some(restore_path(current_pos, &parentize, start));
@@ -132,7 +132,7 @@ mod std_a_star {
 
             closed_set.insert(current_pos);
 
-            let neighbours = get_neighbors(current_pos, &field);
+            let neighbours = get_neighbors(current_pos, field);
             for next in neighbours.items[..neighbours.len]
                 .iter()
                 .cloned()
@@ -154,7 +154,7 @@ mod std_a_star {
 mod keyed_a_star {
     use super::*;
     use fxhash::{FxHashMap, FxHashSet};
-    use keyed_priority_queue::{EditableHeap, Entry, KeyedPriorityQueue};
+    use keyed_priority_queue::{BucketPriority, EditableHeap, Entry, KeyedPriorityQueue};
     use std::hash::BuildHasher;
     // Positions sortered by total cost and real cost.
     // We prefer items with lower real cost if total are same.
@@ -164,14 +164,72 @@ mod keyed_a_star {
         real: usize,
     }
 
+    impl From<Cost> for Reverse<Cost> {
+        fn from(cost: Cost) -> Self {
+            Reverse(cost)
+        }
+    }
+
+    impl From<Reverse<Cost>> for Cost {
+        fn from(cost: Reverse<Cost>) -> Self {
+            cost.0
+        }
+    }
+
+    // Bucket queue can order items only by total cost so real cost doesn't break ties
+    #[derive(Copy, Clone, Debug)]
+    pub(crate) struct TotalCost(Cost);
+
+    impl PartialEq for TotalCost {
+        fn eq(&self, other: &Self) -> bool {
+            self.0.total == other.0.total
+        }
+    }
+
+    impl Eq for TotalCost {}
+
+    impl PartialOrd for TotalCost {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for TotalCost {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            other.0.total.cmp(&self.0.total)
+        }
+    }
+
+    impl From<Cost> for TotalCost {
+        fn from(cost: Cost) -> Self {
+            TotalCost(cost)
+        }
+    }
+
+    impl From<TotalCost> for Cost {
+        fn from(cost: TotalCost) -> Self {
+            cost.0
+        }
+    }
+
+    impl BucketPriority for TotalCost {
+        fn bucket_index(&self) -> usize {
+            self.0.total
+        }
+    }
+
     pub(crate) fn find_path<
         HasherParam: BuildHasher + Default,
-        THeap: EditableHeap<Reverse<Cost>>,
+        TPriority: Ord + Copy + From<Cost> + Into<Cost>,
+        THeap: EditableHeap<TPriority>,
     >(
         start: Position,
         target: Position,
         field: &Field,
-    ) -> Option<Vec<Position>> {
+    ) -> Option<Vec<Position>>
+    where
+        THeap::Alloc: Default,
+    {
         if start == target {
             return Some(vec![start]);
         }
@@ -202,22 +260,23 @@ mod keyed_a_star {
         // Already checked
         let mut closed_set: FxHashSet<Position> = FxHashSet::default();
         let mut available =
-            KeyedPriorityQueue::<Position, Reverse<Cost>, THeap, HasherParam>::default();
+            KeyedPriorityQueue::<Position, TPriority, THeap, HasherParam>::default();
         available.push(
             start,
-            Reverse(Cost {
+            TPriority::from(Cost {
                 total: calc_heuristic(start),
                 real: 0,
             }),
         );
-        while let Some((current_pos, Reverse(current_cost))) = available.pop() {
+        while let Some((current_pos, current_cost)) = available.pop() {
+            let current_cost: Cost = current_cost.into();
             if current_pos == target {
                 return Some(restore_path(current_pos, &parentize, start));
             }
 
             closed_set.insert(current_pos);
 
-            let neighbours = get_neighbors(current_pos, &field);
+            let neighbours = get_neighbors(current_pos, field);
             for next in neighbours.items[..neighbours.len]
                 .iter()
                 .cloned()
@@ -228,11 +287,11 @@ mod keyed_a_star {
                 let cost = Cost { total, real };
                 match available.entry(next) {
                     Entry::Vacant(entry) => {
-                        entry.set_priority(Reverse(cost));
+                        entry.set_priority(TPriority::from(cost));
                         parentize.insert(next, current_pos);
                     }
-                    Entry::Occupied(entry) if *entry.get_priority() < Reverse(cost) => {
-                        entry.set_priority(Reverse(cost));
+                    Entry::Occupied(entry) if *entry.get_priority() < TPriority::from(cost) => {
+                        entry.set_priority(TPriority::from(cost));
                         parentize.insert(next, current_pos);
                     }
                     _ => {}
@@ -251,10 +310,7 @@ fn generate_field(size: usize) -> Field {
     use rand_chacha::ChaCha8Rng;
     let mut rng = ChaCha8Rng::seed_from_u64(SEED);
     let dist = rand::distributions::Uniform::new_inclusive(1u32, 10u32);
-    let vec: Vec<u32> = (0..size * size)
-        .into_iter()
-        .map(|_| rng.sample(dist))
-        .collect();
+    let vec: Vec<u32> = (0..size * size).map(|_| rng.sample(dist)).collect();
     Field {
         columns: size,
         rows: size,
@@ -283,6 +339,7 @@ fn find_path_benchmark(c: &mut Criterion) {
                 b.iter(|| {
                     keyed_a_star::find_path::<
                         std::collections::hash_map::RandomState,
+                        Reverse<keyed_a_star::Cost>,
                         keyed_priority_queue::BinaryHeap<Reverse<keyed_a_star::Cost>>,
                     >(start, target, field)
                 })
@@ -295,6 +352,7 @@ fn find_path_benchmark(c: &mut Criterion) {
                 b.iter(|| {
                     keyed_a_star::find_path::<
                         std::collections::hash_map::RandomState,
+                        Reverse<keyed_a_star::Cost>,
                         keyed_priority_queue::WeakHeap<Reverse<keyed_a_star::Cost>>,
                     >(start, target, field)
                 })
@@ -307,6 +365,7 @@ fn find_path_benchmark(c: &mut Criterion) {
                 b.iter(|| {
                     keyed_a_star::find_path::<
                         fxhash::FxBuildHasher,
+                        Reverse<keyed_a_star::Cost>,
                         keyed_priority_queue::BinaryHeap<Reverse<keyed_a_star::Cost>>,
                     >(start, target, field)
                 })
@@ -319,11 +378,25 @@ fn find_path_benchmark(c: &mut Criterion) {
                 b.iter(|| {
                     keyed_a_star::find_path::<
                         fxhash::FxBuildHasher,
+                        Reverse<keyed_a_star::Cost>,
                         keyed_priority_queue::WeakHeap<Reverse<keyed_a_star::Cost>>,
                     >(start, target, field)
                 })
             },
         );
+        group.bench_with_input(
+            BenchmarkId::new("Keyed A Star Bucket FxHash", end),
+            &(start, stop_at, &field),
+            |b, &(start, target, field)| {
+                b.iter(|| {
+                    keyed_a_star::find_path::<
+                        fxhash::FxBuildHasher,
+                        keyed_a_star::TotalCost,
+                        keyed_priority_queue::BucketQueue<keyed_a_star::TotalCost>,
+                    >(start, target, field)
+                })
+            },
+        );
     }
     const BIG_SIZE: usize = 500;
     let field_eq = Field {
@@ -349,6 +422,7 @@ fn find_path_benchmark(c: &mut Criterion) {
             b.iter(|| {
                 keyed_a_star::find_path::<
                     std::collections::hash_map::RandomState,
+                    Reverse<keyed_a_star::Cost>,
                     keyed_priority_queue::BinaryHeap<Reverse<keyed_a_star::Cost>>,
                 >(start, stop_at, &field_eq)
             })
@@ -361,6 +435,7 @@ fn find_path_benchmark(c: &mut Criterion) {
             b.iter(|| {
                 keyed_a_star::find_path::<
                     std::collections::hash_map::RandomState,
+                    Reverse<keyed_a_star::Cost>,
                     keyed_priority_queue::WeakHeap<Reverse<keyed_a_star::Cost>>,
                 >(start, stop_at, &field_eq)
             })
@@ -373,6 +448,7 @@ fn find_path_benchmark(c: &mut Criterion) {
             b.iter(|| {
                 keyed_a_star::find_path::<
                     fxhash::FxBuildHasher,
+                    Reverse<keyed_a_star::Cost>,
                     keyed_priority_queue::BinaryHeap<Reverse<keyed_a_star::Cost>>,
                 >(start, stop_at, &field_eq)
             })
@@ -385,11 +461,25 @@ fn find_path_benchmark(c: &mut Criterion) {
             b.iter(|| {
                 keyed_a_star::find_path::<
                     fxhash::FxBuildHasher,
+                    Reverse<keyed_a_star::Cost>,
                     keyed_priority_queue::WeakHeap<Reverse<keyed_a_star::Cost>>,
                 >(start, stop_at, &field_eq)
             })
         },
     );
+    group.bench_with_input(
+        BenchmarkId::new("Keyed A Star Bucket Ones field FxHash", BIG_SIZE),
+        &(start, stop_at, &field),
+        |b, _| {
+            b.iter(|| {
+                keyed_a_star::find_path::<
+                    fxhash::FxBuildHasher,
+                    keyed_a_star::TotalCost,
+                    keyed_priority_queue::BucketQueue<keyed_a_star::TotalCost>,
+                >(start, stop_at, &field_eq)
+            })
+        },
+    );
 
     group.finish();
 }
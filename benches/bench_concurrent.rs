@@ -0,0 +1,73 @@
+extern crate criterion;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use keyed_priority_queue::{ConcurrentKeyedBinaryPriorityQueue, KeyedBinaryPriorityQueue};
+use std::sync::Mutex;
+
+mod generators;
+use crate::generators::gen_random_usizes;
+
+const OPS_PER_THREAD: usize = 10_000;
+
+pub fn bench_concurrent(c: &mut Criterion) {
+    let keys = gen_random_usizes(OPS_PER_THREAD * 8, 0);
+    let values = gen_random_usizes(OPS_PER_THREAD * 8, 7);
+
+    let mut group = c.benchmark_group("concurrent_push_pop_usizes");
+    for &threads in &[1, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::new("global_mutex", threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    let queue = Mutex::new(KeyedBinaryPriorityQueue::<usize, usize>::new());
+                    std::thread::scope(|s| {
+                        for t in 0..threads {
+                            let queue = &queue;
+                            let keys = &keys[t * OPS_PER_THREAD..(t + 1) * OPS_PER_THREAD];
+                            let values = &values[t * OPS_PER_THREAD..(t + 1) * OPS_PER_THREAD];
+                            s.spawn(move || {
+                                for (i, (&k, &v)) in keys.iter().zip(values).enumerate() {
+                                    queue.lock().unwrap().push(k, v);
+                                    if i % 2 == 1 {
+                                        queue.lock().unwrap().pop();
+                                    }
+                                }
+                            });
+                        }
+                    });
+                    queue
+                });
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("sharded", threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    let queue = ConcurrentKeyedBinaryPriorityQueue::<usize, usize>::new();
+                    std::thread::scope(|s| {
+                        for t in 0..threads {
+                            let queue = &queue;
+                            let keys = &keys[t * OPS_PER_THREAD..(t + 1) * OPS_PER_THREAD];
+                            let values = &values[t * OPS_PER_THREAD..(t + 1) * OPS_PER_THREAD];
+                            s.spawn(move || {
+                                for (i, (&k, &v)) in keys.iter().zip(values).enumerate() {
+                                    queue.push(k, v);
+                                    if i % 2 == 1 {
+                                        queue.pop();
+                                    }
+                                }
+                            });
+                        }
+                    });
+                    queue
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_concurrent);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+extern crate criterion;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use keyed_priority_queue::{BinaryHeap, CalendarQueue, EditableHeap, KeyedPriorityQueue, WeakHeap};
+use std::cmp::Reverse;
+
+mod generators;
+use crate::generators::gen_random_usizes;
+
+type Queue<THeap> = KeyedPriorityQueue<usize, Reverse<u64>, THeap>;
+
+// Classic hold model of discrete-event simulation:
+// every popped event schedules next one at random time after it.
+fn hold<THeap: EditableHeap<Reverse<u64>>>(
+    mut queue: Queue<THeap>,
+    increments: &[usize],
+) -> Queue<THeap> {
+    for &increment in increments {
+        let (id, Reverse(now)) = queue.pop().unwrap();
+        queue.push(id, Reverse(now + increment as u64));
+    }
+    queue
+}
+
+fn bench_queue<THeap: EditableHeap<Reverse<u64>> + Clone>(
+    c: &mut Criterion,
+    name: &str,
+    base_times: &[usize],
+    increments: &[usize],
+) where
+    THeap::Alloc: Default,
+{
+    let mut group = c.benchmark_group(name);
+    for &size in &[10_000, 500_000] {
+        assert!(base_times.len() >= size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            let base_queue: Queue<THeap> = base_times[..size]
+                .iter()
+                .map(|&time| Reverse(time as u64))
+                .enumerate()
+                .collect();
+            b.iter_batched(
+                || base_queue.clone(),
+                |queue| hold(queue, increments),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+pub fn bench_hold(c: &mut Criterion) {
+    let base_times = gen_random_usizes(500_000, 0);
+    let increments = gen_random_usizes(1000, 7);
+
+    bench_queue::<BinaryHeap<Reverse<u64>>>(c, "binary_hold", &base_times, &increments);
+    bench_queue::<WeakHeap<Reverse<u64>>>(c, "weak_hold", &base_times, &increments);
+    bench_queue::<CalendarQueue<Reverse<u64>>>(c, "calendar_hold", &base_times, &increments);
+}
+
+criterion_group!(benches, bench_hold);
+criterion_main!(benches);
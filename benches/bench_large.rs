@@ -0,0 +1,67 @@
+extern crate criterion;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use keyed_priority_queue::{
+    BinaryHeap, BlockedHeap, EditableHeap, KeyedPriorityQueue, SoaStorage, WeakHeap,
+};
+
+mod generators;
+use crate::generators::gen_random_usizes;
+
+type Queue<THeap> = KeyedPriorityQueue<usize, usize, THeap>;
+
+const SIZES: [usize; 2] = [1_000_000, 10_000_000];
+const OPERATIONS: usize = 1000;
+
+// Queue is reused between iterations because cloning tens of millions
+// of items costs much more than measured operations.
+// Operations keep number of items the same.
+fn bench_queue<THeap>(c: &mut Criterion, name: &str, priorities: &[usize])
+where
+    THeap: EditableHeap<usize>,
+    THeap::Alloc: Default,
+{
+    let updates = gen_random_usizes(OPERATIONS, 7);
+
+    let mut group = c.benchmark_group(name);
+    group.sample_size(20);
+    for &size in SIZES.iter() {
+        let mut queue: Queue<THeap> = priorities[..size].iter().cloned().enumerate().collect();
+        let keys: Vec<usize> = gen_random_usizes(OPERATIONS, 11)
+            .into_iter()
+            .map(|x| x % size)
+            .collect();
+
+        group.bench_function(BenchmarkId::new("pop_push", size), |b| {
+            b.iter(|| {
+                for &update in updates.iter() {
+                    let (key, priority) = queue.pop().unwrap();
+                    queue.push(key, priority / 2 + update / 2);
+                }
+            });
+        });
+        group.bench_function(BenchmarkId::new("set_priority", size), |b| {
+            b.iter(|| {
+                for (&key, &update) in keys.iter().zip(updates.iter()) {
+                    let old = queue.set_priority(&key, update).unwrap();
+                    queue.set_priority(&key, old).unwrap();
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+pub fn bench_large(c: &mut Criterion) {
+    let priorities = gen_random_usizes(SIZES[SIZES.len() - 1], 0);
+
+    bench_queue::<BinaryHeap<usize>>(c, "binary_large", &priorities);
+    bench_queue::<BinaryHeap<usize, SoaStorage<usize>>>(c, "binary_soa_large", &priorities);
+    bench_queue::<WeakHeap<usize>>(c, "weak_large", &priorities);
+    bench_queue::<WeakHeap<usize, SoaStorage<usize>>>(c, "weak_soa_large", &priorities);
+    bench_queue::<BlockedHeap<usize>>(c, "blocked_large", &priorities);
+    bench_queue::<BlockedHeap<usize, 7>>(c, "blocked_page_large", &priorities);
+}
+
+criterion_group!(benches, bench_large);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+extern crate criterion;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use keyed_priority_queue::{BinaryHeap, EditableHeap, KeyedPriorityQueue, LeftistHeap, WeakHeap};
+
+mod generators;
+use crate::generators::gen_random_usizes;
+
+type Queue<THeap> = KeyedPriorityQueue<usize, usize, THeap>;
+
+const REGIONS: usize = 64;
+
+// Merges frontiers of regions into one queue like parallel search does.
+fn bench_queue<THeap: EditableHeap<usize> + Clone>(
+    c: &mut Criterion,
+    name: &str,
+    priorities: &[usize],
+) where
+    THeap::Alloc: Default,
+{
+    let mut group = c.benchmark_group(name);
+    for &size in &[10_000, 500_000] {
+        assert!(priorities.len() >= size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            let region_size = size / REGIONS;
+            let frontiers: Vec<Queue<THeap>> = (0..REGIONS)
+                .map(|region| {
+                    let keys = region * region_size..(region + 1) * region_size;
+                    keys.map(|key| (key, priorities[key])).collect()
+                })
+                .collect();
+            b.iter_batched(
+                || frontiers.clone(),
+                |frontiers| {
+                    let mut merged = Queue::<THeap>::new();
+                    for frontier in frontiers {
+                        merged.meld(frontier);
+                    }
+                    merged
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+pub fn bench_meld(c: &mut Criterion) {
+    let priorities = gen_random_usizes(500_000, 0);
+
+    bench_queue::<BinaryHeap<usize>>(c, "binary_meld", &priorities);
+    bench_queue::<WeakHeap<usize>>(c, "weak_meld", &priorities);
+    bench_queue::<LeftistHeap<usize>>(c, "leftist_meld", &priorities);
+}
+
+criterion_group!(benches, bench_meld);
+criterion_main!(benches);
@@ -14,10 +14,7 @@ pub fn bench_push(c: &mut Criterion) {
 
     let extra_keys = gen_random_usizes(1000, 8);
     let extra_values = gen_random_usizes(1000, 20);
-    let extra: Vec<_> = extra_keys
-        .into_iter()
-        .zip(extra_values.into_iter())
-        .collect();
+    let extra: Vec<_> = extra_keys.into_iter().zip(extra_values).collect();
 
     let mut group = c.benchmark_group("binary_push_usizes_random");
     for &size in &[100_000, 200_000, 300_000, 400_000, 500_000] {
@@ -48,10 +45,7 @@ pub fn bench_push(c: &mut Criterion) {
 
     let extra_keys = gen_random_usizes(1000, 8);
     let extra_values = gen_random_usizes(1000, 20);
-    let extra: Vec<_> = extra_keys
-        .into_iter()
-        .zip(extra_values.into_iter())
-        .collect();
+    let extra: Vec<_> = extra_keys.into_iter().zip(extra_values).collect();
 
     let mut group = c.benchmark_group("weak_push_usizes_random");
     for &size in &[100_000, 200_000, 300_000, 400_000, 500_000] {
@@ -83,10 +77,7 @@ pub fn bench_push(c: &mut Criterion) {
 
     let extra_keys = get_random_strings(1000, 8);
     let extra_values = get_random_strings(1000, 20);
-    let extra: Vec<_> = extra_keys
-        .into_iter()
-        .zip(extra_values.into_iter())
-        .collect();
+    let extra: Vec<_> = extra_keys.into_iter().zip(extra_values).collect();
 
     for &size in &[10_000, 20_000, 30_000, 40_000, 50_000] {
         assert!(base_keys.len() >= size);
@@ -116,10 +107,7 @@ pub fn bench_push(c: &mut Criterion) {
 
     let extra_keys = get_random_strings(1000, 8);
     let extra_values = get_random_strings(1000, 20);
-    let extra: Vec<_> = extra_keys
-        .into_iter()
-        .zip(extra_values.into_iter())
-        .collect();
+    let extra: Vec<_> = extra_keys.into_iter().zip(extra_values).collect();
 
     for &size in &[10_000, 20_000, 30_000, 40_000, 50_000] {
         assert!(base_keys.len() >= size);
@@ -143,16 +131,13 @@ pub fn bench_push(c: &mut Criterion) {
     }
     group.finish();
 
-    let mut base_keys: Vec<usize> = (0..520_000).into_iter().collect();
+    let mut base_keys: Vec<usize> = (0..520_000).collect();
     let base_values = gen_random_usizes(520_000, 7);
 
     let extra_keys: Vec<_> = base_keys[500_000..].into();
     base_keys.truncate(500_000);
     let (base_values, extra_values) = generate_worst_push_data(base_values, 20_000, 987987);
-    let extra: Vec<_> = extra_keys
-        .into_iter()
-        .zip(extra_values.into_iter())
-        .collect();
+    let extra: Vec<_> = extra_keys.into_iter().zip(extra_values).collect();
 
     let mut group = c.benchmark_group("binary_push_usizes_worst");
     for &size in &[100_000, 200_000, 300_000, 400_000, 500_000] {
@@ -178,16 +163,13 @@ pub fn bench_push(c: &mut Criterion) {
 
     group.finish();
 
-    let mut base_keys: Vec<usize> = (0..520_000).into_iter().collect();
+    let mut base_keys: Vec<usize> = (0..520_000).collect();
     let base_values = gen_random_usizes(520_000, 7);
 
     let extra_keys: Vec<_> = base_keys[500_000..].into();
     base_keys.truncate(500_000);
     let (base_values, extra_values) = generate_worst_push_data(base_values, 20_000, 987987);
-    let extra: Vec<_> = extra_keys
-        .into_iter()
-        .zip(extra_values.into_iter())
-        .collect();
+    let extra: Vec<_> = extra_keys.into_iter().zip(extra_values).collect();
 
     let mut group = c.benchmark_group("weak_push_usizes_worst");
     for &size in &[100_000, 200_000, 300_000, 400_000, 500_000] {
@@ -216,14 +198,11 @@ pub fn bench_push(c: &mut Criterion) {
     let mut base_keys: Vec<String> = get_unique_random_strings(55_000, 987987);
     let base_values = get_unique_random_strings(55_000, 23423);
 
-    let extra_keys: Vec<_> = base_keys[50_000..].iter().cloned().collect();
+    let extra_keys: Vec<_> = base_keys[50_000..].to_vec();
     base_keys.truncate(50_000);
     let (base_values, extra_values) =
         generators::generate_worst_push_data(base_values, 5_000, 987987);
-    let extra: Vec<_> = extra_keys
-        .into_iter()
-        .zip(extra_values.into_iter())
-        .collect();
+    let extra: Vec<_> = extra_keys.into_iter().zip(extra_values).collect();
 
     let mut group = c.benchmark_group("binary_push_strings_worst");
     for &size in &[10_000, 20_000, 30_000, 40_000, 50_000] {
@@ -252,14 +231,11 @@ pub fn bench_push(c: &mut Criterion) {
     let mut base_keys: Vec<String> = get_unique_random_strings(55_000, 987987);
     let base_values = get_unique_random_strings(55_000, 23423);
 
-    let extra_keys: Vec<_> = base_keys[50_000..].iter().cloned().collect();
+    let extra_keys: Vec<_> = base_keys[50_000..].to_vec();
     base_keys.truncate(50_000);
     let (base_values, extra_values) =
         generators::generate_worst_push_data(base_values, 5_000, 987987);
-    let extra: Vec<_> = extra_keys
-        .into_iter()
-        .zip(extra_values.into_iter())
-        .collect();
+    let extra: Vec<_> = extra_keys.into_iter().zip(extra_values).collect();
 
     let mut group = c.benchmark_group("weak_push_strings_worst");
     for &size in &[10_000, 20_000, 30_000, 40_000, 50_000] {
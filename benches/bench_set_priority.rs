@@ -1,7 +1,10 @@
 extern crate criterion;
 
 use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
-use keyed_priority_queue::{KeyedBinaryPriorityQueue, KeyedWeakPriorityQueue};
+use keyed_priority_queue::{
+    KeyedBinaryPriorityQueue, KeyedRadixPriorityQueue, KeyedWeakPriorityQueue,
+};
+use std::cmp::Reverse;
 
 mod generators;
 use crate::generators::{choose_some, gen_random_usizes, get_random_strings};
@@ -76,6 +79,39 @@ pub fn bench_set_priority(c: &mut Criterion) {
 
     group.finish();
 
+    let mut group = c.benchmark_group("radix_set_priority_usize");
+    for &size in &[10_000, 500_000] {
+        assert!(base_keys.len() >= size);
+
+        let test_keys: Vec<_> = choose_some(&base_keys[..size], 500, 500);
+        let test_vals: Vec<_> = gen_random_usizes(500, 564);
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            let base_queue: KeyedRadixPriorityQueue<_, _> = base_keys[..size]
+                .iter()
+                .cloned()
+                .zip(base_values[..size].iter().cloned().map(Reverse))
+                .collect();
+            b.iter_batched(
+                || {
+                    (
+                        base_queue.clone(),
+                        Vec::<Result<Reverse<usize>, _>>::with_capacity(test_keys.len()),
+                    )
+                },
+                |(mut queue, mut results_store)| {
+                    for (&k, &v) in test_keys.iter().zip(test_vals.iter()) {
+                        results_store.push(queue.set_priority(&k, Reverse(v)));
+                    }
+                    (queue, results_store)
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+
+    group.finish();
+
     let mut group = c.benchmark_group("binary_set_priority_string");
     let base_keys = get_random_strings(50_000, 0);
     let base_values = get_random_strings(50_000, 7);
@@ -18,7 +18,6 @@ pub(crate) fn gen_random_usizes(n: usize, seed: u64) -> Vec<usize> {
 #[allow(dead_code)]
 pub(crate) fn get_random_strings(n: usize, seed: u64) -> Vec<String> {
     let alphabet: Vec<char> = (0u8..0x7f)
-        .into_iter()
         .filter(|x| x.is_ascii_alphanumeric())
         .map(|x| x as char)
         .collect();
@@ -38,7 +37,6 @@ pub(crate) fn get_unique_random_strings(n: usize, seed: u64) -> Vec<String> {
     use std::collections::HashSet;
 
     let alphabet: Vec<char> = (0u8..0x7f)
-        .into_iter()
         .filter(|x| x.is_ascii_alphanumeric())
         .map(|x| x as char)
         .collect();
@@ -0,0 +1,106 @@
+#![no_main]
+
+//! Runs arbitrary sequences of operations against both queues
+//! and compares results with simple `BTreeMap` based model.
+
+use arbitrary::Arbitrary;
+use keyed_priority_queue::{
+    EditableHeap, Entry, KeyedBinaryPriorityQueue, KeyedPriorityQueue, KeyedWeakPriorityQueue,
+};
+use libfuzzer_sys::fuzz_target;
+use std::collections::BTreeMap;
+
+#[derive(Arbitrary, Debug, Clone)]
+enum Operation {
+    Push(u8, u8),
+    Pop,
+    EntrySet(u8, u8),
+    EntryRemove(u8),
+    SetPriority(u8, u8),
+    GetMut(u8, u8),
+    Remove(u8),
+    Clear,
+    FromIter(Vec<(u8, u8)>),
+}
+
+fn run<THeap: EditableHeap<u8>>(
+    mut queue: KeyedPriorityQueue<u8, u8, THeap>,
+    operations: &[Operation],
+) {
+    let mut model: BTreeMap<u8, u8> = BTreeMap::new();
+
+    for operation in operations {
+        match operation.clone() {
+            Operation::Push(key, priority) => {
+                assert_eq!(queue.push(key, priority), model.insert(key, priority));
+            }
+            Operation::Pop => match queue.pop() {
+                Some((key, priority)) => {
+                    assert_eq!(Some(&priority), model.values().max());
+                    assert_eq!(model.remove(&key), Some(priority));
+                }
+                None => assert!(model.is_empty()),
+            },
+            Operation::EntrySet(key, priority) => match queue.entry(key) {
+                Entry::Occupied(entry) => {
+                    assert_eq!(
+                        Some(entry.set_priority(priority)),
+                        model.insert(key, priority)
+                    );
+                }
+                Entry::Vacant(entry) => {
+                    entry.set_priority(priority);
+                    assert_eq!(model.insert(key, priority), None);
+                }
+            },
+            Operation::EntryRemove(key) => match queue.entry(key) {
+                Entry::Occupied(entry) => {
+                    let (removed_key, priority) = entry.remove();
+                    assert_eq!(removed_key, key);
+                    assert_eq!(model.remove(&key), Some(priority));
+                }
+                Entry::Vacant(_) => assert!(!model.contains_key(&key)),
+            },
+            Operation::SetPriority(key, priority) => match model.get_mut(&key) {
+                Some(expected) => {
+                    assert_eq!(queue.set_priority(&key, priority), Ok(*expected));
+                    *expected = priority;
+                }
+                None => assert!(queue.set_priority(&key, priority).is_err()),
+            },
+            Operation::GetMut(key, priority) => match queue.get_mut(&key) {
+                Some(mut guard) => {
+                    assert_eq!(Some(&*guard), model.get(&key));
+                    *guard = priority;
+                    model.insert(key, priority);
+                }
+                None => assert!(!model.contains_key(&key)),
+            },
+            Operation::Remove(key) => {
+                assert_eq!(queue.remove(&key), model.remove(&key));
+            }
+            Operation::Clear => {
+                queue.clear();
+                model.clear();
+            }
+            Operation::FromIter(items) => {
+                queue = items.iter().cloned().collect();
+                model = items.into_iter().collect();
+            }
+        }
+
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.len(), model.len());
+        assert_eq!(queue.peek().map(|(_, &p)| p), model.values().max().cloned());
+    }
+
+    let mut rest: Vec<(u8, u8)> = queue.into_iter().collect();
+    assert!(rest.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    rest.sort_unstable();
+    assert_eq!(rest, model.into_iter().collect::<Vec<_>>());
+}
+
+fuzz_target!(|operations: Vec<Operation>| {
+    run(KeyedBinaryPriorityQueue::new(), &operations);
+    run(KeyedWeakPriorityQueue::new(), &operations);
+});
@@ -0,0 +1,548 @@
+use crate::heap_traits::EditableHeap;
+use crate::keyed_priority_queue::{Entry, KeyedPriorityQueue, SetPriorityNotFoundError};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+use std::time::{Duration, Instant};
+
+/// Policy which describes how priority of item grows while it waits in [`AgingKeyedPriorityQueue`].
+///
+/// Instead of recalculating priorities on every tick, policy converts base priority
+/// and moment of enqueueing into *anchored* priority: the priority which item would have
+/// at queue creation if it had aged since then with the same speed.
+/// Effective priorities of all items grow with the same function of time
+/// so their order never changes and anchored values can be compared directly.
+///
+/// For example, for linear aging `effective = base + rate * waited`
+/// anchored priority is `base - rate * enqueued`,
+/// and for exponential aging `effective = base * exp(rate * waited)`
+/// it is `ln(base) - rate * enqueued`.
+///
+/// Items enqueued later with the same base priority must not get bigger anchored priority,
+/// otherwise old items would starve.
+///
+/// [`AgingKeyedPriorityQueue`]: struct.AgingKeyedPriorityQueue.html
+pub trait Aging<TPriority> {
+    /// Time independent priority used to order items. Bigger values have more priority.
+    type Anchored: Ord;
+
+    /// Calculates anchored priority of item with `base` priority enqueued
+    /// after `enqueued` time passed since queue creation.
+    fn anchor(&self, base: &TPriority, enqueued: Duration) -> Self::Anchored;
+}
+
+/// Aging policy which increases priority by one every `period` of waiting.
+///
+/// Works with integer priorities which can be converted into `i64`.
+///
+/// # Examples
+///
+/// ```
+/// use keyed_priority_queue::{AgingKeyedBinaryPriorityQueue, LinearAging};
+/// use std::time::{Duration, Instant};
+///
+/// let start = Instant::now();
+/// let mut queue = AgingKeyedBinaryPriorityQueue::with_aging_at(
+///     LinearAging::new(Duration::from_secs(1)),
+///     start,
+/// );
+/// queue.push_at("old", 1i32, start);
+/// queue.push_at("new", 3i32, start + Duration::from_secs(5));
+/// // Old item waited 5 seconds so its effective priority is 6
+/// assert_eq!(queue.pop(), Some(("old", 1)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinearAging {
+    period_nanos: u64,
+}
+
+impl LinearAging {
+    /// Creates policy which increases priority by one every `period`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero or longer than `u64::MAX` nanoseconds.
+    pub fn new(period: Duration) -> Self {
+        let period_nanos = u64::try_from(period.as_nanos()).expect("Aging period is too long");
+        assert!(period_nanos > 0, "Aging period must be positive");
+        Self { period_nanos }
+    }
+}
+
+impl<TPriority: Copy + Into<i64>> Aging<TPriority> for LinearAging {
+    type Anchored = i128;
+
+    #[inline]
+    fn anchor(&self, base: &TPriority, enqueued: Duration) -> i128 {
+        let base: i64 = (*base).into();
+        i128::from(base) * i128::from(self.period_nanos) - enqueued.as_nanos() as i128
+    }
+}
+
+/// Priority stored in heap of [`AgingKeyedPriorityQueue`].
+///
+/// It is compared only by anchored priority.
+///
+/// [`AgingKeyedPriorityQueue`]: struct.AgingKeyedPriorityQueue.html
+#[derive(Debug, Clone, Copy)]
+pub struct Aged<TPriority, TAnchored> {
+    anchored: TAnchored,
+    base: TPriority,
+    enqueued: Duration,
+}
+
+impl<TPriority, TAnchored> Aged<TPriority, TAnchored> {
+    /// Base priority passed by user.
+    #[inline]
+    pub fn base(&self) -> &TPriority {
+        &self.base
+    }
+
+    /// Priority calculated by aging policy which is used for ordering.
+    #[inline]
+    pub fn anchored(&self) -> &TAnchored {
+        &self.anchored
+    }
+
+    /// Time passed since queue creation till item enqueueing.
+    #[inline]
+    pub fn enqueued(&self) -> Duration {
+        self.enqueued
+    }
+}
+
+impl<TPriority, TAnchored: Ord> PartialEq for Aged<TPriority, TAnchored> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.anchored == other.anchored
+    }
+}
+
+impl<TPriority, TAnchored: Ord> Eq for Aged<TPriority, TAnchored> {}
+
+impl<TPriority, TAnchored: Ord> PartialOrd for Aged<TPriority, TAnchored> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<TPriority, TAnchored: Ord> Ord for Aged<TPriority, TAnchored> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.anchored.cmp(&other.anchored)
+    }
+}
+
+type AgedOf<TPriority, TAging> = Aged<TPriority, <TAging as Aging<TPriority>>::Anchored>;
+
+/// A priority queue where priority of items grows while they wait.
+///
+/// Each item has base priority and moment of enqueueing,
+/// and [`Aging`] policy combines them into effective priority.
+/// It prevents starvation of items with low base priority.
+///
+/// Order of items never changes as time passes so queue doesn't touch items on every tick,
+/// all operations have the same complexity as in [`KeyedPriorityQueue`].
+///
+/// Changing priority of key keeps its moment of enqueueing.
+///
+/// [`Aging`]: trait.Aging.html
+/// [`KeyedPriorityQueue`]: struct.KeyedPriorityQueue.html
+///
+/// # Examples
+///
+/// ```
+/// use keyed_priority_queue::{AgingKeyedWeakPriorityQueue, LinearAging};
+/// use std::time::{Duration, Instant};
+///
+/// let start = Instant::now();
+/// // Priority increases by 1 every 10 seconds of waiting
+/// let mut queue = AgingKeyedWeakPriorityQueue::with_aging_at(
+///     LinearAging::new(Duration::from_secs(10)),
+///     start,
+/// );
+///
+/// queue.push_at("background", 0u8, start);
+/// let later = start + Duration::from_secs(35);
+/// queue.push_at("interactive", 3u8, later);
+/// queue.push_at("urgent", 5u8, later);
+///
+/// // Background job waited for 35 seconds so it has effective priority 3.5
+/// assert_eq!(queue.pop(), Some(("urgent", 5)));
+/// assert_eq!(queue.pop(), Some(("background", 0)));
+/// assert_eq!(queue.pop(), Some(("interactive", 3)));
+/// ```
+pub struct AgingKeyedPriorityQueue<TKey, TPriority, TAging, THeap, S = RandomState>
+where
+    TKey: Hash + Eq,
+    TAging: Aging<TPriority>,
+    THeap: EditableHeap<AgedOf<TPriority, TAging>>,
+    S: BuildHasher,
+{
+    queue: KeyedPriorityQueue<TKey, AgedOf<TPriority, TAging>, THeap, S>,
+    aging: TAging,
+    epoch: Instant,
+}
+
+impl<TKey, TPriority, TAging, THeap> AgingKeyedPriorityQueue<TKey, TPriority, TAging, THeap>
+where
+    TKey: Hash + Eq,
+    TAging: Aging<TPriority>,
+    THeap: EditableHeap<AgedOf<TPriority, TAging>>,
+{
+    /// Creates an empty queue with aging policy.
+    /// Waiting time is counted from current moment.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::{AgingKeyedBinaryPriorityQueue, LinearAging};
+    /// use std::time::Duration;
+    /// let mut queue = AgingKeyedBinaryPriorityQueue::with_aging(LinearAging::new(Duration::from_secs(1)));
+    /// queue.push("Key", 4);
+    /// ```
+    #[inline]
+    pub fn with_aging(aging: TAging) -> Self
+    where
+        THeap::Alloc: Default,
+    {
+        Self::with_aging_at(aging, Instant::now())
+    }
+
+    /// Creates an empty queue with aging policy.
+    /// Waiting time is counted from `epoch`.
+    #[inline]
+    pub fn with_aging_at(aging: TAging, epoch: Instant) -> Self
+    where
+        THeap::Alloc: Default,
+    {
+        Self::with_aging_and_hasher(aging, epoch, RandomState::default())
+    }
+}
+
+impl<TKey, TPriority, TAging, THeap, S> AgingKeyedPriorityQueue<TKey, TPriority, TAging, THeap, S>
+where
+    TKey: Hash + Eq,
+    TAging: Aging<TPriority>,
+    THeap: EditableHeap<AgedOf<TPriority, TAging>>,
+    S: BuildHasher,
+{
+    /// Creates an empty queue with aging policy and specific Hasher.
+    /// Waiting time is counted from `epoch`.
+    #[inline]
+    pub fn with_aging_and_hasher(aging: TAging, epoch: Instant, hasher: S) -> Self
+    where
+        THeap::Alloc: Default,
+    {
+        Self {
+            queue: KeyedPriorityQueue::with_hasher(hasher),
+            aging,
+            epoch,
+        }
+    }
+
+    /// Get the aging policy.
+    #[inline]
+    pub fn aging(&self) -> &TAging {
+        &self.aging
+    }
+
+    /// Adds new element enqueued at current moment.
+    /// If key exists, replaces its base priority but keeps moment of enqueueing.
+    /// Returns old base priority if key existed.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(log n)*** in average.
+    #[inline]
+    pub fn push(&mut self, key: TKey, priority: TPriority) -> Option<TPriority> {
+        self.push_at(key, priority, Instant::now())
+    }
+
+    /// Adds new element enqueued at `now`.
+    /// If key exists, replaces its base priority but keeps moment of enqueueing.
+    /// Returns old base priority if key existed.
+    ///
+    /// `now` must not be earlier than queue epoch, otherwise it is treated as epoch.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(log n)*** in average.
+    pub fn push_at(&mut self, key: TKey, priority: TPriority, now: Instant) -> Option<TPriority> {
+        match self.queue.entry(key) {
+            Entry::Occupied(entry) => {
+                let enqueued = entry.get_priority().enqueued;
+                let aged = make_aged(&self.aging, priority, enqueued);
+                Some(entry.set_priority(aged).base)
+            }
+            Entry::Vacant(entry) => {
+                let enqueued = now.saturating_duration_since(self.epoch);
+                entry.set_priority(make_aged(&self.aging, priority, enqueued));
+                None
+            }
+        }
+    }
+
+    /// Remove and return item with the maximal effective priority.
+    /// Returns base priority.
+    ///
+    /// ### Time complexity
+    ///
+    /// Cost of pop from underlying heap, ***O(log n)*** for binary heap.
+    #[inline]
+    pub fn pop(&mut self) -> Option<(TKey, TPriority)> {
+        self.queue.pop().map(|(key, aged)| (key, aged.base))
+    }
+
+    /// Get item with the maximal effective priority and its base priority.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn peek(&self) -> Option<(&TKey, &TPriority)> {
+        self.queue.peek().map(|(key, aged)| (key, &aged.base))
+    }
+
+    /// Get base priority of the key.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(1)*** in average.
+    #[inline]
+    pub fn get_priority<Q>(&self, key: &Q) -> Option<&TPriority>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.queue.get_priority(key).map(|aged| &aged.base)
+    }
+
+    /// Get base priority of the key together with its anchored priority and moment of enqueueing.
+    #[inline]
+    pub fn get_aged<Q>(&self, key: &Q) -> Option<&AgedOf<TPriority, TAging>>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.queue.get_priority(key)
+    }
+
+    /// Set new base priority for existing key and reorder the queue.
+    /// Moment of enqueueing stays the same.
+    /// Returns old base priority if succeeds or [`SetPriorityNotFoundError`].
+    ///
+    /// ### Time complexity
+    ///
+    /// In best case ***O(1)***, in average costs ***O(log n)***.
+    ///
+    /// [`SetPriorityNotFoundError`]: struct.SetPriorityNotFoundError.html
+    pub fn set_priority<Q>(
+        &mut self,
+        key: &Q,
+        priority: TPriority,
+    ) -> Result<TPriority, SetPriorityNotFoundError>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let enqueued = self
+            .queue
+            .get_priority(key)
+            .ok_or(SetPriorityNotFoundError {})?
+            .enqueued;
+        let aged = make_aged(&self.aging, priority, enqueued);
+        self.queue.set_priority(key, aged).map(|old| old.base)
+    }
+
+    /// Allow removing item by key.
+    /// Returns base priority if succeeds.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(log n)*** in average.
+    #[inline]
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<TPriority>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.queue.remove(key).map(|aged| aged.base)
+    }
+
+    /// Allow removing item by key.
+    /// Returns key and base priority if succeeds.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(log n)*** in average.
+    #[inline]
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(TKey, TPriority)>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.queue
+            .remove_entry(key)
+            .map(|(key, aged)| (key, aged.base))
+    }
+
+    /// Get the number of elements in queue.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns true if queue is empty.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Make the queue empty.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(n)***
+    #[inline]
+    pub fn clear(&mut self) {
+        self.queue.clear()
+    }
+
+    /// Returns iterator over keys and their base priorities in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&TKey, &TPriority)> + '_ {
+        self.queue.iter().map(|(key, aged)| (key, &aged.base))
+    }
+}
+
+impl<TKey, TPriority, TAging, THeap, S> Clone
+    for AgingKeyedPriorityQueue<TKey, TPriority, TAging, THeap, S>
+where
+    TKey: Hash + Eq + Clone,
+    TPriority: Clone,
+    TAging: Aging<TPriority> + Clone,
+    TAging::Anchored: Clone,
+    THeap: EditableHeap<AgedOf<TPriority, TAging>> + Clone,
+    S: BuildHasher + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            aging: self.aging.clone(),
+            epoch: self.epoch,
+        }
+    }
+}
+
+impl<TKey, TPriority, TAging, THeap, S> Debug
+    for AgingKeyedPriorityQueue<TKey, TPriority, TAging, THeap, S>
+where
+    TKey: Hash + Eq + Debug,
+    TPriority: Debug,
+    TAging: Aging<TPriority>,
+    THeap: EditableHeap<AgedOf<TPriority, TAging>>,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[inline]
+fn make_aged<TPriority, TAging: Aging<TPriority>>(
+    aging: &TAging,
+    base: TPriority,
+    enqueued: Duration,
+) -> AgedOf<TPriority, TAging> {
+    Aged {
+        anchored: aging.anchor(&base, enqueued),
+        base,
+        enqueued,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aging, AgingKeyedPriorityQueue, LinearAging};
+    use crate::editable_binary_heap::BinaryHeap;
+    use crate::editable_weak_heap::WeakHeap;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_linear_aging_order() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let aging = LinearAging::new(Duration::from_secs(2));
+        let mut queue =
+            AgingKeyedPriorityQueue::<_, u32, _, BinaryHeap<_>>::with_aging_at(aging, start);
+        // Effective priorities at time 10: a = 5, b = 4.5, c = 4, d = 5.5
+        assert_eq!(queue.push_at("a", 0, at(0)), None);
+        assert_eq!(queue.push_at("b", 1, at(3)), None);
+        assert_eq!(queue.push_at("c", 4, at(10)), None);
+        assert_eq!(queue.push_at("d", 3, at(5)), None);
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.peek(), Some((&"d", &3)));
+        assert_eq!(queue.pop(), Some(("d", 3)));
+        assert_eq!(queue.pop(), Some(("a", 0)));
+        assert_eq!(queue.pop(), Some(("b", 1)));
+        assert_eq!(queue.pop(), Some(("c", 4)));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_change_keeps_enqueue_time() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut queue = AgingKeyedPriorityQueue::<_, i64, _, WeakHeap<_>>::with_aging_at(
+            LinearAging::new(Duration::from_secs(1)),
+            start,
+        );
+        queue.push_at("old", 0, at(0));
+        queue.push_at("new", 5, at(10));
+        assert_eq!(queue.push_at("old", 6, at(20)), Some(0));
+        assert_eq!(queue.get_aged(&"old").unwrap().enqueued(), Duration::ZERO);
+        assert_eq!(queue.peek(), Some((&"old", &6)));
+        assert_eq!(queue.set_priority(&"old", 4), Ok(6));
+        assert_eq!(queue.get_priority(&"old"), Some(&4));
+        assert_eq!(queue.peek(), Some((&"old", &4)));
+        assert_eq!(queue.set_priority(&"old", -6), Ok(4));
+        assert_eq!(queue.peek(), Some((&"new", &5)));
+        assert!(queue.set_priority(&"missing", 2).is_err());
+        assert_eq!(queue.remove_entry(&"new"), Some(("new", 5)));
+        assert_eq!(queue.remove(&"old"), Some(-6));
+    }
+
+    #[test]
+    fn test_custom_aging() {
+        // Items enqueued in earlier minute always go first
+        struct Deadline;
+        impl Aging<u8> for Deadline {
+            type Anchored = (std::cmp::Reverse<u64>, u8);
+            fn anchor(&self, base: &u8, enqueued: Duration) -> Self::Anchored {
+                (std::cmp::Reverse(enqueued.as_secs() / 60), *base)
+            }
+        }
+        let start = Instant::now();
+        let mut queue =
+            AgingKeyedPriorityQueue::<_, _, _, BinaryHeap<_>>::with_aging_at(Deadline, start);
+        queue.push_at(1, 1u8, start);
+        queue.push_at(2, 9u8, start + Duration::from_secs(70));
+        queue.push_at(3, 5u8, start + Duration::from_secs(10));
+        assert_eq!(queue.pop(), Some((3, 5)));
+        assert_eq!(queue.pop(), Some((1, 1)));
+        assert_eq!(queue.pop(), Some((2, 9)));
+    }
+}
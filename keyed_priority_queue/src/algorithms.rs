@@ -0,0 +1,474 @@
+//! Graph algorithms built on top of [`KeyedPriorityQueue`].
+//!
+//! Graphs are described implicitly by closures which return neighbours of node with edge costs
+//! so they can be used with adjacency lists, grids or graphs generated on the fly.
+//! Algorithms use decrease-key operation of the queue so each node is stored in queue only once.
+//!
+//! Available only with `algorithms` feature.
+//!
+//! [`KeyedPriorityQueue`]: ../struct.KeyedPriorityQueue.html
+
+use crate::{Entry, KeyedBinaryPriorityQueue};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::ops::Add;
+
+/// Shortest paths from one node to all reachable nodes.
+/// Returned by [`dijkstra`].
+///
+/// [`dijkstra`]: fn.dijkstra.html
+#[derive(Debug, Clone)]
+pub struct ShortestPaths<TNode, TCost>
+where
+    TNode: Hash + Eq,
+{
+    start: TNode,
+    costs: HashMap<TNode, TCost>,
+    predecessors: HashMap<TNode, TNode>,
+}
+
+impl<TNode, TCost> ShortestPaths<TNode, TCost>
+where
+    TNode: Hash + Eq + Clone,
+    TCost: Copy,
+{
+    /// Node from which paths were searched.
+    #[inline]
+    pub fn start(&self) -> &TNode {
+        &self.start
+    }
+
+    /// Cost of the shortest path to `target` or `None` if it is unreachable.
+    #[inline]
+    pub fn cost(&self, target: &TNode) -> Option<TCost> {
+        self.costs.get(target).copied()
+    }
+
+    /// Shortest path from start to `target` including both ends
+    /// or `None` if it is unreachable.
+    pub fn path_to(&self, target: &TNode) -> Option<Vec<TNode>> {
+        if !self.costs.contains_key(target) {
+            return None;
+        }
+        Some(build_path(&self.predecessors, target.clone()))
+    }
+
+    /// Costs of the shortest paths to all reachable nodes.
+    #[inline]
+    pub fn costs(&self) -> &HashMap<TNode, TCost> {
+        &self.costs
+    }
+
+    /// Previous node in the shortest path for every reachable node except start.
+    #[inline]
+    pub fn predecessors(&self) -> &HashMap<TNode, TNode> {
+        &self.predecessors
+    }
+}
+
+/// Minimum spanning tree of connected component.
+/// Returned by [`prim_mst`].
+///
+/// [`prim_mst`]: fn.prim_mst.html
+#[derive(Debug, Clone)]
+pub struct SpanningTree<TNode, TCost> {
+    edges: Vec<(TNode, TNode, TCost)>,
+    total_cost: TCost,
+}
+
+impl<TNode, TCost: Copy> SpanningTree<TNode, TCost> {
+    /// Edges of tree as `(parent, child, cost)` in order of adding to tree.
+    #[inline]
+    pub fn edges(&self) -> &[(TNode, TNode, TCost)] {
+        &self.edges
+    }
+
+    /// Sum of costs of all edges in tree.
+    #[inline]
+    pub fn total_cost(&self) -> TCost {
+        self.total_cost
+    }
+
+    /// Returns edges of tree as `(parent, child, cost)`.
+    #[inline]
+    pub fn into_edges(self) -> Vec<(TNode, TNode, TCost)> {
+        self.edges
+    }
+}
+
+/// Finds shortest paths from `start` to all reachable nodes using [Dijkstra's algorithm][dijkstra].
+///
+/// `neighbors` returns nodes adjacent to passed one with costs of edges.
+/// Costs must not be negative, `TCost::default()` is used as zero.
+///
+/// [dijkstra]: https://en.wikipedia.org/wiki/Dijkstra%27s_algorithm
+///
+/// ### Examples
+///
+///
+/// ```
+/// use keyed_priority_queue::algorithms::dijkstra;
+///
+/// let edges = vec![
+///     vec![(1, 4), (2, 1)], // 0
+///     vec![(3, 1)],         // 1
+///     vec![(1, 2), (3, 5)], // 2
+///     vec![],               // 3
+/// ];
+/// let paths = dijkstra(0usize, |&node| edges[node].iter().copied());
+/// assert_eq!(paths.cost(&3), Some(4));
+/// assert_eq!(paths.path_to(&3), Some(vec![0, 2, 1, 3]));
+/// ```
+///
+/// ### Time complexity
+///
+/// ***O((V + E) log V)***
+pub fn dijkstra<TNode, TCost, FNeighbors, IEdges>(
+    start: TNode,
+    mut neighbors: FNeighbors,
+) -> ShortestPaths<TNode, TCost>
+where
+    TNode: Hash + Eq + Clone,
+    TCost: Ord + Copy + Add<Output = TCost> + Default,
+    FNeighbors: FnMut(&TNode) -> IEdges,
+    IEdges: IntoIterator<Item = (TNode, TCost)>,
+{
+    let mut costs = HashMap::new();
+    let mut predecessors = HashMap::new();
+    let mut queue = KeyedBinaryPriorityQueue::new();
+    costs.insert(start.clone(), TCost::default());
+    queue.push(start.clone(), Reverse(TCost::default()));
+
+    while let Some((node, Reverse(cost))) = queue.pop() {
+        for (next, edge_cost) in neighbors(&node) {
+            let next_cost = cost + edge_cost;
+            if costs.get(&next).map_or(true, |&old| next_cost < old) {
+                costs.insert(next.clone(), next_cost);
+                predecessors.insert(next.clone(), node.clone());
+                // Decreases priority if node is already in queue
+                queue.push(next, Reverse(next_cost));
+            }
+        }
+    }
+
+    ShortestPaths {
+        start,
+        costs,
+        predecessors,
+    }
+}
+
+/// Finds shortest path from `start` to any node for which `is_goal` returns true
+/// using [A* algorithm][a_star].
+///
+/// `neighbors` returns nodes adjacent to passed one with costs of edges.
+/// `heuristic` estimates cost of path from node to goal, it must not overestimate it.
+/// Costs must not be negative, `TCost::default()` is used as zero.
+///
+/// Returns path including both ends with its cost or `None` if goal is unreachable.
+///
+/// [a_star]: https://en.wikipedia.org/wiki/A*_search_algorithm
+///
+/// ### Examples
+///
+///
+/// ```
+/// use keyed_priority_queue::algorithms::astar;
+///
+/// // Grid 4x4 where moving to cell costs its value
+/// let costs = [
+///     [1, 3, 3, 6],
+///     [4, 4, 3, 8],
+///     [3, 1, 2, 4],
+///     [4, 8, 9, 4],
+/// ];
+/// let target = (3usize, 3usize);
+/// let (path, cost) = astar(
+///     (0usize, 0usize),
+///     |&(row, column)| {
+///         let mut next = Vec::new();
+///         if row > 0 { next.push((row - 1, column)); }
+///         if row < 3 { next.push((row + 1, column)); }
+///         if column > 0 { next.push((row, column - 1)); }
+///         if column < 3 { next.push((row, column + 1)); }
+///         next.into_iter().map(|(r, c)| ((r, c), costs[r][c]))
+///     },
+///     |&(row, column)| ((target.0 - row) + (target.1 - column)) as u32,
+///     |&node| node == target,
+/// )
+/// .unwrap();
+/// assert_eq!(cost, 18);
+/// assert_eq!(path.first(), Some(&(0, 0)));
+/// assert_eq!(path.last(), Some(&target));
+/// ```
+///
+/// ### Time complexity
+///
+/// In worst case same as [`dijkstra`], good heuristic significally reduces number of visited nodes.
+///
+/// [`dijkstra`]: fn.dijkstra.html
+pub fn astar<TNode, TCost, FNeighbors, IEdges, FHeuristic, FGoal>(
+    start: TNode,
+    mut neighbors: FNeighbors,
+    mut heuristic: FHeuristic,
+    mut is_goal: FGoal,
+) -> Option<(Vec<TNode>, TCost)>
+where
+    TNode: Hash + Eq + Clone,
+    TCost: Ord + Copy + Add<Output = TCost> + Default,
+    FNeighbors: FnMut(&TNode) -> IEdges,
+    IEdges: IntoIterator<Item = (TNode, TCost)>,
+    FHeuristic: FnMut(&TNode) -> TCost,
+    FGoal: FnMut(&TNode) -> bool,
+{
+    let mut costs = HashMap::new();
+    let mut predecessors = HashMap::new();
+    // Nodes with lower estimated total cost go first,
+    // if they are same, ones with lower real cost.
+    let mut queue = KeyedBinaryPriorityQueue::new();
+    costs.insert(start.clone(), TCost::default());
+    let start_estimate = heuristic(&start);
+    queue.push(start, Reverse((start_estimate, TCost::default())));
+
+    while let Some((node, Reverse((_, cost)))) = queue.pop() {
+        if is_goal(&node) {
+            return Some((build_path(&predecessors, node), cost));
+        }
+        for (next, edge_cost) in neighbors(&node) {
+            let next_cost = cost + edge_cost;
+            // Node is reopened if better path is found after it was visited
+            // so inconsistent heuristics are supported too.
+            if costs.get(&next).map_or(true, |&old| next_cost < old) {
+                costs.insert(next.clone(), next_cost);
+                predecessors.insert(next.clone(), node.clone());
+                let estimate = next_cost + heuristic(&next);
+                queue.push(next, Reverse((estimate, next_cost)));
+            }
+        }
+    }
+    None
+}
+
+/// Finds minimum spanning tree of connected component which contains `start`
+/// using [Prim's algorithm][prim].
+///
+/// `neighbors` returns nodes adjacent to passed one with costs of edges.
+/// Graph must be undirected: every edge should be returned for both its ends.
+/// `TCost::default()` is used as zero.
+///
+/// [prim]: https://en.wikipedia.org/wiki/Prim%27s_algorithm
+///
+/// ### Examples
+///
+///
+/// ```
+/// use keyed_priority_queue::algorithms::prim_mst;
+///
+/// let edges = [(0, 1, 4), (0, 2, 1), (1, 2, 2), (1, 3, 5), (2, 3, 8)];
+/// let tree = prim_mst(0, |&node| {
+///     edges.iter().filter_map(move |&(a, b, cost)| {
+///         if a == node {
+///             Some((b, cost))
+///         } else if b == node {
+///             Some((a, cost))
+///         } else {
+///             None
+///         }
+///     })
+/// });
+/// assert_eq!(tree.total_cost(), 8);
+/// assert_eq!(tree.edges(), &[(0, 2, 1), (2, 1, 2), (1, 3, 5)]);
+/// ```
+///
+/// ### Time complexity
+///
+/// ***O((V + E) log V)***
+pub fn prim_mst<TNode, TCost, FNeighbors, IEdges>(
+    start: TNode,
+    mut neighbors: FNeighbors,
+) -> SpanningTree<TNode, TCost>
+where
+    TNode: Hash + Eq + Clone,
+    TCost: Ord + Copy + Add<Output = TCost> + Default,
+    FNeighbors: FnMut(&TNode) -> IEdges,
+    IEdges: IntoIterator<Item = (TNode, TCost)>,
+{
+    let mut in_tree = HashSet::new();
+    // Best known edge to tree for every node in queue
+    let mut parents: HashMap<TNode, TNode> = HashMap::new();
+    let mut edges = Vec::new();
+    let mut total_cost = TCost::default();
+    let mut queue = KeyedBinaryPriorityQueue::new();
+    queue.push(start, Reverse(TCost::default()));
+
+    while let Some((node, Reverse(cost))) = queue.pop() {
+        if let Some(parent) = parents.remove(&node) {
+            edges.push((parent, node.clone(), cost));
+            total_cost = total_cost + cost;
+        }
+        for (next, edge_cost) in neighbors(&node) {
+            if in_tree.contains(&next) || next == node {
+                continue;
+            }
+            // Entire interaction makes only one lookup in queue
+            match queue.entry(next) {
+                Entry::Vacant(entry) => {
+                    parents.insert(entry.get_key().clone(), node.clone());
+                    entry.set_priority(Reverse(edge_cost));
+                }
+                Entry::Occupied(entry) if *entry.get_priority() < Reverse(edge_cost) => {
+                    parents.insert(entry.get_key().clone(), node.clone());
+                    entry.set_priority(Reverse(edge_cost));
+                }
+                Entry::Occupied(_) => {}
+            }
+        }
+        in_tree.insert(node);
+    }
+
+    SpanningTree { edges, total_cost }
+}
+
+fn build_path<TNode>(predecessors: &HashMap<TNode, TNode>, target: TNode) -> Vec<TNode>
+where
+    TNode: Hash + Eq + Clone,
+{
+    let mut path = vec![target];
+    while let Some(previous) = predecessors.get(path.last().unwrap()) {
+        path.push(previous.clone());
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{astar, dijkstra, prim_mst};
+
+    type Cell = (usize, usize);
+
+    fn grid_neighbors(costs: &[Vec<u32>]) -> impl FnMut(&Cell) -> Vec<(Cell, u32)> + '_ {
+        move |&(row, column)| {
+            let mut next = Vec::new();
+            if row > 0 {
+                next.push((row - 1, column));
+            }
+            if row + 1 < costs.len() {
+                next.push((row + 1, column));
+            }
+            if column > 0 {
+                next.push((row, column - 1));
+            }
+            if column + 1 < costs[row].len() {
+                next.push((row, column + 1));
+            }
+            next.into_iter()
+                .map(|(r, c)| ((r, c), costs[r][c]))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_dijkstra() {
+        let edges: Vec<Vec<(usize, u64)>> = vec![
+            vec![(1, 7), (2, 9), (5, 14)],
+            vec![(0, 7), (2, 10), (3, 15)],
+            vec![(0, 9), (1, 10), (3, 11), (5, 2)],
+            vec![(1, 15), (2, 11), (4, 6)],
+            vec![(3, 6), (5, 9)],
+            vec![(0, 14), (2, 2), (4, 9)],
+            // Unreachable
+            vec![(0, 1)],
+        ];
+        let paths = dijkstra(0, |&node| edges[node].clone());
+        let costs: Vec<_> = (0..7).map(|node| paths.cost(&node)).collect();
+        assert_eq!(
+            costs,
+            vec![
+                Some(0),
+                Some(7),
+                Some(9),
+                Some(20),
+                Some(20),
+                Some(11),
+                None
+            ]
+        );
+        assert_eq!(paths.path_to(&4), Some(vec![0, 2, 5, 4]));
+        assert_eq!(paths.path_to(&0), Some(vec![0]));
+        assert_eq!(paths.path_to(&6), None);
+        assert_eq!(paths.predecessors().get(&3), Some(&2));
+        assert_eq!(paths.start(), &0);
+    }
+
+    #[test]
+    fn test_astar_matches_dijkstra() {
+        let costs: Vec<Vec<u32>> = (0..10)
+            .map(|row| {
+                (0..10)
+                    .map(|column| (row * 7 + column * 13) % 10 + 1)
+                    .collect()
+            })
+            .collect();
+        let target = (9, 9);
+        let paths = dijkstra((0, 0), grid_neighbors(&costs));
+        let (path, cost) = astar(
+            (0, 0),
+            grid_neighbors(&costs),
+            |&(row, column)| ((target.0 - row) + (target.1 - column)) as u32,
+            |&node| node == target,
+        )
+        .unwrap();
+        assert_eq!(Some(cost), paths.cost(&target));
+        let path_cost: u32 = path[1..].iter().map(|&(r, c)| costs[r][c]).sum();
+        assert_eq!(path_cost, cost);
+        assert_eq!(path.first(), Some(&(0, 0)));
+        assert_eq!(path.last(), Some(&target));
+
+        let unreachable = astar((0, 0), grid_neighbors(&costs), |_| 0, |_| false);
+        assert_eq!(unreachable, None);
+    }
+
+    #[test]
+    fn test_prim() {
+        let edges = [
+            ('a', 'b', 7),
+            ('a', 'd', 5),
+            ('b', 'c', 8),
+            ('b', 'd', 9),
+            ('b', 'e', 7),
+            ('c', 'e', 5),
+            ('d', 'e', 15),
+            ('d', 'f', 6),
+            ('e', 'f', 8),
+            ('e', 'g', 9),
+            ('f', 'g', 11),
+        ];
+        let tree = prim_mst('a', |&node| {
+            edges
+                .iter()
+                .filter(move |&&(a, b, _)| a == node || b == node)
+                .map(move |&(a, b, cost)| (if a == node { b } else { a }, cost))
+        });
+        assert_eq!(tree.total_cost(), 39);
+        assert_eq!(tree.edges().len(), 6);
+        let mut tree_edges: Vec<_> = tree
+            .into_edges()
+            .into_iter()
+            .map(|(a, b, _)| if a < b { (a, b) } else { (b, a) })
+            .collect();
+        tree_edges.sort_unstable();
+        assert_eq!(
+            tree_edges,
+            vec![
+                ('a', 'b'),
+                ('a', 'd'),
+                ('b', 'e'),
+                ('c', 'e'),
+                ('d', 'f'),
+                ('e', 'g')
+            ]
+        );
+    }
+}
@@ -0,0 +1,866 @@
+use crate::errors::{InvariantError, SetPriorityNotFoundError};
+use crate::make_hash;
+use core::borrow::Borrow;
+use core::fmt::{self, Debug, Display};
+use core::hash::{BuildHasher, Hash};
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+
+/// Key with its hash and position in heap.
+/// Slots are kept dense so slot of the last key is moved in place of removed one.
+#[derive(Clone)]
+struct ArraySlot<TKey> {
+    key: TKey,
+    // Saved to avoid rehashing keys when their slots are moved
+    hash: u64,
+    heap_idx: usize,
+}
+
+#[derive(Clone)]
+struct ArrayHeapEntry<TPriority> {
+    slot: usize,
+    priority: TPriority,
+}
+
+/// A priority queue with fixed capacity `N` which keeps all data inline and never allocates.
+///
+/// It works like [`KeyedPriorityQueue`] with binary heap but both heap and index of keys
+/// are arrays of length `N`. Keys are found using open addressing hash table
+/// with linear probing so lookups of missing keys may scan whole table when queue is almost full.
+///
+/// [`push`] of new key returns [`CapacityError`] with passed item when queue is full.
+///
+/// Unlike other queues `N` must be chosen at compile time,
+/// so queue is suitable for environments without allocator, e.g. schedulers of firmware.
+/// It is the only queue available without `std` feature,
+/// in this case hasher must be passed to [`with_hasher`] because there is no default one.
+///
+/// [`KeyedPriorityQueue`]: struct.KeyedPriorityQueue.html
+/// [`push`]: struct.ArrayKeyedPriorityQueue.html#method.push
+/// [`CapacityError`]: struct.CapacityError.html
+/// [`with_hasher`]: struct.ArrayKeyedPriorityQueue.html#method.with_hasher
+///
+/// # Examples
+///
+/// ```
+/// use keyed_priority_queue::{ArrayKeyedPriorityQueue, CapacityError};
+///
+/// let mut tasks: ArrayKeyedPriorityQueue<&str, u8, 2> = ArrayKeyedPriorityQueue::new();
+/// assert_eq!(tasks.push("blink", 1), Ok(None));
+/// assert_eq!(tasks.push("sensor", 5), Ok(None));
+/// assert!(tasks.is_full());
+///
+/// // New keys don't fit but priorities of queued ones can be changed
+/// assert_eq!(tasks.push("radio", 3), Err(CapacityError(("radio", 3))));
+/// assert_eq!(tasks.push("blink", 7), Ok(Some(1)));
+///
+/// assert_eq!(tasks.pop(), Some(("blink", 7)));
+/// assert_eq!(tasks.push("radio", 3), Ok(None));
+/// assert_eq!(tasks.pop(), Some(("sensor", 5)));
+/// assert_eq!(tasks.pop(), Some(("radio", 3)));
+/// assert_eq!(tasks.pop(), None);
+/// ```
+#[derive(Clone)]
+pub struct ArrayKeyedPriorityQueue<
+    TKey,
+    TPriority,
+    const N: usize,
+    #[cfg(feature = "std")] S = RandomState,
+    #[cfg(not(feature = "std"))] S,
+> where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    // Items of binary heap, first `len` are occupied
+    heap: [Option<ArrayHeapEntry<TPriority>>; N],
+    // Keys in arbitrary order, first `len` are occupied
+    slots: [Option<ArraySlot<TKey>>; N],
+    // Indexes of slots placed by hashes of their keys
+    table: [Option<usize>; N],
+    len: usize,
+    hasher: S,
+}
+
+/// This is error type for [`push`] method of [`ArrayKeyedPriorityQueue`].
+/// Contains item which wasn't pushed because queue is full.
+///
+/// [`ArrayKeyedPriorityQueue`]: struct.ArrayKeyedPriorityQueue.html
+/// [`push`]: struct.ArrayKeyedPriorityQueue.html#method.push
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Default)]
+pub struct CapacityError<T>(pub T);
+
+impl<T> Debug for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.write_str("CapacityError { .. }")
+    }
+}
+
+impl<T> Display for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.write_str("Pushing new key to full ArrayKeyedPriorityQueue")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> std::error::Error for CapacityError<T> {}
+
+#[cfg(feature = "std")]
+impl<TKey: Hash + Eq, TPriority: Ord, const N: usize>
+    ArrayKeyedPriorityQueue<TKey, TPriority, N, RandomState>
+{
+    /// Creates an empty queue which can keep up to `N` items.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ArrayKeyedPriorityQueue;
+    /// let mut queue = ArrayKeyedPriorityQueue::<_, _, 8>::new();
+    /// queue.push("Key", 4).unwrap();
+    /// ```
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::default())
+    }
+}
+
+impl<TKey, TPriority, const N: usize, S> ArrayKeyedPriorityQueue<TKey, TPriority, N, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    /// Creates an empty queue with specific Hasher.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ArrayKeyedPriorityQueue;
+    /// use std::collections::hash_map::RandomState;
+    /// let mut queue = ArrayKeyedPriorityQueue::<_, _, 8>::with_hasher(RandomState::default());
+    /// queue.push("Key", 4).unwrap();
+    /// ```
+    pub fn with_hasher(hasher: S) -> Self {
+        Self {
+            heap: core::array::from_fn(|_| None),
+            slots: core::array::from_fn(|_| None),
+            table: [None; N],
+            len: 0,
+            hasher,
+        }
+    }
+
+    /// Get the maximal number of items in queue which is always `N`.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Adds new item to queue if missing key or replace its priority if key exists.
+    /// In second case returns old priority.
+    ///
+    /// If key is missing and queue is full, returns [`CapacityError`] with passed key and priority.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::{ArrayKeyedPriorityQueue, CapacityError};
+    /// let mut queue = ArrayKeyedPriorityQueue::<_, _, 1>::new();
+    /// assert_eq!(queue.push("First", 5), Ok(None));
+    /// assert_eq!(queue.push("First", 3), Ok(Some(5)));
+    /// assert_eq!(queue.push("Second", 4), Err(CapacityError(("Second", 4))));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(log n)*** in average.
+    ///
+    /// [`CapacityError`]: struct.CapacityError.html
+    pub fn push(
+        &mut self,
+        key: TKey,
+        priority: TPriority,
+    ) -> Result<Option<TPriority>, CapacityError<(TKey, TPriority)>> {
+        let hash = make_hash(&self.hasher, &key);
+        if let Some((_, slot)) = self.find(hash, &key) {
+            let heap_idx = self.slot(slot).heap_idx;
+            return Ok(Some(self.change_priority(heap_idx, priority)));
+        }
+        if self.len == N {
+            return Err(CapacityError((key, priority)));
+        }
+
+        let position = self.len;
+        let mut table_pos = self.ideal_table_pos(hash);
+        while self.table[table_pos].is_some() {
+            table_pos = (table_pos + 1) % N;
+        }
+        self.table[table_pos] = Some(position);
+        self.slots[position] = Some(ArraySlot {
+            key,
+            hash,
+            heap_idx: position,
+        });
+        self.heap[position] = Some(ArrayHeapEntry {
+            slot: position,
+            priority,
+        });
+        self.len += 1;
+        self.heapify_up(position);
+        Ok(None)
+    }
+
+    /// Remove and return item with the maximal priority.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ArrayKeyedPriorityQueue;
+    /// let mut queue = ArrayKeyedPriorityQueue::<_, _, 4>::new();
+    /// queue.push(0, 0).unwrap();
+    /// queue.push(1, 1).unwrap();
+    /// assert_eq!(queue.pop(), Some((1, 1)));
+    /// assert_eq!(queue.pop(), Some((0, 0)));
+    /// assert_eq!(queue.pop(), None);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Cost of pop is always ***O(log n)***
+    #[inline]
+    pub fn pop(&mut self) -> Option<(TKey, TPriority)> {
+        if self.len == 0 {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    /// Get reference to the pair with the maximal priority.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ArrayKeyedPriorityQueue;
+    /// let mut queue = ArrayKeyedPriorityQueue::<_, _, 4>::new();
+    /// queue.push(0, 0).unwrap();
+    /// queue.push(1, 1).unwrap();
+    /// assert_eq!(queue.peek(), Some((&1, &1)));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn peek(&self) -> Option<(&TKey, &TPriority)> {
+        if self.len == 0 {
+            return None;
+        }
+        let entry = self.entry(0);
+        Some((&self.slot(entry.slot).key, &entry.priority))
+    }
+
+    /// Get reference to the priority by key.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ArrayKeyedPriorityQueue;
+    /// let mut queue = ArrayKeyedPriorityQueue::<_, _, 4>::new();
+    /// queue.push("first", 0).unwrap();
+    /// assert_eq!(queue.get_priority(&"first"), Some(&0));
+    /// assert_eq!(queue.get_priority(&"second"), None);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(1)*** in average (limited by hash table key lookup).
+    pub fn get_priority<Q>(&self, key: &Q) -> Option<&TPriority>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (_, slot) = self.find(make_hash(&self.hasher, key), key)?;
+        Some(&self.entry(self.slot(slot).heap_idx).priority)
+    }
+
+    /// Set new priority for existing key and reorder the queue.
+    /// Returns old priority if succeeds or [`SetPriorityNotFoundError`].
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::{ArrayKeyedPriorityQueue, SetPriorityNotFoundError};
+    /// let mut queue = ArrayKeyedPriorityQueue::<_, _, 4>::new();
+    /// queue.push("first", 0).unwrap();
+    /// queue.push("second", 1).unwrap();
+    /// assert_eq!(queue.set_priority(&"first", 5), Ok(0));
+    /// assert_eq!(queue.pop(), Some(("first", 5)));
+    /// assert_eq!(queue.set_priority(&"Missing", 5), Err(SetPriorityNotFoundError{}));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// In best case ***O(1)***, in average costs ***O(log n)***.
+    ///
+    /// [`SetPriorityNotFoundError`]: struct.SetPriorityNotFoundError.html
+    pub fn set_priority<Q>(
+        &mut self,
+        key: &Q,
+        priority: TPriority,
+    ) -> Result<TPriority, SetPriorityNotFoundError>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (_, slot) = self
+            .find(make_hash(&self.hasher, key), key)
+            .ok_or(SetPriorityNotFoundError {})?;
+        let heap_idx = self.slot(slot).heap_idx;
+        Ok(self.change_priority(heap_idx, priority))
+    }
+
+    /// Allow removing item by key.
+    /// Returns priority if succeeds.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ArrayKeyedPriorityQueue;
+    /// let mut queue = ArrayKeyedPriorityQueue::<_, _, 4>::new();
+    /// queue.push(1, 1).unwrap();
+    /// queue.push(2, 2).unwrap();
+    /// assert_eq!(queue.remove(&2), Some(2));
+    /// assert_eq!(queue.remove(&2), None);
+    /// assert_eq!(queue.pop(), Some((1, 1)));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// On average the function will require ***O(log n)*** operations.
+    #[inline]
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<TPriority>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, priority)| priority)
+    }
+
+    /// Allow removing item by key.
+    /// Returns key and priority if succeeds.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ArrayKeyedPriorityQueue;
+    /// let mut queue = ArrayKeyedPriorityQueue::<_, _, 4>::new();
+    /// queue.push(1, 1).unwrap();
+    /// assert_eq!(queue.remove_entry(&1), Some((1, 1)));
+    /// assert_eq!(queue.remove_entry(&1), None);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// On average the function will require ***O(log n)*** operations.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(TKey, TPriority)>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (_, slot) = self.find(make_hash(&self.hasher, key), key)?;
+        let heap_idx = self.slot(slot).heap_idx;
+        Some(self.remove_at(heap_idx))
+    }
+
+    /// Get the number of items in queue.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if queue has no items.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if queue has `N` items so new keys can't be pushed.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Make the queue empty.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(N)***
+    pub fn clear(&mut self) {
+        for item in self.heap[..self.len].iter_mut() {
+            *item = None;
+        }
+        for slot in self.slots[..self.len].iter_mut() {
+            *slot = None;
+        }
+        self.table = [None; N];
+        self.len = 0;
+    }
+
+    /// Create readonly borrowing iterator over items in arbitrary order.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ArrayKeyedPriorityQueue;
+    /// let mut queue = ArrayKeyedPriorityQueue::<_, _, 4>::new();
+    /// queue.push("first", 0).unwrap();
+    /// queue.push("second", 1).unwrap();
+    /// let mut items: Vec<_> = queue.iter().collect();
+    /// items.sort();
+    /// assert_eq!(items, vec![(&"first", &0), (&"second", &1)]);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Iterating over whole queue is ***O(n)***
+    pub fn iter(&self) -> ArrayKeyedPriorityQueueIter<'_, TKey, TPriority> {
+        ArrayKeyedPriorityQueueIter {
+            slots: self.slots[..self.len].iter(),
+            heap: &self.heap[..self.len],
+        }
+    }
+
+    /// Checks internal invariants of the queue.
+    /// Returns first found violation as [`InvariantError`].
+    ///
+    /// It is intended for tests and debugging.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(N)***
+    ///
+    /// [`InvariantError`]: enum.InvariantError.html
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        let keys = self.table.iter().filter(|x| x.is_some()).count();
+        if keys != self.len {
+            return Err(InvariantError::LengthMismatch {
+                keys,
+                heap: self.len,
+            });
+        }
+        for (key_index, slot) in self.slots[..self.len].iter().enumerate() {
+            let heap_index = slot.as_ref().ok_or(InvariantError::HeapStructure)?.heap_idx;
+            let entry = self
+                .heap
+                .get(heap_index)
+                .filter(|_| heap_index < self.len)
+                .and_then(Option::as_ref)
+                .ok_or(InvariantError::IndexOutOfBounds {
+                    key_index,
+                    heap_index,
+                })?;
+            if entry.slot != key_index {
+                return Err(InvariantError::BrokenBackReference {
+                    key_index,
+                    heap_index,
+                    back_index: entry.slot,
+                });
+            }
+        }
+        for child in 1..self.len {
+            let parent = (child - 1) / 2;
+            if self.entry(parent).priority < self.entry(child).priority {
+                return Err(InvariantError::HeapOrder { parent, child });
+            }
+        }
+        // Every key must be reachable from its ideal position without gaps
+        for (table_pos, stored) in self.table.iter().enumerate() {
+            let slot = match *stored {
+                Some(slot) if slot < self.len => slot,
+                Some(_) => return Err(InvariantError::HeapStructure),
+                None => continue,
+            };
+            let mut probe = self.ideal_table_pos(self.slot(slot).hash);
+            while probe != table_pos {
+                if self.table[probe].is_none() {
+                    return Err(InvariantError::HeapStructure);
+                }
+                probe = (probe + 1) % N;
+            }
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn entry(&self, position: usize) -> &ArrayHeapEntry<TPriority> {
+        self.heap[position]
+            .as_ref()
+            .expect("Heap positions below len are occupied")
+    }
+
+    #[inline(always)]
+    fn slot(&self, index: usize) -> &ArraySlot<TKey> {
+        self.slots[index]
+            .as_ref()
+            .expect("Slots below len are occupied")
+    }
+
+    #[inline(always)]
+    fn slot_mut(&mut self, index: usize) -> &mut ArraySlot<TKey> {
+        self.slots[index]
+            .as_mut()
+            .expect("Slots below len are occupied")
+    }
+
+    #[inline(always)]
+    fn ideal_table_pos(&self, hash: u64) -> usize {
+        (hash % N as u64) as usize
+    }
+
+    /// Returns position in table and index of slot of the key
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<(usize, usize)>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if N == 0 {
+            return None;
+        }
+        let start = self.ideal_table_pos(hash);
+        for offset in 0..N {
+            let table_pos = (start + offset) % N;
+            let slot = self.table[table_pos]?;
+            let stored = self.slot(slot);
+            if stored.hash == hash && stored.key.borrow() == key {
+                return Some((table_pos, slot));
+            }
+        }
+        None
+    }
+
+    // Every swap updates positions in slots at once
+    // so panic in comparison of priorities leaves queue consistent
+    fn swap_positions(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        for &position in [a, b].iter() {
+            let slot = self.entry(position).slot;
+            self.slot_mut(slot).heap_idx = position;
+        }
+    }
+
+    fn heapify_up(&mut self, mut position: usize) {
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if self.entry(parent).priority >= self.entry(position).priority {
+                break;
+            }
+            self.swap_positions(parent, position);
+            position = parent;
+        }
+    }
+
+    fn heapify_down(&mut self, mut position: usize) {
+        loop {
+            let child1 = position * 2 + 1;
+            let child2 = child1 + 1;
+            if child1 >= self.len {
+                break;
+            }
+            let max_child = if child2 < self.len
+                && self.entry(child1).priority <= self.entry(child2).priority
+            {
+                child2
+            } else {
+                child1
+            };
+            if self.entry(position).priority >= self.entry(max_child).priority {
+                break;
+            }
+            self.swap_positions(position, max_child);
+            position = max_child;
+        }
+    }
+
+    fn restore_order(&mut self, position: usize) {
+        if position > 0 && self.entry((position - 1) / 2).priority < self.entry(position).priority {
+            self.heapify_up(position);
+        } else {
+            self.heapify_down(position);
+        }
+    }
+
+    fn change_priority(&mut self, position: usize, priority: TPriority) -> TPriority {
+        let entry = self.heap[position]
+            .as_mut()
+            .expect("Heap positions below len are occupied");
+        let old = core::mem::replace(&mut entry.priority, priority);
+        self.restore_order(position);
+        old
+    }
+
+    fn remove_at(&mut self, position: usize) -> (TKey, TPriority) {
+        let last = self.len - 1;
+        self.swap_positions(position, last);
+        let ArrayHeapEntry { slot, priority } = self.heap[last]
+            .take()
+            .expect("Heap positions below len are occupied");
+        let (table_pos, _) = self
+            .find_slot(slot)
+            .expect("All occupied slots are in table");
+        self.remove_from_table(table_pos);
+        let ArraySlot { key, .. } = self.slots[slot]
+            .take()
+            .expect("Slots below len are occupied");
+        // Keep slots dense by moving the last one in place of removed
+        if slot != last {
+            let (table_pos, _) = self
+                .find_slot(last)
+                .expect("All occupied slots are in table");
+            self.table[table_pos] = Some(slot);
+            self.slots.swap(slot, last);
+            let heap_idx = self.slot(slot).heap_idx;
+            self.heap[heap_idx]
+                .as_mut()
+                .expect("Heap positions below len are occupied")
+                .slot = slot;
+        }
+        self.len = last;
+        if position != last {
+            self.restore_order(position);
+        }
+        (key, priority)
+    }
+
+    fn find_slot(&self, slot: usize) -> Option<(usize, usize)> {
+        let start = self.ideal_table_pos(self.slot(slot).hash);
+        (0..N)
+            .map(|offset| (start + offset) % N)
+            .find(|&table_pos| self.table[table_pos] == Some(slot))
+            .map(|table_pos| (table_pos, slot))
+    }
+
+    // Shifts following keys back to keep them reachable from their ideal positions
+    fn remove_from_table(&mut self, table_pos: usize) {
+        self.table[table_pos] = None;
+        let mut hole = table_pos;
+        let mut next = (hole + 1) % N;
+        while let Some(slot) = self.table[next] {
+            let ideal = self.ideal_table_pos(self.slot(slot).hash);
+            // Key can be moved to hole if hole is between its ideal position and current one
+            if (next + N - ideal) % N >= (next + N - hole) % N {
+                self.table[hole] = self.table[next].take();
+                hole = next;
+            }
+            next = (next + 1) % N;
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<TKey, TPriority, const N: usize> Default
+    for ArrayKeyedPriorityQueue<TKey, TPriority, N, RandomState>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<TKey, TPriority, const N: usize, S> Debug for ArrayKeyedPriorityQueue<TKey, TPriority, N, S>
+where
+    TKey: Hash + Eq + Debug,
+    TPriority: Ord + Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// This is unordered borrowing iterator over [`ArrayKeyedPriorityQueue`].
+///
+/// [`ArrayKeyedPriorityQueue`]: struct.ArrayKeyedPriorityQueue.html
+pub struct ArrayKeyedPriorityQueueIter<'a, TKey, TPriority> {
+    slots: core::slice::Iter<'a, Option<ArraySlot<TKey>>>,
+    heap: &'a [Option<ArrayHeapEntry<TPriority>>],
+}
+
+impl<'a, TKey, TPriority> Iterator for ArrayKeyedPriorityQueueIter<'a, TKey, TPriority> {
+    type Item = (&'a TKey, &'a TPriority);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self
+            .slots
+            .next()?
+            .as_ref()
+            .expect("Slots below len are occupied");
+        let entry = self.heap[slot.heap_idx]
+            .as_ref()
+            .expect("Heap positions below len are occupied");
+        Some((&slot.key, &entry.priority))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+impl<'a, TKey, TPriority> ExactSizeIterator for ArrayKeyedPriorityQueueIter<'a, TKey, TPriority> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArrayKeyedPriorityQueue, CapacityError};
+    use crate::errors::InvariantError;
+    use core::hash::{BuildHasherDefault, Hasher};
+
+    // Puts all keys into the same chain of table
+    #[derive(Default)]
+    struct ConstHasher;
+
+    impl Hasher for ConstHasher {
+        fn finish(&self) -> u64 {
+            3
+        }
+
+        fn write(&mut self, _bytes: &[u8]) {}
+    }
+
+    type Colliding<const N: usize> =
+        ArrayKeyedPriorityQueue<u32, u32, N, BuildHasherDefault<ConstHasher>>;
+
+    // FNV-1a so tests don't need `RandomState` from std
+    struct FnvHasher(u64);
+
+    impl Default for FnvHasher {
+        fn default() -> Self {
+            FnvHasher(0xcbf2_9ce4_8422_2325)
+        }
+    }
+
+    impl Hasher for FnvHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &byte in bytes {
+                self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+
+    type Queue<const N: usize> =
+        ArrayKeyedPriorityQueue<u32, u32, N, BuildHasherDefault<FnvHasher>>;
+
+    #[test]
+    fn test_fill_and_drain() {
+        let mut queue = Queue::<16>::with_hasher(Default::default());
+        for x in 0..16 {
+            assert_eq!(queue.push(x, (x * 7) % 16), Ok(None));
+            assert_eq!(queue.validate(), Ok(()));
+        }
+        assert!(queue.is_full());
+        assert_eq!(queue.push(100, 0), Err(CapacityError((100, 0))));
+        assert_eq!(queue.len(), 16);
+
+        for expected in (0..16).rev() {
+            assert_eq!(queue.pop().map(|(_, priority)| priority), Some(expected));
+            assert_eq!(queue.validate(), Ok(()));
+        }
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn test_colliding_keys() {
+        let mut queue = Colliding::<8>::with_hasher(Default::default());
+        for x in 0..8 {
+            queue.push(x, x).unwrap();
+        }
+        // Removal from middle of chain keeps following keys reachable
+        for &x in [2, 0, 7, 4].iter() {
+            assert_eq!(queue.remove(&x), Some(x));
+            assert_eq!(queue.validate(), Ok(()));
+        }
+        for &x in [1, 3, 5, 6].iter() {
+            assert_eq!(queue.get_priority(&x), Some(&x));
+        }
+        assert_eq!(queue.get_priority(&2), None);
+        assert_eq!(queue.push(10, 10), Ok(None));
+        assert_eq!(queue.set_priority(&1, 20), Ok(1));
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.pop(), Some((1, 20)));
+        assert_eq!(queue.pop(), Some((10, 10)));
+    }
+
+    #[test]
+    fn test_wrapping_chain() {
+        // Hash 3 in table of 5 wraps chain around the end
+        let mut queue = Colliding::<5>::with_hasher(Default::default());
+        for x in 0..5 {
+            queue.push(x, x).unwrap();
+        }
+        assert_eq!(queue.remove(&3), Some(3));
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.remove(&0), Some(0));
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.get_priority(&4), Some(&4));
+        assert_eq!(queue.get_priority(&2), Some(&2));
+    }
+
+    #[test]
+    fn test_clear_and_reuse() {
+        let mut queue = Queue::<4>::with_hasher(Default::default());
+        for x in 0..4 {
+            queue.push(x, x).unwrap();
+        }
+        queue.clear();
+        assert!(queue.is_empty());
+        assert_eq!(queue.peek(), None);
+        assert_eq!(queue.iter().count(), 0);
+        assert_eq!(queue.validate(), Ok(()));
+        for x in 10..14 {
+            queue.push(x, x).unwrap();
+        }
+        assert_eq!(queue.peek(), Some((&13, &13)));
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut queue = Queue::<0>::with_hasher(Default::default());
+        assert_eq!(queue.push(1, 1), Err(CapacityError((1, 1))));
+        assert_eq!(queue.get_priority(&1), None);
+        assert_eq!(queue.pop(), None);
+        assert_eq!(queue.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_detects_broken_heap() {
+        let mut queue = Queue::<4>::with_hasher(Default::default());
+        for x in 0..4 {
+            queue.push(x, x).unwrap();
+        }
+        queue.heap[3].as_mut().unwrap().priority = 100;
+        assert_eq!(
+            queue.validate(),
+            Err(InvariantError::HeapOrder {
+                parent: 1,
+                child: 3
+            })
+        );
+    }
+}
@@ -0,0 +1,492 @@
+use crate::heap_traits::EditableHeap;
+use crate::keyed_priority_queue::{Entry, KeyedPriorityQueue, SetPriorityNotFoundError};
+use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+
+/// A priority queue with limited length which evicts items with the lowest priority.
+///
+/// When queue is full, pushing of new key compares its priority with the current minimum:
+/// if new priority is bigger, minimal item is evicted and returned, otherwise new item is rejected.
+/// Changing priority of key which is already in queue never evicts anything.
+///
+/// It is useful for caches and for tracking top N items of some stream.
+///
+/// Lowest priority item is stored on top of internal heap so
+/// both [`peek_min`] and [`pop_min`] are cheap.
+///
+/// [`peek_min`]: struct.BoundedKeyedPriorityQueue.html#method.peek_min
+/// [`pop_min`]: struct.BoundedKeyedPriorityQueue.html#method.pop_min
+///
+/// # Examples
+///
+/// ```
+/// use keyed_priority_queue::{BoundedKeyedBinaryPriorityQueue, BoundedPushResult};
+///
+/// let mut top = BoundedKeyedBinaryPriorityQueue::with_max_len(3);
+/// assert_eq!(top.push("first", 5), BoundedPushResult::Inserted);
+/// assert_eq!(top.push("second", 3), BoundedPushResult::Inserted);
+/// assert_eq!(top.push("third", 4), BoundedPushResult::Inserted);
+///
+/// // Queue is full so smallest item is evicted
+/// assert_eq!(top.push("fourth", 10), BoundedPushResult::Evicted("second", 3));
+/// // Or new item is rejected if it is not better than smallest one
+/// assert_eq!(top.push("fifth", 1), BoundedPushResult::Rejected("fifth", 1));
+/// // Keys which are already in queue are just updated
+/// assert_eq!(top.push("third", 0), BoundedPushResult::Updated(4));
+///
+/// assert_eq!(top.peek_min(), Some((&"third", &0)));
+/// assert_eq!(
+///     top.into_sorted_vec(),
+///     vec![("fourth", 10), ("first", 5), ("third", 0)]
+/// );
+/// ```
+#[derive(Clone)]
+pub struct BoundedKeyedPriorityQueue<TKey, TPriority, THeap, S = RandomState>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<Reverse<TPriority>>,
+    S: BuildHasher,
+{
+    queue: KeyedPriorityQueue<TKey, Reverse<TPriority>, THeap, S>,
+    max_len: usize,
+}
+
+/// Result of [`push`] method of [`BoundedKeyedPriorityQueue`].
+///
+/// [`BoundedKeyedPriorityQueue`]: struct.BoundedKeyedPriorityQueue.html
+/// [`push`]: struct.BoundedKeyedPriorityQueue.html#method.push
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BoundedPushResult<TKey, TPriority> {
+    /// New key was added and nothing was evicted.
+    Inserted,
+    /// Key was already in queue, its old priority is returned.
+    Updated(TPriority),
+    /// New key was added and item with the lowest priority was evicted.
+    Evicted(TKey, TPriority),
+    /// Queue is full and new priority isn't bigger than minimal one,
+    /// so passed key and priority are returned back.
+    Rejected(TKey, TPriority),
+}
+
+impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<Reverse<TPriority>>>
+    BoundedKeyedPriorityQueue<TKey, TPriority, THeap, RandomState>
+{
+    /// Creates an empty queue which can keep up to `max_len` elements.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::BoundedKeyedWeakPriorityQueue;
+    /// let mut queue = BoundedKeyedWeakPriorityQueue::with_max_len(10);
+    /// queue.push("Key", 4);
+    /// ```
+    #[inline]
+    pub fn with_max_len(max_len: usize) -> Self
+    where
+        THeap::Alloc: Default,
+    {
+        Self::with_max_len_and_hasher(max_len, RandomState::default())
+    }
+}
+
+impl<TKey, TPriority, THeap, S> BoundedKeyedPriorityQueue<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<Reverse<TPriority>>,
+    S: BuildHasher,
+{
+    /// Creates an empty queue which can keep up to `max_len` elements with specific Hasher.
+    ///
+    /// Memory for `max_len` elements is allocated at once.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::BoundedKeyedBinaryPriorityQueue;
+    /// use std::collections::hash_map::RandomState;
+    /// let mut queue = BoundedKeyedBinaryPriorityQueue::with_max_len_and_hasher(10, RandomState::default());
+    /// queue.push("Key", 4);
+    /// ```
+    #[inline]
+    pub fn with_max_len_and_hasher(max_len: usize, hasher: S) -> Self
+    where
+        THeap::Alloc: Default,
+    {
+        Self {
+            queue: KeyedPriorityQueue::with_capacity_and_hasher(max_len, hasher),
+            max_len,
+        }
+    }
+
+    /// Get the maximal number of elements in queue.
+    #[inline]
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Adds new element to queue if missing key or replace its priority if key exists.
+    ///
+    /// If queue is full and key is missing, new element pushed only
+    /// if its priority is bigger than minimal priority in queue.
+    /// In that case element with minimal priority is evicted.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::{BoundedKeyedWeakPriorityQueue, BoundedPushResult};
+    /// let mut queue = BoundedKeyedWeakPriorityQueue::with_max_len(1);
+    /// assert_eq!(queue.push("First", 5), BoundedPushResult::Inserted);
+    /// assert_eq!(queue.push("First", 3), BoundedPushResult::Updated(5));
+    /// assert_eq!(queue.push("Second", 3), BoundedPushResult::Rejected("Second", 3));
+    /// assert_eq!(queue.push("Second", 4), BoundedPushResult::Evicted("First", 3));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(log n)*** in average.
+    pub fn push(&mut self, key: TKey, priority: TPriority) -> BoundedPushResult<TKey, TPriority> {
+        let is_full = self.queue.len() >= self.max_len;
+        let can_evict = match self.queue.peek() {
+            Some((_, Reverse(min_priority))) => *min_priority < priority,
+            None => false,
+        };
+        match self.queue.entry(key) {
+            Entry::Occupied(entry) => {
+                let Reverse(old) = entry.set_priority(Reverse(priority));
+                BoundedPushResult::Updated(old)
+            }
+            Entry::Vacant(entry) if !is_full => {
+                entry.set_priority(Reverse(priority));
+                BoundedPushResult::Inserted
+            }
+            // Minimum is replaced in place so key is hashed once and heap is sifted once
+            Entry::Vacant(entry) if can_evict => {
+                let (evicted_key, Reverse(evicted_priority)) = entry.replace_top(Reverse(priority));
+                BoundedPushResult::Evicted(evicted_key, evicted_priority)
+            }
+            Entry::Vacant(entry) => BoundedPushResult::Rejected(entry.into_key(), priority),
+        }
+    }
+
+    /// Get reference to the pair with the minimal priority.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::BoundedKeyedBinaryPriorityQueue;
+    /// let mut queue = BoundedKeyedBinaryPriorityQueue::with_max_len(5);
+    /// queue.push(1, 1);
+    /// queue.push(0, 0);
+    /// assert_eq!(queue.peek_min(), Some((&0, &0)));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn peek_min(&self) -> Option<(&TKey, &TPriority)> {
+        let (key, Reverse(priority)) = self.queue.peek()?;
+        Some((key, priority))
+    }
+
+    /// Remove and return item with the minimal priority.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::BoundedKeyedWeakPriorityQueue;
+    /// let mut queue = BoundedKeyedWeakPriorityQueue::with_max_len(5);
+    /// queue.push(1, 1);
+    /// queue.push(0, 0);
+    /// assert_eq!(queue.pop_min(), Some((0, 0)));
+    /// assert_eq!(queue.pop_min(), Some((1, 1)));
+    /// assert_eq!(queue.pop_min(), None);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Cost of pop is always ***O(log n)***
+    #[inline]
+    pub fn pop_min(&mut self) -> Option<(TKey, TPriority)> {
+        let (key, Reverse(priority)) = self.queue.pop()?;
+        Some((key, priority))
+    }
+
+    /// Get reference to the priority by key.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(1)*** in average (limited by hash map key lookup).
+    #[inline]
+    pub fn get_priority<Q>(&self, key: &Q) -> Option<&TPriority>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Reverse(priority) = self.queue.get_priority(key)?;
+        Some(priority)
+    }
+
+    /// Set new priority for existing key and reorder the queue.
+    /// Returns old priority if succeeds or [`SetPriorityNotFoundError`].
+    ///
+    /// ### Time complexity
+    ///
+    /// In best case ***O(1)***, in average costs ***O(log n)***.
+    ///
+    /// [`SetPriorityNotFoundError`]: struct.SetPriorityNotFoundError.html
+    #[inline]
+    pub fn set_priority<Q>(
+        &mut self,
+        key: &Q,
+        priority: TPriority,
+    ) -> Result<TPriority, SetPriorityNotFoundError>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Reverse(old) = self.queue.set_priority(key, Reverse(priority))?;
+        Ok(old)
+    }
+
+    /// Allow removing item by key.
+    /// Returns priority if succeeds.
+    ///
+    /// ### Time complexity
+    ///
+    /// On average the function will require ***O(log n)*** operations.
+    #[inline]
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<TPriority>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Reverse(priority) = self.queue.remove(key)?;
+        Some(priority)
+    }
+
+    /// Allow removing item by key.
+    /// Returns key and priority if succeeds.
+    ///
+    /// ### Time complexity
+    ///
+    /// On average the function will require ***O(log n)*** operations.
+    #[inline]
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(TKey, TPriority)>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, Reverse(priority)) = self.queue.remove_entry(key)?;
+        Some((key, priority))
+    }
+
+    /// Get the number of elements in queue.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns true if queue is empty.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Returns true if next push of missing key would evict or reject something.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.queue.len() >= self.max_len
+    }
+
+    /// Make the queue empty.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(n)***
+    #[inline]
+    pub fn clear(&mut self) {
+        self.queue.clear()
+    }
+
+    /// Create readonly borrowing iterator over queue in arbitrary order.
+    ///
+    /// ### Time complexity
+    ///
+    /// Iterating over whole queue is ***O(n)***
+    pub fn iter(&self) -> impl Iterator<Item = (&TKey, &TPriority)> {
+        self.queue
+            .iter()
+            .map(|(key, Reverse(priority))| (key, priority))
+    }
+
+    /// Consumes queue and returns its items sorted by priority in descending order.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::BoundedKeyedBinaryPriorityQueue;
+    /// let mut queue = BoundedKeyedBinaryPriorityQueue::with_max_len(3);
+    /// for x in 0..10 {
+    ///     queue.push(x, x * 10);
+    /// }
+    /// assert_eq!(queue.into_sorted_vec(), vec![(9, 90), (8, 80), (7, 70)]);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(n log n)***
+    pub fn into_sorted_vec(mut self) -> Vec<(TKey, TPriority)> {
+        let mut result = Vec::with_capacity(self.len());
+        while let Some(item) = self.pop_min() {
+            result.push(item);
+        }
+        result.reverse();
+        result
+    }
+}
+
+impl<TKey, TPriority, THeap, S> Debug for BoundedKeyedPriorityQueue<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq + Debug,
+    TPriority: Ord + Debug,
+    THeap: EditableHeap<Reverse<TPriority>>,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "[")?;
+        for entry in self.iter() {
+            write!(f, "{:?}", entry)?;
+        }
+        write!(f, "]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoundedKeyedPriorityQueue, BoundedPushResult};
+    use crate::editable_binary_heap::BinaryHeap;
+    use crate::editable_weak_heap::WeakHeap;
+    use std::cmp::Reverse;
+
+    #[test]
+    fn test_top_n() {
+        let items = [
+            17, 5, 42, 8, 23, 4, 16, 15, 99, -3, 0, 31, 77, 12, 56, 1, 64, 28,
+        ];
+        let mut queue =
+            BoundedKeyedPriorityQueue::<usize, i32, WeakHeap<Reverse<i32>>>::with_max_len(5);
+        for (i, &x) in items.iter().enumerate() {
+            queue.push(i, x);
+            assert!(queue.len() <= 5);
+        }
+        let mut expected = items;
+        expected.sort_unstable_by_key(|&x| Reverse(x));
+        let result: Vec<i32> = queue
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(_, p)| p)
+            .collect();
+        assert_eq!(&result[..], &expected[..5]);
+    }
+
+    #[test]
+    fn test_push_results() {
+        let mut queue =
+            BoundedKeyedPriorityQueue::<&str, i32, BinaryHeap<Reverse<i32>>>::with_max_len(2);
+        assert!(queue.is_empty());
+        assert_eq!(queue.push("a", 1), BoundedPushResult::Inserted);
+        assert_eq!(queue.push("b", 2), BoundedPushResult::Inserted);
+        assert!(queue.is_full());
+        // Equal to minimum is rejected
+        assert_eq!(queue.push("c", 1), BoundedPushResult::Rejected("c", 1));
+        assert_eq!(queue.push("c", 3), BoundedPushResult::Evicted("a", 1));
+        assert_eq!(queue.push("b", -5), BoundedPushResult::Updated(2));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.get_priority(&"a"), None);
+        assert_eq!(queue.peek_min(), Some((&"b", &-5)));
+        assert_eq!(queue.push("d", -4), BoundedPushResult::Evicted("b", -5));
+        assert_eq!(queue.pop_min(), Some(("d", -4)));
+        assert_eq!(queue.pop_min(), Some(("c", 3)));
+        assert_eq!(queue.pop_min(), None);
+    }
+
+    #[test]
+    fn test_eviction_keeps_queue_valid() {
+        let mut binary =
+            BoundedKeyedPriorityQueue::<u32, u32, BinaryHeap<Reverse<u32>>>::with_max_len(8);
+        let mut weak =
+            BoundedKeyedPriorityQueue::<u32, u32, WeakHeap<Reverse<u32>>>::with_max_len(8);
+        for key in 0..100u32 {
+            let priority = (key * 37) % 101;
+            assert_eq!(binary.push(key, priority), weak.push(key, priority));
+            assert_eq!(binary.queue.validate(), Ok(()));
+            assert_eq!(weak.queue.validate(), Ok(()));
+        }
+        for (&key, &priority) in binary.iter() {
+            assert_eq!(binary.get_priority(&key), Some(&priority));
+            assert_eq!(weak.get_priority(&key), Some(&priority));
+        }
+        let mut expected: Vec<u32> = (0..100u32).map(|key| (key * 37) % 101).collect();
+        expected.sort_unstable_by_key(|&x| Reverse(x));
+        expected.truncate(8);
+        expected.reverse();
+        let result: Vec<u32> = std::iter::from_fn(|| binary.pop_min())
+            .map(|(_, priority)| priority)
+            .collect();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_zero_max_len() {
+        let mut queue =
+            BoundedKeyedPriorityQueue::<i32, i32, WeakHeap<Reverse<i32>>>::with_max_len(0);
+        assert_eq!(queue.push(1, 1), BoundedPushResult::Rejected(1, 1));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_modifications() {
+        let mut queue =
+            BoundedKeyedPriorityQueue::<i32, i32, BinaryHeap<Reverse<i32>>>::with_max_len(10);
+        for x in 0..5 {
+            queue.push(x, x);
+        }
+        assert_eq!(queue.set_priority(&0, 10), Ok(0));
+        assert!(queue.set_priority(&20, 10).is_err());
+        assert_eq!(queue.remove(&1), Some(1));
+        assert_eq!(queue.remove_entry(&2), Some((2, 2)));
+        assert_eq!(queue.remove(&2), None);
+        assert_eq!(queue.peek_min(), Some((&3, &3)));
+        let mut items: Vec<_> = queue.iter().map(|(&k, &p)| (k, p)).collect();
+        items.sort_unstable();
+        assert_eq!(items, vec![(0, 10), (3, 3), (4, 4)]);
+        assert_eq!(format!("{:?}", queue).len(), "[(3, 3)(4, 4)(0, 10)]".len());
+        queue.clear();
+        assert!(queue.is_empty());
+        assert_eq!(queue.max_len(), 10);
+    }
+}
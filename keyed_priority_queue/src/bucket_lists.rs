@@ -0,0 +1,258 @@
+use crate::keyed_priority_queue::TryReserveError;
+use std::vec::Vec;
+
+const NONE: usize = usize::MAX;
+
+// Node of intrusive doubly linked list of bucket items
+#[derive(Clone, Copy, Debug)]
+struct Link {
+    prev: usize,
+    next: usize,
+    bucket: usize,
+}
+
+const UNLINKED: Link = Link {
+    prev: NONE,
+    next: NONE,
+    bucket: NONE,
+};
+
+/// Splits positions of heap items into buckets using intrusive doubly linked lists.
+/// Links are stored in the same positions as items so
+/// every operation on bucket is ***O(1)***.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BucketLists {
+    links: Vec<Link>,
+    // First item of every bucket
+    heads: Vec<usize>,
+}
+
+impl BucketLists {
+    /// Creates lists for `len` positions. They must be linked before use.
+    pub(crate) fn with_len(len: usize) -> Self {
+        BucketLists {
+            links: vec![UNLINKED; len],
+            heads: Vec::new(),
+        }
+    }
+
+    /// Number of buckets which may be non-empty
+    #[inline(always)]
+    pub(crate) fn buckets(&self) -> usize {
+        self.heads.len()
+    }
+
+    #[inline(always)]
+    pub(crate) fn first(&self, bucket: usize) -> Option<usize> {
+        match self.heads.get(bucket) {
+            Some(&head) if head != NONE => Some(head),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn iter_bucket(&self, bucket: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut current = self.heads.get(bucket).copied().unwrap_or(NONE);
+        std::iter::from_fn(move || {
+            if current == NONE {
+                return None;
+            }
+            let position = current;
+            current = self.links[position].next;
+            Some(position)
+        })
+    }
+
+    /// Adds position after the last one and puts it into bucket.
+    pub(crate) fn push(&mut self, bucket: usize) {
+        self.links.push(UNLINKED);
+        self.link(self.links.len() - 1, bucket);
+    }
+
+    /// Adds position after the last one. It must be linked before use.
+    #[inline]
+    pub(crate) fn push_unlinked(&mut self) {
+        self.links.push(UNLINKED);
+    }
+
+    /// Removes the last position which must be unlinked.
+    #[inline]
+    pub(crate) fn pop_unlinked(&mut self) {
+        debug_assert!(
+            self.links.last().map_or(false, |link| link.bucket == NONE),
+            "Last position is linked"
+        );
+        self.links.pop();
+    }
+
+    /// Bucket of linked position
+    #[inline(always)]
+    pub(crate) fn bucket(&self, position: usize) -> usize {
+        self.links[position].bucket
+    }
+
+    /// Puts position into bucket. Bucket must be less than `buckets()`.
+    pub(crate) fn link(&mut self, position: usize, bucket: usize) {
+        debug_assert!(bucket < self.heads.len(), "Bucket out of range");
+        let next = self.heads[bucket];
+        self.links[position] = Link {
+            prev: NONE,
+            next,
+            bucket,
+        };
+        if next != NONE {
+            self.links[next].prev = position;
+        }
+        self.heads[bucket] = position;
+    }
+
+    pub(crate) fn unlink(&mut self, position: usize) {
+        let Link { prev, next, bucket } = self.links[position];
+        if prev == NONE {
+            self.heads[bucket] = next;
+        } else {
+            self.links[prev].next = next;
+        }
+        if next != NONE {
+            self.links[next].prev = prev;
+        }
+    }
+
+    /// Moves position to other bucket if it differs from current one.
+    /// Returns true if position was moved.
+    pub(crate) fn relink(&mut self, position: usize, bucket: usize) -> bool {
+        if self.links[position].bucket == bucket {
+            return false;
+        }
+        self.unlink(position);
+        self.link(position, bucket);
+        true
+    }
+
+    /// Removes position and moves the last one in its place like `Vec::swap_remove`.
+    pub(crate) fn swap_remove(&mut self, position: usize) {
+        self.unlink(position);
+        let last = self.links.len() - 1;
+        if position != last {
+            // Neighbours of the last item must point to its new position
+            let Link { prev, next, bucket } = self.links[last];
+            if prev == NONE {
+                self.heads[bucket] = position;
+            } else {
+                self.links[prev].next = position;
+            }
+            if next != NONE {
+                self.links[next].prev = position;
+            }
+        }
+        self.links.swap_remove(position);
+    }
+
+    /// Removes all links keeping `buckets` empty buckets.
+    /// Positions must be linked again after this.
+    pub(crate) fn reset(&mut self, buckets: usize) {
+        self.heads.clear();
+        self.heads.resize(buckets, NONE);
+    }
+
+    #[inline]
+    pub(crate) fn clear(&mut self) {
+        self.links.clear();
+        self.heads.clear();
+    }
+
+    #[inline]
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.links.reserve(additional)
+    }
+
+    #[inline]
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.links
+            .try_reserve(additional)
+            .map_err(|_| TryReserveError {})
+    }
+
+    #[inline]
+    pub(crate) fn capacity(&self) -> usize {
+        self.links.capacity()
+    }
+
+    #[inline]
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.links.shrink_to_fit();
+        self.heads.shrink_to_fit();
+    }
+
+    #[inline]
+    pub(crate) fn shrink_to(&mut self, min_capacity: usize) {
+        self.links.shrink_to(min_capacity)
+    }
+
+    /// Checks that every one of `len` positions is linked exactly once
+    /// and belongs to bucket returned by `bucket_of`.
+    pub(crate) fn is_valid<F: Fn(usize) -> usize>(&self, len: usize, bucket_of: F) -> bool {
+        if self.links.len() != len {
+            return false;
+        }
+        let mut visited = 0;
+        for (bucket, &head) in self.heads.iter().enumerate() {
+            let mut prev = NONE;
+            let mut current = head;
+            while current != NONE {
+                let link = match self.links.get(current) {
+                    Some(link) => link,
+                    None => return false,
+                };
+                visited += 1;
+                if visited > len
+                    || link.prev != prev
+                    || link.bucket != bucket
+                    || bucket_of(current) != bucket
+                {
+                    return false;
+                }
+                prev = current;
+                current = link.next;
+            }
+        }
+        visited == len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_remove() {
+        let mut lists = BucketLists::default();
+        lists.reset(3);
+        let mut buckets = vec![0, 2, 2, 1, 2, 0];
+        for &bucket in buckets.iter() {
+            lists.push(bucket);
+        }
+        assert!(lists.is_valid(buckets.len(), |x| buckets[x]));
+
+        for &position in &[1, 4, 0, 0, 1, 0] {
+            lists.swap_remove(position);
+            buckets.swap_remove(position);
+            assert!(lists.is_valid(buckets.len(), |x| buckets[x]));
+        }
+        assert_eq!(lists.first(0), None);
+        assert_eq!(lists.first(2), None);
+    }
+
+    #[test]
+    fn test_relink() {
+        let mut lists = BucketLists::default();
+        lists.reset(6);
+        for bucket in 0..10 {
+            lists.push(bucket % 3);
+        }
+        assert!(!lists.relink(4, 1));
+        assert!(lists.relink(4, 5));
+        assert_eq!(lists.iter_bucket(5).collect::<Vec<_>>(), vec![4]);
+        assert_eq!(lists.iter_bucket(1).collect::<Vec<_>>(), vec![7, 1]);
+        assert!(lists.is_valid(10, |x| if x == 4 { 5 } else { x % 3 }));
+    }
+}
@@ -0,0 +1,777 @@
+use crate::heap_traits::EditableHeap;
+use crate::keyed_priority_queue::{KeyedPriorityQueue, SetPriorityNotFoundError};
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+struct Shard<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    queue: Mutex<KeyedPriorityQueue<TKey, TPriority, THeap, S>>,
+    // Copy of the maximal priority in queue.
+    // It is updated while queue is locked so pop can choose shard without locking all of them.
+    top: RwLock<Option<TPriority>>,
+}
+
+impl<TKey, TPriority, THeap, S> Shard<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord + Clone,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, KeyedPriorityQueue<TKey, TPriority, THeap, S>> {
+        lock(&self.queue)
+    }
+
+    /// Locks queue for modification.
+    /// Total length and copy of top are updated when returned guard is dropped.
+    #[inline]
+    fn lock_mut<'a>(&'a self, len: &'a AtomicUsize) -> ShardGuard<'a, TKey, TPriority, THeap, S> {
+        let queue = self.lock();
+        let old_len = queue.len();
+        ShardGuard {
+            shard: self,
+            queue,
+            len,
+            old_len,
+        }
+    }
+
+    /// Must be called with locked queue after every change of it.
+    #[inline]
+    fn update_top(&self, queue: &KeyedPriorityQueue<TKey, TPriority, THeap, S>) {
+        let top = queue.peek().map(|(_, priority)| priority.clone());
+        *self.top.write().unwrap_or_else(PoisonError::into_inner) = top;
+    }
+}
+
+// Synchronizes total length and copy of top with locked shard on drop.
+// It happens during unwinding too, so panic in comparison of priorities
+// doesn't leave item which isn't counted in total length.
+struct ShardGuard<'a, TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord + Clone,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    shard: &'a Shard<TKey, TPriority, THeap, S>,
+    queue: MutexGuard<'a, KeyedPriorityQueue<TKey, TPriority, THeap, S>>,
+    len: &'a AtomicUsize,
+    old_len: usize,
+}
+
+impl<'a, TKey, TPriority, THeap, S> Deref for ShardGuard<'a, TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord + Clone,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    type Target = KeyedPriorityQueue<TKey, TPriority, THeap, S>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.queue
+    }
+}
+
+impl<'a, TKey, TPriority, THeap, S> DerefMut for ShardGuard<'a, TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord + Clone,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.queue
+    }
+}
+
+impl<'a, TKey, TPriority, THeap, S> Drop for ShardGuard<'a, TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord + Clone,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        let new_len = self.queue.len();
+        if new_len > self.old_len {
+            self.len.fetch_add(new_len - self.old_len, Ordering::SeqCst);
+        } else if new_len < self.old_len {
+            self.len.fetch_sub(self.old_len - new_len, Ordering::SeqCst);
+        }
+        self.shard.update_top(&self.queue);
+    }
+}
+
+/// A thread-safe priority queue that support lookup by key.
+///
+/// Bigger `TPriority` values will have more priority.
+///
+/// Queue is split into several shards each of them protected by own lock.
+/// Key is always stored in the same shard so [`push`], [`set_priority`] and [`remove`]
+/// lock only one shard and operations with different keys mostly don't block each other.
+/// Every shard keeps a copy of its maximal priority, so [`pop`] compares these copies
+/// and locks only the shard with the best one.
+///
+/// Panic in another thread doesn't make queue unusable:
+/// every operation leaves shard consistent, so poisoned locks are just taken.
+///
+/// Available only with `sync` feature.
+///
+/// [`push`]: struct.ConcurrentKeyedPriorityQueue.html#method.push
+/// [`set_priority`]: struct.ConcurrentKeyedPriorityQueue.html#method.set_priority
+/// [`remove`]: struct.ConcurrentKeyedPriorityQueue.html#method.remove
+/// [`pop`]: struct.ConcurrentKeyedPriorityQueue.html#method.pop
+///
+/// # Examples
+///
+/// ```
+/// use keyed_priority_queue::ConcurrentKeyedBinaryPriorityQueue;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let queue = Arc::new(ConcurrentKeyedBinaryPriorityQueue::new());
+///
+/// let producers: Vec<_> = (0..4)
+///     .map(|t| {
+///         let queue = Arc::clone(&queue);
+///         thread::spawn(move || {
+///             for i in 0..100 {
+///                 queue.push(t * 100 + i, i);
+///             }
+///         })
+///     })
+///     .collect();
+///
+/// let mut popped = 0;
+/// while popped < 400 {
+///     let (_key, _priority) = queue.pop_wait();
+///     popped += 1;
+/// }
+///
+/// for producer in producers {
+///     producer.join().unwrap();
+/// }
+/// assert!(queue.is_empty());
+/// ```
+pub struct ConcurrentKeyedPriorityQueue<TKey, TPriority, THeap, S = RandomState>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    shards: Box<[Shard<TKey, TPriority, THeap, S>]>,
+    // Number of bits in hash used to choose shard
+    shard_shift: u32,
+    hasher: S,
+    len: AtomicUsize,
+    // Number of threads which wait in `pop_wait` or `pop_timeout`
+    waiting: AtomicUsize,
+    wait_lock: Mutex<()>,
+    not_empty: Condvar,
+}
+
+impl<TKey, TPriority, THeap> ConcurrentKeyedPriorityQueue<TKey, TPriority, THeap, RandomState>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    THeap::Alloc: Default,
+{
+    /// Creates an empty queue with number of shards based on available parallelism.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ConcurrentKeyedBinaryPriorityQueue;
+    /// let queue = ConcurrentKeyedBinaryPriorityQueue::new();
+    /// queue.push("Key", 4);
+    /// ```
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_shards_and_hasher(default_shard_amount(), RandomState::default())
+    }
+
+    /// Creates an empty queue with at least `shards` shards.
+    /// Number of shards is rounded up to power of two.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ConcurrentKeyedWeakPriorityQueue;
+    /// let queue = ConcurrentKeyedWeakPriorityQueue::with_shards(16);
+    /// queue.push("Key", 4);
+    /// ```
+    #[inline]
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, RandomState::default())
+    }
+}
+
+impl<TKey, TPriority, THeap, S> ConcurrentKeyedPriorityQueue<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    THeap::Alloc: Default,
+    S: BuildHasher + Clone,
+{
+    /// Creates an empty queue with at least `shards` shards and specific Hasher.
+    /// Number of shards is rounded up to power of two.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ConcurrentKeyedBinaryPriorityQueue;
+    /// use std::collections::hash_map::RandomState;
+    /// let queue = ConcurrentKeyedBinaryPriorityQueue::with_shards_and_hasher(4, RandomState::default());
+    /// queue.push("Key", 4);
+    /// ```
+    pub fn with_shards_and_hasher(shards: usize, hasher: S) -> Self {
+        let shards = shards.max(1).next_power_of_two();
+        Self {
+            shards: (0..shards)
+                .map(|_| Shard {
+                    queue: Mutex::new(KeyedPriorityQueue::with_hasher(hasher.clone())),
+                    top: RwLock::new(None),
+                })
+                .collect(),
+            shard_shift: shards.trailing_zeros(),
+            hasher,
+            len: AtomicUsize::new(0),
+            waiting: AtomicUsize::new(0),
+            wait_lock: Mutex::new(()),
+            not_empty: Condvar::new(),
+        }
+    }
+}
+
+impl<TKey, TPriority, THeap, S> ConcurrentKeyedPriorityQueue<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord + Clone,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    /// Adds new element to queue if missing key or replace its priority if key exists.
+    /// In second case doesn't replace key.
+    ///
+    /// Wakes one of threads which wait in [`pop_wait`] or [`pop_timeout`].
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ConcurrentKeyedWeakPriorityQueue;
+    /// let queue = ConcurrentKeyedWeakPriorityQueue::new();
+    /// assert_eq!(queue.push("First", 5), None);
+    /// assert_eq!(queue.push("First", 10), Some(5));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Locks only one shard and costs ***O(log n)*** in average.
+    ///
+    /// [`pop_wait`]: struct.ConcurrentKeyedPriorityQueue.html#method.pop_wait
+    /// [`pop_timeout`]: struct.ConcurrentKeyedPriorityQueue.html#method.pop_timeout
+    pub fn push(&self, key: TKey, priority: TPriority) -> Option<TPriority> {
+        let old = self.shard_for(&key).lock_mut(&self.len).push(key, priority);
+        if old.is_none() && self.waiting.load(Ordering::SeqCst) > 0 {
+            let _guard = lock(&self.wait_lock);
+            self.not_empty.notify_one();
+        }
+        old
+    }
+
+    /// Remove and return item with the maximal priority.
+    ///
+    /// Copies of shards tops are examined one by one so if other threads modify queue concurrently,
+    /// returned item is the maximal one among the states of shards at the moments when they were examined.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ConcurrentKeyedBinaryPriorityQueue;
+    /// let queue = ConcurrentKeyedBinaryPriorityQueue::new();
+    /// for x in 0..5 {
+    ///     queue.push(x, x);
+    /// }
+    /// assert_eq!(queue.pop(), Some((4, 4)));
+    /// assert_eq!(queue.pop(), Some((3, 3)));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Locks only the chosen shard and costs ***O(shards + log n)***.
+    /// Choice is repeated if top of the shard changes before it is locked.
+    pub fn pop(&self) -> Option<(TKey, TPriority)> {
+        while self.len.load(Ordering::SeqCst) != 0 {
+            let mut best: Option<(usize, TPriority)> = None;
+            for (index, shard) in self.shards.iter().enumerate() {
+                let top = shard.top.read().unwrap_or_else(PoisonError::into_inner);
+                let current = match top.as_ref() {
+                    Some(current) => current,
+                    None => continue,
+                };
+                if best.as_ref().map_or(true, |(_, best)| best < current) {
+                    best = Some((index, current.clone()));
+                }
+            }
+            let (index, expected) = best?;
+            // Copy of top is refreshed by guard if it is outdated
+            let mut queue = self.shards[index].lock_mut(&self.len);
+            if queue.peek().map_or(false, |(_, top)| *top == expected) {
+                return queue.pop();
+            }
+        }
+        None
+    }
+
+    /// Remove and return item with the maximal priority.
+    /// Blocks current thread until queue becomes non-empty.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ConcurrentKeyedWeakPriorityQueue;
+    /// use std::sync::Arc;
+    /// let queue = Arc::new(ConcurrentKeyedWeakPriorityQueue::new());
+    /// let producer = {
+    ///     let queue = Arc::clone(&queue);
+    ///     std::thread::spawn(move || queue.push("Job", 5))
+    /// };
+    /// assert_eq!(queue.pop_wait(), ("Job", 5));
+    /// producer.join().unwrap();
+    /// ```
+    pub fn pop_wait(&self) -> (TKey, TPriority) {
+        loop {
+            if let Some(item) = self.pop() {
+                return item;
+            }
+            let guard = lock(&self.wait_lock);
+            self.waiting.fetch_add(1, Ordering::SeqCst);
+            let _guard = if self.len.load(Ordering::SeqCst) == 0 {
+                self.not_empty
+                    .wait(guard)
+                    .unwrap_or_else(PoisonError::into_inner)
+            } else {
+                guard
+            };
+            self.waiting.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Remove and return item with the maximal priority.
+    /// Blocks current thread until queue becomes non-empty or `timeout` passes.
+    /// Returns `None` on timeout.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ConcurrentKeyedBinaryPriorityQueue;
+    /// use std::time::Duration;
+    /// let queue = ConcurrentKeyedBinaryPriorityQueue::<&str, i32>::new();
+    /// assert_eq!(queue.pop_timeout(Duration::from_millis(10)), None);
+    /// queue.push("Job", 5);
+    /// assert_eq!(queue.pop_timeout(Duration::from_millis(10)), Some(("Job", 5)));
+    /// ```
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<(TKey, TPriority)> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(item) = self.pop() {
+                return Some(item);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            let guard = lock(&self.wait_lock);
+            self.waiting.fetch_add(1, Ordering::SeqCst);
+            let _guard = if self.len.load(Ordering::SeqCst) == 0 {
+                let (guard, _) = self
+                    .not_empty
+                    .wait_timeout(guard, deadline - now)
+                    .unwrap_or_else(PoisonError::into_inner);
+                guard
+            } else {
+                guard
+            };
+            self.waiting.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Get copy of the priority by key.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ConcurrentKeyedWeakPriorityQueue;
+    /// let queue = ConcurrentKeyedWeakPriorityQueue::new();
+    /// queue.push("first", 1);
+    /// assert_eq!(queue.get_priority(&"first"), Some(1));
+    /// assert_eq!(queue.get_priority(&"second"), None);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Locks only one shard and costs ***O(1)*** in average.
+    pub fn get_priority<Q>(&self, key: &Q) -> Option<TPriority>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard_for(key).lock().get_priority(key).cloned()
+    }
+
+    /// Set new priority for existing key and reorder the queue.
+    /// Returns old priority if succeeds or [`SetPriorityNotFoundError`].
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::{ConcurrentKeyedBinaryPriorityQueue, SetPriorityNotFoundError};
+    /// let queue = ConcurrentKeyedBinaryPriorityQueue::new();
+    /// queue.push("first", 1);
+    /// assert_eq!(queue.set_priority(&"first", 5), Ok(1));
+    /// assert_eq!(queue.set_priority(&"Missing", 5), Err(SetPriorityNotFoundError{}));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Locks only one shard, in best case ***O(1)***, in average costs ***O(log n)***.
+    ///
+    /// [`SetPriorityNotFoundError`]: struct.SetPriorityNotFoundError.html
+    pub fn set_priority<Q>(
+        &self,
+        key: &Q,
+        priority: TPriority,
+    ) -> Result<TPriority, SetPriorityNotFoundError>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard_for(key)
+            .lock_mut(&self.len)
+            .set_priority(key, priority)
+    }
+
+    /// Allow removing item by key.
+    /// Returns priority if succeeds.
+    ///
+    /// ### Time complexity
+    ///
+    /// Locks only one shard and costs ***O(log n)*** in average.
+    pub fn remove<Q>(&self, key: &Q) -> Option<TPriority>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (_, priority) = self.remove_entry(key)?;
+        Some(priority)
+    }
+
+    /// Allow removing item by key.
+    /// Returns key and priority if succeeds.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::ConcurrentKeyedWeakPriorityQueue;
+    /// let queue = ConcurrentKeyedWeakPriorityQueue::new();
+    /// queue.push(2, 2);
+    /// assert_eq!(queue.remove_entry(&2), Some((2, 2)));
+    /// assert_eq!(queue.remove_entry(&2), None);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Locks only one shard and costs ***O(log n)*** in average.
+    pub fn remove_entry<Q>(&self, key: &Q) -> Option<(TKey, TPriority)>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard_for(key).lock_mut(&self.len).remove_entry(key)
+    }
+
+    /// Get the number of elements in queue.
+    ///
+    /// Value can be outdated already when returned if other threads modify queue.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if queue is empty.
+    ///
+    /// Value can be outdated already when returned if other threads modify queue.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Make the queue empty.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(n)***
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            shard.lock_mut(&self.len).clear();
+        }
+    }
+
+    /// Get the number of shards.
+    #[inline]
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    // `BuildHasher::hash_one` is not available in our minimal supported rustc
+    #[allow(clippy::manual_hash_one)]
+    fn shard_for<Q>(&self, key: &Q) -> &Shard<TKey, TPriority, THeap, S>
+    where
+        Q: Hash + ?Sized,
+    {
+        if self.shard_shift == 0 {
+            return &self.shards[0];
+        }
+        let mut hasher = self.hasher.build_hasher();
+        key.hash(&mut hasher);
+        // Hashtables use lowest bits to find bucket and highest ones as tags
+        // so bits right after tag are used to avoid keys collisions inside shard.
+        let hash = hasher.finish() << 7;
+        &self.shards[(hash >> (64 - self.shard_shift)) as usize]
+    }
+}
+
+impl<TKey, TPriority, THeap, S> Default for ConcurrentKeyedPriorityQueue<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    THeap::Alloc: Default,
+    S: BuildHasher + Clone + Default,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::with_shards_and_hasher(default_shard_amount(), S::default())
+    }
+}
+
+impl<TKey, TPriority, THeap, S> std::fmt::Debug
+    for ConcurrentKeyedPriorityQueue<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord + Clone,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_struct("ConcurrentKeyedPriorityQueue")
+            .field("len", &self.len())
+            .field("shards", &self.shards.len())
+            .finish()
+    }
+}
+
+fn default_shard_amount() -> usize {
+    let parallelism = std::thread::available_parallelism().map_or(1, |x| x.get());
+    (parallelism * 4).next_power_of_two()
+}
+
+// Queue stays consistent if panic happens while lock is held
+// so poisoning is ignored
+#[inline]
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConcurrentKeyedPriorityQueue;
+    use crate::editable_binary_heap::BinaryHeap;
+    use crate::editable_weak_heap::WeakHeap;
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    #[test]
+    fn test_single_thread() {
+        let queue = ConcurrentKeyedPriorityQueue::<i32, i32, BinaryHeap<i32>>::with_shards(4);
+        assert_eq!(queue.shards(), 4);
+        for x in 0..20 {
+            assert_eq!(queue.push(x, x), None);
+        }
+        assert_eq!(queue.len(), 20);
+        assert_eq!(queue.push(5, 50), Some(5));
+        assert_eq!(queue.set_priority(&6, 60), Ok(6));
+        assert_eq!(queue.remove(&19), Some(19));
+        assert_eq!(queue.remove(&19), None);
+        assert_eq!(queue.get_priority(&6), Some(60));
+        assert_eq!(queue.len(), 19);
+        assert_eq!(queue.pop(), Some((6, 60)));
+        assert_eq!(queue.pop(), Some((5, 50)));
+        for x in (0..19).rev().filter(|&x| x != 5 && x != 6) {
+            assert_eq!(queue.pop(), Some((x, x)));
+        }
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_clear() {
+        let queue = ConcurrentKeyedPriorityQueue::<i32, i32, WeakHeap<i32>>::with_shards(3);
+        assert_eq!(queue.shards(), 4);
+        for x in 0..20 {
+            queue.push(x, x);
+        }
+        queue.clear();
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn test_concurrent_push_pop() {
+        const THREADS: usize = 4;
+        const PER_THREAD: usize = 500;
+        let queue = ConcurrentKeyedPriorityQueue::<usize, usize, WeakHeap<usize>>::new();
+        let popped: Vec<Vec<usize>> = std::thread::scope(|s| {
+            for t in 0..THREADS {
+                let queue = &queue;
+                s.spawn(move || {
+                    for i in 0..PER_THREAD {
+                        let key = t * PER_THREAD + i;
+                        queue.push(key, key);
+                        if i % 3 == 0 {
+                            // Item may be already popped by consumer
+                            queue.set_priority(&key, key * 2).ok();
+                        }
+                    }
+                });
+            }
+            let consumers: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    let queue = &queue;
+                    s.spawn(move || {
+                        (0..PER_THREAD)
+                            .map(|_| queue.pop_wait().0)
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            consumers.into_iter().map(|c| c.join().unwrap()).collect()
+        });
+        let unique: HashSet<usize> = popped.iter().flatten().copied().collect();
+        assert_eq!(unique.len(), THREADS * PER_THREAD);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_pop_timeout() {
+        let queue = ConcurrentKeyedPriorityQueue::<i32, i32, BinaryHeap<i32>>::new();
+        assert_eq!(queue.pop_timeout(Duration::from_millis(5)), None);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+                queue.push(1, 1);
+            });
+            assert_eq!(queue.pop_timeout(Duration::from_secs(60)), Some((1, 1)));
+        });
+    }
+
+    #[test]
+    fn test_poisoned_lock() {
+        let queue = ConcurrentKeyedPriorityQueue::<i32, i32, BinaryHeap<i32>>::with_shards(1);
+        queue.push(1, 1);
+        std::thread::scope(|s| {
+            let panicked = s.spawn(|| {
+                let _queue = queue.shards[0].lock();
+                panic!("Poison lock");
+            });
+            assert!(panicked.join().is_err());
+        });
+        assert!(queue.shards[0].queue.is_poisoned());
+        queue.push(2, 2);
+        assert_eq!(queue.pop(), Some((2, 2)));
+        assert_eq!(queue.pop(), Some((1, 1)));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn test_panicking_comparison() {
+        use std::cmp::Ordering as CmpOrdering;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        struct Priority(i32);
+
+        impl PartialOrd for Priority {
+            fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for Priority {
+            fn cmp(&self, other: &Self) -> CmpOrdering {
+                if self.0 < 0 || other.0 < 0 {
+                    panic!("Comparison panicked");
+                }
+                self.0.cmp(&other.0)
+            }
+        }
+
+        let queue = ConcurrentKeyedPriorityQueue::<i32, Priority, BinaryHeap<Priority>>::new();
+        for x in 0..64 {
+            queue.push(x, Priority(x));
+        }
+        let result = catch_unwind(AssertUnwindSafe(|| queue.push(64, Priority(-1))));
+        assert!(result.is_err());
+        // Item stays in shard so it is counted
+        let total: usize = queue.shards.iter().map(|shard| shard.lock().len()).sum();
+        assert_eq!(queue.len(), total);
+        queue.clear();
+        assert_eq!(queue.len(), 0);
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+        queue.push(1, Priority(1));
+        assert_eq!(queue.pop_wait(), (1, Priority(1)));
+    }
+
+    #[test]
+    fn test_sync() {
+        fn assert_sync<T: Sync + Send>() {}
+        assert_sync::<ConcurrentKeyedPriorityQueue<i32, i32, BinaryHeap<i32>>>();
+    }
+}
@@ -0,0 +1,152 @@
+/// Defines struct which can be used as multi-level priority.
+///
+/// Fields are compared lexicographically in order of declaration,
+/// each field is marked with direction:
+/// * `max` means that bigger value has more priority,
+/// * `min` means that smaller value has more priority, like if it is wrapped into [`Reverse`].
+///
+/// Macro implements `PartialEq`, `Eq`, `PartialOrd` and `Ord` so they must not be derived.
+/// Any other attributes and derives are passed to struct as is.
+/// All fields must implement `Ord`.
+///
+/// [`Reverse`]: https://doc.rust-lang.org/std/cmp/struct.Reverse.html
+///
+/// # Examples
+///
+/// ```
+/// use keyed_priority_queue::{define_priority, KeyedBinaryPriorityQueue};
+///
+/// define_priority! {
+///     /// Jobs with bigger level go first, then ones which were submitted earlier.
+///     #[derive(Debug, Clone, Copy)]
+///     pub struct JobPriority {
+///         max pub level: u8,
+///         min pub submitted_at: u64,
+///     }
+/// }
+///
+/// let mut queue = KeyedBinaryPriorityQueue::new();
+/// queue.push("old", JobPriority { level: 1, submitted_at: 10 });
+/// queue.push("new", JobPriority { level: 1, submitted_at: 20 });
+/// queue.push("important", JobPriority { level: 5, submitted_at: 30 });
+///
+/// assert_eq!(queue.pop().map(|(key, _)| key), Some("important"));
+/// assert_eq!(queue.pop().map(|(key, _)| key), Some("old"));
+/// assert_eq!(queue.pop().map(|(key, _)| key), Some("new"));
+/// ```
+#[macro_export]
+macro_rules! define_priority {
+    (
+        $(#[$struct_meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $direction:ident $field_vis:vis $field:ident : $field_type:ty
+            ),+ $(,)?
+        }
+    ) => {
+        $(#[$struct_meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $field_type,
+            )+
+        }
+
+        impl ::core::cmp::PartialEq for $name {
+            #[inline]
+            fn eq(&self, other: &Self) -> bool {
+                $(self.$field == other.$field)&&+
+            }
+        }
+
+        impl ::core::cmp::Eq for $name {}
+
+        impl ::core::cmp::PartialOrd for $name {
+            #[inline]
+            fn partial_cmp(&self, other: &Self) -> ::core::option::Option<::core::cmp::Ordering> {
+                ::core::option::Option::Some(::core::cmp::Ord::cmp(self, other))
+            }
+        }
+
+        impl ::core::cmp::Ord for $name {
+            #[inline]
+            fn cmp(&self, other: &Self) -> ::core::cmp::Ordering {
+                ::core::cmp::Ordering::Equal
+                $(
+                    .then_with(|| {
+                        $crate::__priority_field_cmp!($direction, &self.$field, &other.$field)
+                    })
+                )+
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __priority_field_cmp {
+    (max, $a:expr, $b:expr) => {
+        ::core::cmp::Ord::cmp($a, $b)
+    };
+    (min, $a:expr, $b:expr) => {
+        ::core::cmp::Ord::cmp($b, $a)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cmp::Ordering;
+
+    define_priority! {
+        #[derive(Debug, Clone, Copy)]
+        struct Mixed {
+            min first: u32,
+            max second: i32,
+            min third: u8,
+        }
+    }
+
+    #[test]
+    fn test_directions() {
+        let base = Mixed {
+            first: 5,
+            second: 0,
+            third: 5,
+        };
+        let with = |first, second, third| Mixed {
+            first,
+            second,
+            third,
+        };
+        assert_eq!(base.cmp(&base), Ordering::Equal);
+        assert_eq!(base, with(5, 0, 5));
+        // Lower first wins regardless of other fields
+        assert!(with(4, -10, 100) > base);
+        assert!(with(6, 10, 0) < base);
+        // Then bigger second
+        assert!(with(5, 1, 100) > base);
+        assert!(with(5, -1, 0) < base);
+        // Then lower third
+        assert!(with(5, 0, 4) > base);
+        assert!(with(5, 0, 6) < base);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_in_queue() {
+        let mut queue = crate::KeyedWeakPriorityQueue::new();
+        for (key, first, second) in [(0, 1, 1), (1, 0, 1), (2, 0, 2), (3, 2, 5)] {
+            queue.push(
+                key,
+                Mixed {
+                    first,
+                    second,
+                    third: 0,
+                },
+            );
+        }
+        let keys: Vec<_> = std::iter::from_fn(|| queue.pop().map(|(key, _)| key)).collect();
+        assert_eq!(keys, vec![2, 1, 0, 3]);
+    }
+}
@@ -1,54 +1,66 @@
-use crate::heap_traits::{EditableHeap, HeapEntry, HeapIndex};
+use crate::heap_storage::{HeapSlice, HeapStorage};
+use crate::heap_traits::{EditableHeap, HeapEntry, HeapIndex, SiftGuard};
+use crate::keyed_priority_queue::InvariantError;
+use crate::keyed_priority_queue::TryReserveError;
 use std::cmp::{Ord, Ordering};
 use std::fmt::Debug;
+use std::marker::PhantomData;
 use std::vec::Vec;
 
 use crate::mediator::MediatorIndex;
 
+/// Binary heap which keeps items in `TStorage`.
+///
+/// By default priority of every item is stored next to its outer index.
+/// [`SoaStorage`] can be used to keep priorities in separate array.
+///
+/// [`SoaStorage`]: struct.SoaStorage.html
 #[derive(Clone)]
-pub struct BinaryHeap<TPriority>
+pub struct BinaryHeap<TPriority, TStorage = Vec<HeapEntry<TPriority>>>
 where
     TPriority: Ord,
+    TStorage: HeapStorage<TPriority>,
 {
-    data: Vec<HeapEntry<TPriority>>,
+    data: TStorage,
+    _phantom: PhantomData<fn() -> TPriority>,
 }
 
-impl<TPriority: Ord> BinaryHeap<TPriority> {
+impl<TPriority: Ord, TStorage: HeapStorage<TPriority>> BinaryHeap<TPriority, TStorage> {
     fn heapify_up<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
         &mut self,
         position: HeapIndex,
-        mut change_handler: TChangeHandler,
+        change_handler: TChangeHandler,
     ) {
-        debug_assert!(position.0 < self.data.len(), "Out of index in heapify_up");
-        let HeapIndex(mut position) = position;
-        while position > 0 {
-            let parent_pos = (position - 1) / 2;
-            if self.data[parent_pos].priority >= self.data[position].priority {
+        debug_assert!(
+            position.get() < self.data.len(),
+            "Out of index in heapify_up"
+        );
+        let mut item = SiftGuard::new(&mut self.data, position.get(), change_handler);
+        while item.position > 0 {
+            let parent_pos = (item.position - 1) / 2;
+            if item.data.priority(parent_pos) >= item.data.priority(item.position) {
                 break;
             }
-            self.data.swap(parent_pos, position);
-            change_handler(self.data[position].outer_pos, HeapIndex(position));
-            position = parent_pos;
+            item.move_to(parent_pos);
         }
-        change_handler(self.data[position].outer_pos, HeapIndex(position));
     }
 
     fn heapify_down<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
         &mut self,
         position: HeapIndex,
-        mut change_handler: TChangeHandler,
+        change_handler: TChangeHandler,
     ) {
         debug_assert!(position < self.len(), "Out of index in heapify_down");
-        let HeapIndex(mut position) = position;
+        let mut item = SiftGuard::new(&mut self.data, position.get(), change_handler);
         loop {
             let max_child_idx = {
-                let child1 = position * 2 + 1;
+                let child1 = item.position * 2 + 1;
                 let child2 = child1 + 1;
-                if child1 >= self.data.len() {
+                if child1 >= item.data.len() {
                     break;
                 }
-                if child2 < self.data.len()
-                    && self.data[child1].priority <= self.data[child2].priority
+                if child2 < item.data.len()
+                    && item.data.priority(child1) <= item.data.priority(child2)
                 {
                     child2
                 } else {
@@ -56,22 +68,26 @@ impl<TPriority: Ord> BinaryHeap<TPriority> {
                 }
             };
 
-            if self.data[position].priority >= self.data[max_child_idx].priority {
+            if item.data.priority(item.position) >= item.data.priority(max_child_idx) {
                 break;
             }
-            self.data.swap(position, max_child_idx);
-            change_handler(self.data[position].outer_pos, HeapIndex(position));
-            position = max_child_idx;
+            item.move_to(max_child_idx);
         }
-        change_handler(self.data[position].outer_pos, HeapIndex(position));
     }
 }
 
-impl<TPriority: Ord> EditableHeap<TPriority> for BinaryHeap<TPriority> {
-    fn from_entries_vec(heap_base: Vec<HeapEntry<TPriority>>) -> Self {
+impl<TPriority: Ord, TStorage: HeapStorage<TPriority>> EditableHeap<TPriority>
+    for BinaryHeap<TPriority, TStorage>
+{
+    type Alloc = TStorage::Alloc;
+
+    fn from_entries_vec_in(heap_base: Vec<HeapEntry<TPriority>>, alloc: TStorage::Alloc) -> Self {
         let heapify_start = std::cmp::min(heap_base.len() / 2 + 2, heap_base.len());
-        let mut heap = BinaryHeap { data: heap_base };
-        for pos in (0..heapify_start).rev().map(HeapIndex) {
+        let mut heap = BinaryHeap {
+            data: TStorage::from_entries_vec_in(heap_base, alloc),
+            _phantom: PhantomData,
+        };
+        for pos in (0..heapify_start).rev().map(HeapIndex::new) {
             heap.heapify_down(pos, |_, _| {});
         }
 
@@ -83,6 +99,26 @@ impl<TPriority: Ord> EditableHeap<TPriority> for BinaryHeap<TPriority> {
         self.data.reserve(additional)
     }
 
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.data.try_reserve(additional)
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        self.data.shrink_to_fit()
+    }
+
+    #[inline]
+    fn shrink_to(&mut self, min_capacity: usize) {
+        self.data.shrink_to(min_capacity)
+    }
+
     /// Puts outer index and priority in queue
     /// outer_pos is assumed to be unique but not validated
     /// because validation too expensive
@@ -93,11 +129,8 @@ impl<TPriority: Ord> EditableHeap<TPriority> for BinaryHeap<TPriority> {
         priority: TPriority,
         change_handler: TChangeHandler,
     ) {
-        self.data.push(HeapEntry {
-            outer_pos,
-            priority,
-        });
-        self.heapify_up(HeapIndex(self.data.len() - 1), change_handler);
+        self.data.push(outer_pos, priority);
+        self.heapify_up(HeapIndex::new(self.data.len() - 1), change_handler);
     }
 
     /// Removes item at position and returns it
@@ -105,33 +138,38 @@ impl<TPriority: Ord> EditableHeap<TPriority> for BinaryHeap<TPriority> {
     fn remove<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
         &mut self,
         position: HeapIndex,
-        change_handler: TChangeHandler,
+        mut change_handler: TChangeHandler,
     ) -> Option<(MediatorIndex, TPriority)> {
         if position >= self.len() {
             return None;
         }
-        if position.0 + 1 == self.len().0 {
-            let result = self.data.pop().expect("At least 1 item");
-            return Some(result.conv_pair());
+        if position.get() + 1 == self.len().get() {
+            return self.data.pop();
         }
 
-        let result = self.data.swap_remove(position.0);
-        self.heapify_down(position, change_handler);
-        Some(result.conv_pair())
+        let result = self.data.swap_remove(position.get());
+        // Report move before any comparison so panic in it cannot leave stale index
+        change_handler(self.data.outer_pos(position.get()), position);
+        // Moved item can be bigger than parent of removed one
+        self.restore_order(position, change_handler);
+        Some(result)
+    }
+
+    #[inline]
+    fn data(&self) -> HeapSlice<'_, TPriority> {
+        self.data.view()
     }
 
     #[inline]
-    fn data(&self) -> &[HeapEntry<TPriority>] {
-        &self.data
+    fn len(&self) -> HeapIndex {
+        HeapIndex::new(self.data.len())
     }
 
     // Changes outer index for element and return old index
     fn change_outer_pos(&mut self, outer_pos: MediatorIndex, position: HeapIndex) -> MediatorIndex {
         debug_assert!(position < self.len(), "Out of index during changing key");
 
-        let old_pos = self.data[position.0].outer_pos;
-        self.data[position.0].outer_pos = outer_pos;
-        old_pos
+        self.data.replace_outer_pos(position.get(), outer_pos)
     }
 
     /// Changes priority of queue item
@@ -147,8 +185,8 @@ impl<TPriority: Ord> EditableHeap<TPriority> for BinaryHeap<TPriority> {
             "Out of index during changing priority"
         );
 
-        let old = std::mem::replace(&mut self.data[position.0].priority, updated);
-        match old.cmp(&self.data[position.0].priority) {
+        let old = std::mem::replace(self.data.priority_mut(position.get()), updated);
+        match old.cmp(self.data.priority(position.get())) {
             Ordering::Less => {
                 self.heapify_up(position, change_handler);
             }
@@ -160,20 +198,73 @@ impl<TPriority: Ord> EditableHeap<TPriority> for BinaryHeap<TPriority> {
         old
     }
 
+    #[inline]
+    fn priority_mut(&mut self, position: HeapIndex) -> &mut TPriority {
+        self.data.priority_mut(position.get())
+    }
+
+    fn restore_order<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        change_handler: TChangeHandler,
+    ) {
+        debug_assert!(position < self.len(), "Out of index during restoring order");
+
+        let pos = position.get();
+        if pos > 0 && self.data.priority((pos - 1) / 2) < self.data.priority(pos) {
+            self.heapify_up(position, change_handler);
+        } else {
+            self.heapify_down(position, change_handler);
+        }
+    }
+
     fn most_prioritized_idx(&self) -> Option<(MediatorIndex, HeapIndex)> {
-        self.data.get(0).map(|x| (x.outer_pos, HeapIndex(0)))
+        if self.data.is_empty() {
+            return None;
+        }
+        Some((self.data.outer_pos(0), HeapIndex(0)))
     }
 
     #[inline]
     fn clear(&mut self) {
         self.data.clear();
     }
+
+    /// Appends items of other heap and sifts them up like on push
+    /// Calls change_handler for every item of other heap and every move of old values
+    fn meld<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        other: Self,
+        mut change_handler: TChangeHandler,
+    ) {
+        let start = self.data.len();
+        self.data.append(other.data);
+        // Report all new items before any comparison
+        for position in start..self.data.len() {
+            change_handler(self.data.outer_pos(position), HeapIndex::new(position));
+        }
+        for position in start..self.data.len() {
+            self.heapify_up(HeapIndex::new(position), &mut change_handler);
+        }
+    }
+
+    fn validate(&self) -> Result<(), InvariantError> {
+        for child in 1..self.data.len() {
+            let parent = (child - 1) / 2;
+            if self.data.priority(parent) < self.data.priority(child) {
+                return Err(InvariantError::HeapOrder { parent, child });
+            }
+        }
+        Ok(())
+    }
 }
 
-impl<TPriority: Debug + Ord> Debug for BinaryHeap<TPriority> {
+impl<TPriority: Debug + Ord, TStorage: HeapStorage<TPriority>> Debug
+    for BinaryHeap<TPriority, TStorage>
+{
     #[inline]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        self.data.fmt(f)
+        self.data.view().fmt(f)
     }
 }
 
@@ -201,14 +292,14 @@ mod tests {
             70, 50, 0, 1, 2, 4, 6, 7, 9, 72, 4, 4, 87, 78, 72, 6, 7, 9, 2, -50, -72, -50, -42, -1,
             -3, -13,
         ];
-        let mut maximum = std::i32::MIN;
+        let mut maximum = i32::MIN;
         let mut heap = <BinaryHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
-        assert!(heap.data().get(0).is_none());
+        assert!(heap.data.is_empty());
         assert!(is_valid_heap(&heap), "Heap state is invalid");
         for (key, x) in items
             .iter()
             .enumerate()
-            .map(|(i, &x)| (MediatorIndex(i), x))
+            .map(|(i, &x)| (MediatorIndex::new(i), x))
         {
             if x > maximum {
                 maximum = x;
@@ -219,8 +310,8 @@ mod tests {
                 "Heap state is invalid after pushing {}",
                 x
             );
-            assert!(heap.data().get(0).is_some());
-            let heap_max = heap.data().get(0).unwrap().priority;
+            assert!(!heap.data.is_empty());
+            let heap_max = heap.data.first().unwrap().priority;
             assert_eq!(maximum, heap_max)
         }
     }
@@ -237,21 +328,21 @@ mod tests {
             last_positions.insert(outer_pos, position);
         };
         for (i, &x) in items.iter().enumerate() {
-            heap.push(MediatorIndex(i), x, &mut on_pos_change);
+            heap.push(MediatorIndex::new(i), x, &mut on_pos_change);
         }
-        assert_eq!(heap.data().len(), last_positions.len());
+        assert_eq!(heap.data.len(), last_positions.len());
         for i in 0..items.len() {
-            let rem_idx = MediatorIndex(i);
+            let rem_idx = MediatorIndex::new(i);
             assert!(
                 last_positions.contains_key(&rem_idx),
                 "Not for all items change_handler called"
             );
             let position = last_positions[&rem_idx];
             assert_eq!(
-                items[(heap.data().get(position.0).unwrap()).outer_pos.0],
-                heap.data().get(position.0).unwrap().priority
+                items[(heap.data.get(position.get()).unwrap()).outer_pos.get()],
+                heap.data.get(position.get()).unwrap().priority
             );
-            assert_eq!((heap.data().get(position.0).unwrap()).outer_pos, rem_idx);
+            assert_eq!((heap.data.get(position.get()).unwrap()).outer_pos, rem_idx);
         }
 
         let mut removed = HashSet::<MediatorIndex>::new();
@@ -266,22 +357,19 @@ mod tests {
             let (key, _) = popped.unwrap();
             last_positions.remove(&key);
             removed.insert(key);
-            assert_eq!(heap.data().len(), last_positions.len());
-            for i in (0..items.len())
-                .into_iter()
-                .filter(|i| !removed.contains(&MediatorIndex(*i)))
-            {
-                let rem_idx = MediatorIndex(i);
+            assert_eq!(heap.data.len(), last_positions.len());
+            for i in (0..items.len()).filter(|i| !removed.contains(&MediatorIndex::new(*i))) {
+                let rem_idx = MediatorIndex::new(i);
                 assert!(
                     last_positions.contains_key(&rem_idx),
                     "Not for all items change_handler called"
                 );
                 let position = last_positions[&rem_idx];
                 assert_eq!(
-                    items[(heap.data().get(position.0).unwrap()).outer_pos.0],
-                    heap.data().get(position.0).unwrap().priority
+                    items[(heap.data.get(position.get()).unwrap()).outer_pos.get()],
+                    heap.data.get(position.get()).unwrap().priority
                 );
-                assert_eq!((heap.data().get(position.0).unwrap()).outer_pos, rem_idx);
+                assert_eq!((heap.data.get(position.get()).unwrap()).outer_pos, rem_idx);
             }
         }
     }
@@ -303,7 +391,7 @@ mod tests {
 
         let mut heap = <BinaryHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
         for (i, &x) in items.iter().enumerate() {
-            heap.push(MediatorIndex(i), x, |_, _| {});
+            heap.push(MediatorIndex::new(i), x, |_, _| {});
         }
         assert!(is_valid_heap(&heap), "Heap is invalid before pops");
 
@@ -314,7 +402,7 @@ mod tests {
             assert!(pop_res.is_some());
             let (rem_idx, val) = pop_res.unwrap();
             assert_eq!(val, x);
-            assert_eq!(items[rem_idx.0], val);
+            assert_eq!(items[rem_idx.get()], val);
             assert!(is_valid_heap(&heap), "Heap is invalid after {}", x);
         }
 
@@ -342,18 +430,79 @@ mod tests {
         assert!(is_valid_heap(&heap), "Invalid after lowering");
     }
 
+    #[test]
+    fn test_restore_order() {
+        let mut heap = <BinaryHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
+        for x in 0..10 {
+            heap.push(MediatorIndex(x), x as i32, |_, _| {});
+        }
+        assert!(is_valid_heap(&heap), "Invalid before change");
+        *heap.priority_mut(HeapIndex(7)) = 20;
+        heap.restore_order(HeapIndex(7), |_, _| {});
+        assert!(is_valid_heap(&heap), "Invalid after upping");
+        assert_eq!(heap.data[0].priority, 20);
+        *heap.priority_mut(HeapIndex(0)) = -20;
+        heap.restore_order(HeapIndex(0), |_, _| {});
+        assert!(is_valid_heap(&heap), "Invalid after lowering");
+        assert_eq!(heap.data[0].priority, 9);
+    }
+
+    #[test]
+    fn test_remove_middle() {
+        let mut heap = <BinaryHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
+        for (i, &x) in [10, 5, 9, 4, 3, 8, 7].iter().enumerate() {
+            heap.push(MediatorIndex::new(i), x, |_, _| {});
+        }
+        // Last item is moved in place of removed one and it is bigger than new parent
+        heap.remove(HeapIndex(3), |_, _| {});
+        assert_eq!(heap.validate(), Ok(()));
+        assert_eq!(heap.data[1].priority, 7);
+
+        heap.clear();
+        for x in 0..30 {
+            heap.push(MediatorIndex(x), ((x * 7) % 30) as i32, |_, _| {});
+        }
+        assert_eq!(heap.validate(), Ok(()));
+        while !heap.is_empty() {
+            let position = HeapIndex(heap.len().0 / 2);
+            heap.remove(position, |_, _| {});
+            assert_eq!(
+                heap.validate(),
+                Ok(()),
+                "Invalid after removing from the middle"
+            );
+        }
+    }
+
     #[test]
     fn test_clear() {
         let mut heap = <BinaryHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
         for x in 0..5 {
             heap.push(MediatorIndex(x), x as i32, |_, _| {});
         }
-        assert!(!heap.data().is_empty(), "Heap must be non empty");
+        assert!(!heap.data.is_empty(), "Heap must be non empty");
         heap.data.clear();
-        assert!(heap.data().is_empty(), "Heap must be empty");
+        assert!(heap.data.is_empty(), "Heap must be empty");
         assert_eq!(heap.remove(HeapIndex(0), |_, _| {}), None);
     }
 
+    #[test]
+    fn test_capacity() {
+        let mut heap = <BinaryHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
+        heap.reserve(100);
+        assert!(heap.capacity() >= 100);
+        for x in 0..5 {
+            heap.push(MediatorIndex(x), x as i32, |_, _| {});
+        }
+        heap.shrink_to(10);
+        assert!(heap.capacity() >= 10 && heap.capacity() < 100);
+        heap.shrink_to_fit();
+        assert!(heap.capacity() >= 5 && heap.capacity() < 10);
+        assert!(heap.try_reserve(50).is_ok());
+        assert!(heap.capacity() >= 55);
+        assert!(heap.try_reserve(usize::MAX).is_err());
+    }
+
     #[test]
     fn test_change_change_outer_pos() {
         let mut heap = <BinaryHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
@@ -361,7 +510,7 @@ mod tests {
             heap.push(MediatorIndex(x), x as i32, |_, _| {});
         }
         assert_eq!(
-            heap.data().get(0).map(|n| *n),
+            heap.data.first().copied(),
             Some(HeapEntry {
                 outer_pos: MediatorIndex(4),
                 priority: 4i32
@@ -372,7 +521,7 @@ mod tests {
             MediatorIndex(4)
         );
         assert_eq!(
-            heap.data().get(0).map(|n| *n),
+            heap.data.first().copied(),
             Some(HeapEntry {
                 outer_pos: MediatorIndex(10),
                 priority: 4i32
@@ -0,0 +1,463 @@
+use crate::heap_storage::HeapSlice;
+use crate::heap_traits::{EditableHeap, HeapEntry, HeapIndex, SiftGuard};
+use crate::keyed_priority_queue::InvariantError;
+use crate::keyed_priority_queue::TryReserveError;
+use allocator_api2::alloc::Global;
+use std::cmp::{Ord, Ordering};
+use std::fmt::Debug;
+use std::vec::Vec;
+
+use crate::mediator::MediatorIndex;
+
+/// Binary heap which keeps subtrees of `BLOCK_HEIGHT` levels in contiguous blocks.
+///
+/// This is a variant of B-heap. Every block holds 2 sibling subtrees
+/// with `2^BLOCK_HEIGHT - 1` items each in level order, and every leaf of block
+/// has its 2 children in the roots of one child block. Blocks themselves are placed
+/// in level order of the tree of blocks. So sifting item through `BLOCK_HEIGHT` levels
+/// touches only one block and compared children never lie in different blocks,
+/// while [`BinaryHeap`] jumps across memory at every level.
+/// It reduces number of memory pages touched by every operation,
+/// which may help queues that are much bigger than CPU caches,
+/// but every level of tree still uses its own cache line,
+/// so it's worth to compare it with [`BinaryHeap`] on target hardware.
+///
+/// Block height should be chosen to make block fill few cache lines or a memory page:
+/// e.g. block of height 3 with 16 byte items takes 224 bytes.
+/// Block height 1 gives the same layout as [`BinaryHeap`].
+///
+/// ### Examples
+///
+///
+/// ```
+/// use keyed_priority_queue::{BlockedHeap, KeyedPriorityQueue};
+/// // Block of 254 items with 16 bytes each fills a memory page
+/// let mut queue: KeyedPriorityQueue<u32, u64, BlockedHeap<u64, 7>> =
+///     (0..1000).map(|x| (x, u64::from(x) * 7 % 1000)).collect();
+/// assert_eq!(queue.pop(), Some((857, 999)));
+/// queue.set_priority(&0, 5000).unwrap();
+/// assert_eq!(queue.pop(), Some((0, 5000)));
+/// ```
+///
+/// [`BinaryHeap`]: struct.BinaryHeap.html
+#[derive(Clone)]
+pub struct BlockedHeap<TPriority, const BLOCK_HEIGHT: usize = 4>
+where
+    TPriority: Ord,
+{
+    data: Vec<HeapEntry<TPriority>>,
+}
+
+impl<TPriority: Ord, const BLOCK_HEIGHT: usize> BlockedHeap<TPriority, BLOCK_HEIGHT> {
+    const BLOCK_LEN: usize = {
+        assert!(
+            BLOCK_HEIGHT >= 1 && BLOCK_HEIGHT < usize::BITS as usize / 2,
+            "Unsupported block height"
+        );
+        (1 << (BLOCK_HEIGHT + 1)) - 2
+    };
+    // Offset of the first leaf in block
+    const FIRST_LEAF: usize = Self::BLOCK_LEN / 2 - 1;
+    const CHILD_BLOCKS: usize = Self::BLOCK_LEN / 2 + 1;
+
+    // Root isn't part of any block so block offsets start from 1
+    #[inline(always)]
+    fn parent(position: usize) -> usize {
+        debug_assert!(position > 0, "Root has no parent");
+        let offset = (position - 1) % Self::BLOCK_LEN;
+        if offset >= 2 {
+            return position - offset + offset / 2 - 1;
+        }
+        let block = (position - 1) / Self::BLOCK_LEN;
+        if block == 0 {
+            return 0;
+        }
+        let parent_block = (block - 1) / Self::CHILD_BLOCKS;
+        1 + parent_block * Self::BLOCK_LEN + Self::FIRST_LEAF + (block - 1) % Self::CHILD_BLOCKS
+    }
+
+    // Children are always adjacent but may be out of bounds
+    #[inline(always)]
+    fn first_child(position: usize) -> usize {
+        if position == 0 {
+            return 1;
+        }
+        let offset = (position - 1) % Self::BLOCK_LEN;
+        if offset < Self::FIRST_LEAF {
+            return position + offset + 2;
+        }
+        let block = (position - 1) / Self::BLOCK_LEN;
+        let child_block = block * Self::CHILD_BLOCKS + 1 + (offset - Self::FIRST_LEAF);
+        // Saturated positions are out of bounds anyway
+        child_block
+            .saturating_mul(Self::BLOCK_LEN)
+            .saturating_add(1)
+    }
+
+    fn heapify_up<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        change_handler: TChangeHandler,
+    ) {
+        debug_assert!(
+            position.get() < self.data.len(),
+            "Out of index in heapify_up"
+        );
+        let mut item = SiftGuard::new(&mut self.data, position.get(), change_handler);
+        while item.position > 0 {
+            let parent_pos = Self::parent(item.position);
+            if item.data[parent_pos].priority >= item.data[item.position].priority {
+                break;
+            }
+            item.move_to(parent_pos);
+        }
+    }
+
+    fn heapify_down<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        change_handler: TChangeHandler,
+    ) {
+        debug_assert!(position < self.len(), "Out of index in heapify_down");
+        let mut item = SiftGuard::new(&mut self.data, position.get(), change_handler);
+        loop {
+            let max_child_idx = {
+                let child1 = Self::first_child(item.position);
+                let child2 = child1.saturating_add(1);
+                if child1 >= item.data.len() {
+                    break;
+                }
+                if child2 < item.data.len()
+                    && item.data[child1].priority <= item.data[child2].priority
+                {
+                    child2
+                } else {
+                    child1
+                }
+            };
+
+            if item.data[item.position].priority >= item.data[max_child_idx].priority {
+                break;
+            }
+            item.move_to(max_child_idx);
+        }
+    }
+}
+
+impl<TPriority: Ord, const BLOCK_HEIGHT: usize> EditableHeap<TPriority>
+    for BlockedHeap<TPriority, BLOCK_HEIGHT>
+{
+    type Alloc = Global;
+
+    fn from_entries_vec_in(heap_base: Vec<HeapEntry<TPriority>>, _alloc: Global) -> Self {
+        let len = heap_base.len();
+        let mut heap = BlockedHeap { data: heap_base };
+        // Parents always precede children but leaves are spread across blocks
+        for pos in (0..len).rev().map(HeapIndex::new) {
+            heap.heapify_down(pos, |_, _| {});
+        }
+
+        heap
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional)
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.data
+            .try_reserve(additional)
+            .map_err(|_| TryReserveError {})
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        self.data.shrink_to_fit()
+    }
+
+    #[inline]
+    fn shrink_to(&mut self, min_capacity: usize) {
+        self.data.shrink_to(min_capacity)
+    }
+
+    /// Puts outer index and priority in queue
+    /// outer_pos is assumed to be unique but not validated
+    /// because validation too expensive
+    /// Calls change_handler for every move of old values
+    fn push<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        outer_pos: MediatorIndex,
+        priority: TPriority,
+        change_handler: TChangeHandler,
+    ) {
+        self.data.push(HeapEntry {
+            outer_pos,
+            priority,
+        });
+        self.heapify_up(HeapIndex::new(self.data.len() - 1), change_handler);
+    }
+
+    /// Removes item at position and returns it
+    /// Time complexity - O(log n) swaps and change_handler calls
+    fn remove<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        mut change_handler: TChangeHandler,
+    ) -> Option<(MediatorIndex, TPriority)> {
+        if position >= self.len() {
+            return None;
+        }
+        if position.get() + 1 == self.len().get() {
+            let result = self.data.pop().expect("At least 1 item");
+            return Some(result.conv_pair());
+        }
+
+        // Last position is always a leaf so it can be moved in place of removed one
+        let result = self.data.swap_remove(position.get());
+        // Report move before any comparison so panic in it cannot leave stale index
+        change_handler(self.data[position.get()].outer_pos, position);
+        self.restore_order(position, change_handler);
+        Some(result.conv_pair())
+    }
+
+    #[inline]
+    fn data(&self) -> HeapSlice<'_, TPriority> {
+        HeapSlice::from(self.data.as_slice())
+    }
+
+    // Changes outer index for element and return old index
+    fn change_outer_pos(&mut self, outer_pos: MediatorIndex, position: HeapIndex) -> MediatorIndex {
+        debug_assert!(position < self.len(), "Out of index during changing key");
+
+        let old_pos = self.data[position.get()].outer_pos;
+        self.data[position.get()].outer_pos = outer_pos;
+        old_pos
+    }
+
+    /// Changes priority of queue item
+    /// Returns old priority
+    fn change_priority<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        updated: TPriority,
+        change_handler: TChangeHandler,
+    ) -> TPriority {
+        debug_assert!(
+            position < self.len(),
+            "Out of index during changing priority"
+        );
+
+        let old = std::mem::replace(&mut self.data[position.get()].priority, updated);
+        match old.cmp(&self.data[position.get()].priority) {
+            Ordering::Less => {
+                self.heapify_up(position, change_handler);
+            }
+            Ordering::Equal => {}
+            Ordering::Greater => {
+                self.heapify_down(position, change_handler);
+            }
+        }
+        old
+    }
+
+    #[inline]
+    fn priority_mut(&mut self, position: HeapIndex) -> &mut TPriority {
+        &mut self.data[position.get()].priority
+    }
+
+    fn restore_order<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        change_handler: TChangeHandler,
+    ) {
+        debug_assert!(position < self.len(), "Out of index during restoring order");
+
+        let pos = position.get();
+        if pos > 0 && self.data[Self::parent(pos)].priority < self.data[pos].priority {
+            self.heapify_up(position, change_handler);
+        } else {
+            self.heapify_down(position, change_handler);
+        }
+    }
+
+    fn most_prioritized_idx(&self) -> Option<(MediatorIndex, HeapIndex)> {
+        self.data.first().map(|x| (x.outer_pos, HeapIndex(0)))
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Appends items of other heap and sifts them up like on push
+    /// Calls change_handler for every item of other heap and every move of old values
+    fn meld<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        other: Self,
+        mut change_handler: TChangeHandler,
+    ) {
+        let start = self.data.len();
+        self.data.extend(other.data);
+        // Report all new items before any comparison
+        for (position, entry) in self.data.iter().enumerate().skip(start) {
+            change_handler(entry.outer_pos, HeapIndex::new(position));
+        }
+        for position in start..self.data.len() {
+            self.heapify_up(HeapIndex::new(position), &mut change_handler);
+        }
+    }
+
+    fn validate(&self) -> Result<(), InvariantError> {
+        for (child, current) in self.data.iter().enumerate().skip(1) {
+            let parent = Self::parent(child);
+            if self.data[parent].priority < current.priority {
+                return Err(InvariantError::HeapOrder { parent, child });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<TPriority: Debug + Ord, const BLOCK_HEIGHT: usize> Debug
+    for BlockedHeap<TPriority, BLOCK_HEIGHT>
+{
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        self.data.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heap_traits::EditableHeap;
+    use std::collections::HashMap;
+
+    fn check_layout<const BLOCK_HEIGHT: usize>() {
+        type Heap<const H: usize> = BlockedHeap<i32, H>;
+        let mut seen = vec![false; 5000];
+        seen[0] = true;
+        for position in 0..seen.len() {
+            let child = Heap::<BLOCK_HEIGHT>::first_child(position);
+            for child in [child, child + 1] {
+                assert!(child > position);
+                assert_eq!(Heap::<BLOCK_HEIGHT>::parent(child), position);
+                if child < seen.len() {
+                    assert!(!seen[child], "Position {} has 2 parents", child);
+                    seen[child] = true;
+                }
+            }
+        }
+        // Every position has a parent so layout has no holes
+        assert!(seen.iter().all(|&x| x));
+    }
+
+    #[test]
+    fn test_layout() {
+        check_layout::<1>();
+        check_layout::<2>();
+        check_layout::<3>();
+        check_layout::<8>();
+
+        // Height 1 is the textbook layout
+        for position in 1..100 {
+            assert_eq!(BlockedHeap::<i32, 1>::parent(position), (position - 1) / 2);
+        }
+        // Blocks of height 3 hold 14 items after the root
+        assert_eq!(BlockedHeap::<i32, 3>::first_child(1), 3);
+        assert_eq!(BlockedHeap::<i32, 3>::first_child(7), 15);
+        assert_eq!(BlockedHeap::<i32, 3>::first_child(14), 113);
+        assert_eq!(BlockedHeap::<i32, 3>::parent(113), 14);
+        assert_eq!(BlockedHeap::<i32, 3>::parent(16), 7);
+    }
+
+    fn check_heap<const BLOCK_HEIGHT: usize>() {
+        let entries: Vec<_> = (0..1000)
+            .map(|i| HeapEntry {
+                outer_pos: MediatorIndex(i),
+                priority: (i as i32 * 379) % 1000,
+            })
+            .collect();
+        let mut heap =
+            <BlockedHeap<i32, BLOCK_HEIGHT> as EditableHeap<i32>>::from_entries_vec(entries);
+        assert_eq!(heap.validate(), Ok(()));
+        let mut positions: HashMap<_, _> = heap
+            .data()
+            .iter()
+            .enumerate()
+            .map(|(position, (outer_pos, _))| (outer_pos, HeapIndex::new(position)))
+            .collect();
+
+        let mut on_change = |outer_pos: MediatorIndex, position: HeapIndex| {
+            positions.insert(outer_pos, position);
+        };
+        for i in 1000..1100 {
+            heap.push(MediatorIndex(i), i as i32 % 200, &mut on_change);
+        }
+        for i in (0..1100).step_by(7) {
+            let position = positions[&MediatorIndex(i)];
+            heap.change_priority(position, 2000 - i as i32, |o, p| {
+                positions.insert(o, p);
+            });
+        }
+        for i in (3..1100).step_by(13) {
+            let position = positions[&MediatorIndex(i)];
+            heap.remove(position, |o, p| {
+                positions.insert(o, p);
+            });
+            positions.remove(&MediatorIndex(i));
+        }
+        assert_eq!(heap.validate(), Ok(()));
+        assert_eq!(heap.data.len(), positions.len());
+        for (&outer_pos, &position) in positions.iter() {
+            assert_eq!(heap.data[position.get()].outer_pos, outer_pos);
+        }
+
+        let mut popped = Vec::new();
+        while let Some((_, position)) = heap.most_prioritized_idx() {
+            popped.push(heap.remove(position, |_, _| {}).unwrap().1);
+        }
+        assert_eq!(popped.len(), positions.len());
+        assert_eq!(popped[0], 2000);
+        assert!(popped.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    fn test_heap() {
+        check_heap::<1>();
+        check_heap::<2>();
+        check_heap::<4>();
+        check_heap::<8>();
+    }
+
+    #[test]
+    fn test_validate() {
+        let entries: Vec<_> = (0..40)
+            .map(|i| HeapEntry {
+                outer_pos: MediatorIndex(i),
+                priority: i as i32,
+            })
+            .collect();
+        let mut heap = <BlockedHeap<i32, 2> as EditableHeap<i32>>::from_entries_vec(entries);
+        assert_eq!(heap.validate(), Ok(()));
+        // Root of the second block
+        *heap.priority_mut(HeapIndex(7)) = 100;
+        assert_eq!(
+            heap.validate(),
+            Err(InvariantError::HeapOrder {
+                parent: 3,
+                child: 7
+            })
+        );
+        heap.restore_order(HeapIndex(7), |_, _| {});
+        assert_eq!(heap.validate(), Ok(()));
+        assert_eq!(heap.data[0].priority, 100);
+    }
+}
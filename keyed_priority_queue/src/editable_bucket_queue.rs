@@ -0,0 +1,620 @@
+use crate::bucket_lists::BucketLists;
+use crate::heap_storage::HeapSlice;
+use crate::heap_traits::{EditableHeap, HeapEntry, HeapIndex};
+use crate::keyed_priority_queue::InvariantError;
+use crate::keyed_priority_queue::TryReserveError;
+use allocator_api2::alloc::Global;
+use std::cmp::Reverse;
+use std::fmt::Debug;
+use std::vec::Vec;
+
+use crate::mediator::MediatorIndex;
+
+/// Priority which can be stored in [`BucketQueue`].
+///
+/// Every priority is mapped to index of bucket and items from bucket with
+/// the smallest index are the most prioritized, so index must be consistent with `Ord`:
+/// if `a > b` then `a.bucket_index() <= b.bucket_index()`.
+/// Items from the same bucket are considered equal and returned in arbitrary order.
+///
+/// Queue keeps buckets for the whole range between the smallest and the biggest
+/// index stored at the same time, so this range must stay small.
+/// Queue panics if the range doesn't fit into `usize`.
+/// In that case item is not pushed and changed priority is not applied
+/// unless it was changed through `get_mut` guard.
+///
+/// It is implemented for `Reverse` of `u8` and `u16`
+/// so the smallest numbers have the biggest priority.
+///
+/// [`BucketQueue`]: struct.BucketQueue.html
+pub trait BucketPriority: Ord {
+    fn bucket_index(&self) -> usize;
+}
+
+macro_rules! impl_bucket_priority_for_reverse {
+    ($($t:ty),*) => {
+        $(
+            impl BucketPriority for Reverse<$t> {
+                #[inline(always)]
+                fn bucket_index(&self) -> usize {
+                    self.0 as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_bucket_priority_for_reverse!(u8, u16);
+
+/// Bucket queue for priorities from small integer range, e.g. for Dial's algorithm.
+///
+/// It keeps one bucket per priority value so `push`, `change_priority` and `remove`
+/// take ***O(1)*** time and pop advances cursor to next non-empty bucket
+/// which is amortized ***O(1)*** when popped priorities never decrease.
+///
+/// Buckets form circular array which starts at cursor like in Dial's algorithm
+/// so bucket index `i` is stored at `i % buckets`.
+/// Array is rebuilt with doubled size when stored indexes don't fit into it
+/// so memory usage grows with the biggest difference of stored bucket indexes
+/// and not with their values.
+///
+/// ### Examples
+///
+/// ```
+/// use keyed_priority_queue::KeyedBucketPriorityQueue;
+/// use std::cmp::Reverse;
+///
+/// let mut queue = KeyedBucketPriorityQueue::new();
+/// queue.push("a", Reverse(3u8));
+/// queue.push("b", Reverse(1));
+/// queue.push("c", Reverse(5));
+/// assert_eq!(queue.pop(), Some(("b", Reverse(1))));
+///
+/// queue.set_priority(&"c", Reverse(2)).unwrap();
+/// assert_eq!(queue.pop(), Some(("c", Reverse(2))));
+/// assert_eq!(queue.pop(), Some(("a", Reverse(3))));
+/// ```
+#[derive(Clone)]
+pub struct BucketQueue<TPriority>
+where
+    TPriority: BucketPriority,
+{
+    data: Vec<HeapEntry<TPriority>>,
+    buckets: BucketLists,
+    // The lowest non-empty bucket index.
+    // All stored indexes are in range `cursor..cursor + buckets`
+    cursor: usize,
+}
+
+const MIN_BUCKETS: usize = 16;
+
+impl<TPriority: BucketPriority> BucketQueue<TPriority> {
+    #[inline(always)]
+    fn bucket_of(&self, index: usize) -> usize {
+        index % self.buckets.buckets()
+    }
+
+    fn advance_cursor(&mut self) {
+        if self.data.is_empty() {
+            self.cursor = 0;
+            return;
+        }
+        // There is stored index in `cursor..cursor + buckets` so it can't overflow
+        while self.buckets.first(self.bucket_of(self.cursor)).is_none() {
+            self.cursor += 1;
+        }
+    }
+
+    /// Links position which isn't linked yet.
+    /// Moves start of circular array down if its index is smaller than cursor
+    /// and rebuilds the array if index doesn't fit.
+    /// Returns false and leaves position unlinked if range of indexes is too big.
+    #[must_use]
+    fn place(&mut self, position: usize) -> bool {
+        let index = self.data[position].priority.bucket_index();
+        let buckets = self.buckets.buckets();
+        let fits = if self.data.len() == 1 {
+            buckets != 0
+        } else if index >= self.cursor {
+            index - self.cursor < buckets
+        } else {
+            // Buckets at the top of current range become bottom ones
+            // so they must be empty
+            self.cursor - index < buckets
+                && (index..self.cursor).all(|i| self.buckets.first(i % buckets).is_none())
+        };
+        if fits {
+            self.buckets.link(position, self.bucket_of(index));
+            if self.data.len() == 1 || index < self.cursor {
+                self.cursor = index;
+            }
+            return true;
+        }
+        match self.fitting_range() {
+            Some((buckets, min)) => {
+                self.relink_all(buckets, min);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns size and start of circular array which fits all stored indexes
+    /// or None if their range doesn't fit into `usize`.
+    fn fitting_range(&self) -> Option<(usize, usize)> {
+        let mut indexes = self.data.iter().map(|x| x.priority.bucket_index());
+        let first = indexes.next()?;
+        let (min, max) = indexes.fold((first, first), |(min, max), x| {
+            (std::cmp::min(min, x), std::cmp::max(max, x))
+        });
+        let buckets = (max - min)
+            .checked_add(1)
+            .and_then(usize::checked_next_power_of_two)?;
+        Some((std::cmp::max(buckets, MIN_BUCKETS), min))
+    }
+
+    fn relink_all(&mut self, buckets: usize, min: usize) {
+        self.buckets.reset(buckets);
+        for position in 0..self.data.len() {
+            let bucket = self.bucket_of(self.data[position].priority.bucket_index());
+            self.buckets.link(position, bucket);
+        }
+        self.cursor = min;
+    }
+
+    /// Relinks all positions into circular array which fits all stored indexes.
+    fn rebuild(&mut self) {
+        if self.data.is_empty() {
+            self.buckets.reset(0);
+            self.cursor = 0;
+            return;
+        }
+        let (buckets, min) = self.fitting_range().expect(RANGE_TOO_BIG);
+        self.relink_all(buckets, min);
+    }
+
+    /// Returns false and keeps position in its old bucket if range of indexes is too big.
+    #[must_use]
+    fn move_to_bucket(&mut self, position: usize) -> bool {
+        let index = self.data[position].priority.bucket_index();
+        if index >= self.cursor && index - self.cursor < self.buckets.buckets() {
+            if self.buckets.relink(position, self.bucket_of(index)) {
+                self.advance_cursor();
+            }
+            return true;
+        }
+        let old_bucket = self.buckets.bucket(position);
+        self.buckets.unlink(position);
+        if !self.place(position) {
+            self.buckets.link(position, old_bucket);
+            return false;
+        }
+        self.advance_cursor();
+        true
+    }
+}
+
+const RANGE_TOO_BIG: &str = "Range of bucket indexes is too big";
+
+impl<TPriority: BucketPriority> EditableHeap<TPriority> for BucketQueue<TPriority> {
+    type Alloc = Global;
+
+    fn from_entries_vec_in(heap_base: Vec<HeapEntry<TPriority>>, _alloc: Global) -> Self {
+        let mut queue = BucketQueue {
+            buckets: BucketLists::with_len(heap_base.len()),
+            data: heap_base,
+            cursor: 0,
+        };
+        queue.rebuild();
+        queue
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+        self.buckets.reserve(additional);
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.data
+            .try_reserve(additional)
+            .map_err(|_| TryReserveError {})?;
+        self.buckets.try_reserve(additional)
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        std::cmp::min(self.data.capacity(), self.buckets.capacity())
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.data.shrink_to_fit();
+        self.rebuild();
+        self.buckets.shrink_to_fit();
+    }
+
+    #[inline]
+    fn shrink_to(&mut self, min_capacity: usize) {
+        self.data.shrink_to(min_capacity);
+        self.buckets.shrink_to(min_capacity);
+    }
+
+    /// Puts outer index and priority in queue
+    /// outer_pos is assumed to be unique but not validated
+    /// because validation too expensive
+    /// Calls change_handler only for new item
+    fn push<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        outer_pos: MediatorIndex,
+        priority: TPriority,
+        mut change_handler: TChangeHandler,
+    ) {
+        let position = self.data.len();
+        self.data.push(HeapEntry {
+            outer_pos,
+            priority,
+        });
+        self.buckets.push_unlinked();
+        if !self.place(position) {
+            // Item is removed so queue stays consistent after panic
+            self.buckets.pop_unlinked();
+            self.data.pop();
+            panic!("{}", RANGE_TOO_BIG);
+        }
+        change_handler(outer_pos, HeapIndex::new(position));
+    }
+
+    /// Removes item at position and returns it
+    /// Time complexity - O(1) swaps and change_handler calls
+    /// and amortized O(1) for advancing of cursor
+    fn remove<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        mut change_handler: TChangeHandler,
+    ) -> Option<(MediatorIndex, TPriority)> {
+        if position >= self.len() {
+            return None;
+        }
+        let position = position.get();
+        let last = self.data.len() - 1;
+        self.buckets.swap_remove(position);
+        let removed = self.data.swap_remove(position);
+        if position != last {
+            change_handler(self.data[position].outer_pos, HeapIndex::new(position));
+        }
+        self.advance_cursor();
+        Some(removed.conv_pair())
+    }
+
+    #[inline]
+    fn data(&self) -> HeapSlice<'_, TPriority> {
+        HeapSlice::from(self.data.as_slice())
+    }
+
+    // Changes outer index for element and return old index
+    fn change_outer_pos(&mut self, outer_pos: MediatorIndex, position: HeapIndex) -> MediatorIndex {
+        debug_assert!(position < self.len(), "Out of index during changing key");
+
+        let old_pos = self.data[position.get()].outer_pos;
+        self.data[position.get()].outer_pos = outer_pos;
+        old_pos
+    }
+
+    /// Changes priority of queue item
+    /// Returns old priority
+    fn change_priority<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        updated: TPriority,
+        _change_handler: TChangeHandler,
+    ) -> TPriority {
+        debug_assert!(
+            position < self.len(),
+            "Out of index during changing priority"
+        );
+
+        let old = std::mem::replace(&mut self.data[position.get()].priority, updated);
+        if !self.move_to_bucket(position.get()) {
+            // Old priority matches bucket of item so queue stays consistent after panic
+            self.data[position.get()].priority = old;
+            panic!("{}", RANGE_TOO_BIG);
+        }
+        old
+    }
+
+    #[inline]
+    fn priority_mut(&mut self, position: HeapIndex) -> &mut TPriority {
+        &mut self.data[position.get()].priority
+    }
+
+    fn restore_order<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        _change_handler: TChangeHandler,
+    ) {
+        debug_assert!(position < self.len(), "Out of index during restoring order");
+        // Priority is already changed so item is just kept linked in its old bucket
+        if !self.move_to_bucket(position.get()) {
+            panic!("{}", RANGE_TOO_BIG);
+        }
+    }
+
+    fn most_prioritized_idx(&self) -> Option<(MediatorIndex, HeapIndex)> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let position = self.buckets.first(self.bucket_of(self.cursor))?;
+        Some((self.data[position].outer_pos, HeapIndex::new(position)))
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.data.clear();
+        self.buckets.clear();
+        self.cursor = 0;
+    }
+
+    fn validate(&self) -> Result<(), InvariantError> {
+        let data = &self.data;
+        let buckets = self.buckets.buckets();
+        let is_valid = data.iter().all(|entry| {
+            let index = entry.priority.bucket_index();
+            index >= self.cursor && index - self.cursor < buckets
+        }) && self.buckets.is_valid(data.len(), |position| {
+            self.bucket_of(data[position].priority.bucket_index())
+        }) && (data.is_empty()
+            || self.buckets.first(self.bucket_of(self.cursor)).is_some());
+        if is_valid {
+            Ok(())
+        } else {
+            Err(InvariantError::HeapStructure)
+        }
+    }
+}
+
+impl<TPriority: Debug + BucketPriority> Debug for BucketQueue<TPriority> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        self.data.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heap_traits::EditableHeap;
+    use std::collections::HashMap;
+
+    fn new_queue() -> BucketQueue<Reverse<u8>> {
+        <BucketQueue<Reverse<u8>> as EditableHeap<Reverse<u8>>>::from_entries_vec(Vec::new())
+    }
+
+    fn check_positions(
+        queue: &BucketQueue<Reverse<u8>>,
+        positions: &HashMap<MediatorIndex, HeapIndex>,
+    ) {
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.data.len(), positions.len());
+        for (&outer_pos, &position) in positions.iter() {
+            assert_eq!(queue.data[position.get()].outer_pos, outer_pos);
+        }
+    }
+
+    fn pop_all(
+        queue: &mut BucketQueue<Reverse<u8>>,
+        positions: &mut HashMap<MediatorIndex, HeapIndex>,
+    ) -> Vec<u8> {
+        let mut popped = Vec::new();
+        while let Some((_, position)) = queue.most_prioritized_idx() {
+            let (outer_pos, Reverse(priority)) = queue
+                .remove(position, |o, p| {
+                    positions.insert(o, p);
+                })
+                .unwrap();
+            positions.remove(&outer_pos);
+            check_positions(queue, positions);
+            popped.push(priority);
+        }
+        popped
+    }
+
+    #[test]
+    fn test_pop_order() {
+        let items = [
+            7u8, 50, 0, 1, 2, 4, 6, 7, 9, 72, 4, 4, 87, 78, 72, 6, 7, 9, 2, 255, 3,
+        ];
+        let mut positions = HashMap::new();
+        let mut queue = new_queue();
+        for (i, &x) in items.iter().enumerate() {
+            queue.push(MediatorIndex::new(i), Reverse(x), |o, p| {
+                positions.insert(o, p);
+            });
+            check_positions(&queue, &positions);
+        }
+        let mut sorted = items;
+        sorted.sort_unstable();
+        assert_eq!(pop_all(&mut queue, &mut positions), sorted.to_vec());
+        assert_eq!(queue.most_prioritized_idx(), None);
+    }
+
+    #[test]
+    fn test_change_priority() {
+        let mut positions = HashMap::new();
+        let mut queue = new_queue();
+        for i in 0..100u8 {
+            queue.push(
+                MediatorIndex::new(i as usize),
+                Reverse(i / 3 + 20),
+                |o, p| {
+                    positions.insert(o, p);
+                },
+            );
+        }
+        check_positions(&queue, &positions);
+        for i in (0..100).step_by(7) {
+            let position = positions[&MediatorIndex(i)];
+            let old = queue.change_priority(position, Reverse(i as u8 % 30), |_, _| {});
+            assert_eq!(old, Reverse(i as u8 / 3 + 20));
+            check_positions(&queue, &positions);
+        }
+        assert_eq!(queue.most_prioritized_idx().unwrap().0, MediatorIndex(0));
+        // Move the most prioritized item to the end
+        let (_, position) = queue.most_prioritized_idx().unwrap();
+        *queue.priority_mut(position) = Reverse(200);
+        queue.restore_order(position, |_, _| {});
+        check_positions(&queue, &positions);
+
+        let popped = pop_all(&mut queue, &mut positions);
+        assert!(popped.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(popped.len(), 100);
+        assert_eq!(popped.last(), Some(&200));
+    }
+
+    #[test]
+    fn test_from_entries_vec() {
+        let entries: Vec<_> = [5u8, 1, 9, 200, 3, 3]
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| HeapEntry {
+                outer_pos: MediatorIndex::new(i),
+                priority: Reverse(x),
+            })
+            .collect();
+        let mut queue =
+            <BucketQueue<Reverse<u8>> as EditableHeap<Reverse<u8>>>::from_entries_vec(entries);
+        let mut positions: HashMap<_, _> =
+            (0..6).map(|i| (MediatorIndex(i), HeapIndex(i))).collect();
+        check_positions(&queue, &positions);
+        assert_eq!(
+            pop_all(&mut queue, &mut positions),
+            vec![1u8, 3, 3, 5, 9, 200]
+        );
+
+        queue.push(MediatorIndex(0), Reverse(100), |_, _| {});
+        queue.shrink_to_fit();
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.buckets.buckets(), MIN_BUCKETS);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut queue = new_queue();
+        for i in 0..10u8 {
+            queue.push(MediatorIndex::new(i as usize), Reverse(i), |_, _| {});
+        }
+        assert_eq!(queue.validate(), Ok(()));
+        *queue.priority_mut(HeapIndex(3)) = Reverse(1);
+        assert_eq!(queue.validate(), Err(InvariantError::HeapStructure));
+        queue.restore_order(HeapIndex(3), |_, _| {});
+        assert_eq!(queue.validate(), Ok(()));
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Far(Reverse<usize>);
+
+    impl BucketPriority for Far {
+        fn bucket_index(&self) -> usize {
+            (self.0).0
+        }
+    }
+
+    #[test]
+    fn test_large_bucket_indexes() {
+        let mut queue = <BucketQueue<Far> as EditableHeap<Far>>::from_entries_vec(Vec::new());
+        for (i, &x) in [usize::MAX, usize::MAX - 3, usize::MAX - 1]
+            .iter()
+            .enumerate()
+        {
+            queue.push(MediatorIndex::new(i), Far(Reverse(x)), |_, _| {});
+            assert_eq!(queue.validate(), Ok(()));
+        }
+        assert_eq!(queue.buckets.buckets(), MIN_BUCKETS);
+
+        let mut popped = Vec::new();
+        while let Some((_, position)) = queue.most_prioritized_idx() {
+            let (_, Far(Reverse(x))) = queue.remove(position, |_, _| {}).unwrap();
+            assert_eq!(queue.validate(), Ok(()));
+            popped.push(x);
+        }
+        assert_eq!(popped, vec![usize::MAX - 3, usize::MAX - 1, usize::MAX]);
+    }
+
+    #[test]
+    fn test_too_big_range_leaves_queue_unchanged() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut queue = <BucketQueue<Far> as EditableHeap<Far>>::from_entries_vec(Vec::new());
+        queue.push(MediatorIndex(0), Far(Reverse(0)), |_, _| {});
+        queue.push(MediatorIndex(1), Far(Reverse(5)), |_, _| {});
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            queue.push(MediatorIndex(2), Far(Reverse(usize::MAX)), |_, _| {})
+        }));
+        assert!(result.is_err());
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.data.len(), 2);
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            queue.change_priority(HeapIndex(1), Far(Reverse(usize::MAX)), |_, _| {})
+        }));
+        assert!(result.is_err());
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.data[1].priority, Far(Reverse(5)));
+
+        let (_, position) = queue.most_prioritized_idx().unwrap();
+        assert_eq!(
+            queue.remove(position, |_, _| {}),
+            Some((MediatorIndex(0), Far(Reverse(0))))
+        );
+        let (_, position) = queue.most_prioritized_idx().unwrap();
+        assert_eq!(
+            queue.remove(position, |_, _| {}),
+            Some((MediatorIndex(1), Far(Reverse(5))))
+        );
+        assert_eq!(queue.most_prioritized_idx(), None);
+    }
+
+    #[test]
+    fn test_circular_buckets() {
+        fn push(
+            queue: &mut BucketQueue<Reverse<u8>>,
+            positions: &mut HashMap<MediatorIndex, HeapIndex>,
+            i: usize,
+            x: u8,
+        ) {
+            queue.push(MediatorIndex::new(i), Reverse(x), |o, p| {
+                positions.insert(o, p);
+            });
+            check_positions(queue, positions);
+        }
+
+        let mut positions = HashMap::new();
+        let mut queue = new_queue();
+        push(&mut queue, &mut positions, 0, 100);
+        push(&mut queue, &mut positions, 1, 110);
+        // Fits into range after moving its start down
+        push(&mut queue, &mut positions, 2, 95);
+        assert_eq!(queue.buckets.buckets(), MIN_BUCKETS);
+        // Top buckets are used so range must grow
+        push(&mut queue, &mut positions, 3, 90);
+        assert_eq!(queue.buckets.buckets(), 32);
+        push(&mut queue, &mut positions, 4, 250);
+        assert_eq!(queue.buckets.buckets(), 256);
+
+        let position = HeapIndex(4);
+        assert_eq!(
+            queue.change_priority(position, Reverse(0), |_, _| {}),
+            Reverse(250)
+        );
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(
+            pop_all(&mut queue, &mut positions),
+            vec![0, 90, 95, 100, 110]
+        );
+
+        push(&mut queue, &mut positions, 0, 200);
+        queue.shrink_to_fit();
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.buckets.buckets(), MIN_BUCKETS);
+    }
+}
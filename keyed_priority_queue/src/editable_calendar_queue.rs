@@ -0,0 +1,530 @@
+use crate::bucket_lists::BucketLists;
+use crate::heap_storage::HeapSlice;
+use crate::heap_traits::{EditableHeap, HeapEntry, HeapIndex};
+use crate::keyed_priority_queue::InvariantError;
+use crate::keyed_priority_queue::TryReserveError;
+use allocator_api2::alloc::Global;
+use std::cmp::Reverse;
+use std::fmt::Debug;
+use std::vec::Vec;
+
+use crate::mediator::MediatorIndex;
+
+/// Priority which can be stored in [`CalendarQueue`].
+///
+/// Every priority is mapped to time of event and events with the earliest time
+/// are the most prioritized, so time must be consistent with `Ord`:
+/// if `a > b` then `a.timestamp() <= b.timestamp()`.
+/// Events with the same time are returned in arbitrary order.
+///
+/// It is implemented for `Reverse` of unsigned integers
+/// so the smallest times have the biggest priority.
+///
+/// [`CalendarQueue`]: struct.CalendarQueue.html
+pub trait CalendarPriority: Ord {
+    fn timestamp(&self) -> u64;
+}
+
+macro_rules! impl_calendar_priority_for_reverse {
+    ($($t:ty),*) => {
+        $(
+            impl CalendarPriority for Reverse<$t> {
+                #[inline(always)]
+                fn timestamp(&self) -> u64 {
+                    self.0 as u64
+                }
+            }
+        )*
+    };
+}
+
+impl_calendar_priority_for_reverse!(u16, u32, u64, usize);
+
+const NONE: usize = usize::MAX;
+const MIN_BUCKETS: usize = 2;
+
+/// Calendar queue for events of discrete-event simulation.
+///
+/// Time is split into days of equal width and every bucket keeps events of the days
+/// which are equal modulo number of buckets, like a calendar keeps days of different years.
+/// Number of buckets is doubled or halved together with number of events
+/// and width of day is recalculated from average gap between events so
+/// every operation takes ***O(1)*** expected time when gaps between events are steady.
+/// Events can be rescheduled or cancelled in ***O(1)*** expected time too.
+///
+/// ### Examples
+///
+/// ```
+/// use keyed_priority_queue::KeyedCalendarPriorityQueue;
+/// use std::cmp::Reverse;
+///
+/// let mut events = KeyedCalendarPriorityQueue::new();
+/// events.push("timeout", Reverse(500u64));
+/// events.push("packet", Reverse(120));
+/// events.push("ack", Reverse(250));
+///
+/// // Reschedule and cancel events by their IDs
+/// events.set_priority(&"ack", Reverse(100)).unwrap();
+/// events.remove(&"timeout");
+///
+/// assert_eq!(events.pop(), Some(("ack", Reverse(100))));
+/// assert_eq!(events.pop(), Some(("packet", Reverse(120))));
+/// assert_eq!(events.pop(), None);
+/// ```
+#[derive(Clone)]
+pub struct CalendarQueue<TPriority>
+where
+    TPriority: CalendarPriority,
+{
+    data: Vec<HeapEntry<TPriority>>,
+    buckets: BucketLists,
+    // Width of day in units of timestamp
+    width: u64,
+    // Position of the earliest event
+    top: usize,
+    // Time of the earliest event
+    last: u64,
+}
+
+impl<TPriority: CalendarPriority> CalendarQueue<TPriority> {
+    #[inline(always)]
+    fn time_at(&self, position: usize) -> u64 {
+        self.data[position].priority.timestamp()
+    }
+
+    #[inline(always)]
+    fn bucket_of(&self, time: u64) -> usize {
+        // Number of buckets is power of two
+        ((time / self.width) as usize) & (self.buckets.buckets() - 1)
+    }
+
+    fn set_top(&mut self, position: usize) {
+        self.top = position;
+        self.last = self.time_at(position);
+    }
+
+    // Scans calendar day by day starting from time of previous top
+    // which is not bigger than time of any event.
+    fn find_top(&mut self) {
+        if self.data.is_empty() {
+            self.top = NONE;
+            return;
+        }
+        let mut day = self.last / self.width;
+        for _ in 0..self.buckets.buckets() {
+            let bucket = (day as usize) & (self.buckets.buckets() - 1);
+            let found = self
+                .buckets
+                .iter_bucket(bucket)
+                .filter(|&position| self.time_at(position) / self.width == day)
+                .min_by_key(|&position| self.time_at(position));
+            if let Some(position) = found {
+                self.set_top(position);
+                return;
+            }
+            day = day.wrapping_add(1);
+        }
+        // Gap to next event is longer than a year so search it directly
+        let position = (0..self.data.len())
+            .min_by_key(|&position| self.time_at(position))
+            .expect("Queue is not empty");
+        self.set_top(position);
+    }
+
+    // Recalculates width of day and puts events to new buckets
+    fn resize(&mut self, buckets: usize) {
+        let len = self.data.len() as u64;
+        let times = self.data.iter().map(|entry| entry.priority.timestamp());
+        self.width = match (times.clone().min(), times.max()) {
+            // Day should contain few events on average
+            (Some(min), Some(max)) if len > 1 => ((max - min) / len).saturating_mul(3).max(1),
+            _ => 1,
+        };
+        self.buckets.reset(buckets);
+        for position in 0..self.data.len() {
+            let bucket = self.bucket_of(self.time_at(position));
+            self.buckets.link(position, bucket);
+        }
+    }
+
+    fn move_to_bucket(&mut self, position: usize) {
+        let time = self.time_at(position);
+        let bucket = self.bucket_of(time);
+        self.buckets.relink(position, bucket);
+        if position == self.top {
+            if time <= self.last {
+                self.last = time;
+            } else {
+                self.find_top();
+            }
+        } else if time < self.last {
+            self.set_top(position);
+        }
+    }
+}
+
+impl<TPriority: CalendarPriority> EditableHeap<TPriority> for CalendarQueue<TPriority> {
+    type Alloc = Global;
+
+    fn from_entries_vec_in(heap_base: Vec<HeapEntry<TPriority>>, _alloc: Global) -> Self {
+        let buckets = std::cmp::max(heap_base.len().next_power_of_two(), MIN_BUCKETS);
+        let mut queue = CalendarQueue {
+            buckets: BucketLists::with_len(heap_base.len()),
+            data: heap_base,
+            width: 1,
+            top: NONE,
+            last: 0,
+        };
+        queue.resize(buckets);
+        if let Some(position) = (0..queue.data.len()).min_by_key(|&x| queue.time_at(x)) {
+            queue.set_top(position);
+        }
+        queue
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+        self.buckets.reserve(additional);
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.data
+            .try_reserve(additional)
+            .map_err(|_| TryReserveError {})?;
+        self.buckets.try_reserve(additional)
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        std::cmp::min(self.data.capacity(), self.buckets.capacity())
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        // Buckets are already shrunk along with number of events
+        self.data.shrink_to_fit();
+        self.buckets.shrink_to(0);
+    }
+
+    #[inline]
+    fn shrink_to(&mut self, min_capacity: usize) {
+        self.data.shrink_to(min_capacity);
+        self.buckets.shrink_to(min_capacity);
+    }
+
+    /// Puts outer index and priority in queue
+    /// outer_pos is assumed to be unique but not validated
+    /// because validation too expensive
+    /// Calls change_handler only for new item
+    fn push<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        outer_pos: MediatorIndex,
+        priority: TPriority,
+        mut change_handler: TChangeHandler,
+    ) {
+        let time = priority.timestamp();
+        let position = self.data.len();
+        self.data.push(HeapEntry {
+            outer_pos,
+            priority,
+        });
+        self.buckets.push(self.bucket_of(time));
+        if self.top == NONE || time < self.last {
+            self.set_top(position);
+        }
+        if self.data.len() > 2 * self.buckets.buckets() {
+            self.resize(2 * self.buckets.buckets());
+        }
+        change_handler(outer_pos, HeapIndex::new(position));
+    }
+
+    /// Removes item at position and returns it
+    /// Time complexity - O(1) swaps and change_handler calls
+    /// and expected O(1) for search of the next event
+    fn remove<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        mut change_handler: TChangeHandler,
+    ) -> Option<(MediatorIndex, TPriority)> {
+        if position >= self.len() {
+            return None;
+        }
+        let position = position.get();
+        let last = self.data.len() - 1;
+        let was_top = position == self.top;
+        self.buckets.swap_remove(position);
+        let removed = self.data.swap_remove(position);
+        if position != last {
+            change_handler(self.data[position].outer_pos, HeapIndex::new(position));
+            if self.top == last {
+                self.top = position;
+            }
+        }
+        let buckets = self.buckets.buckets();
+        if buckets > MIN_BUCKETS && self.data.len() < buckets / 2 {
+            self.resize(buckets / 2);
+        }
+        if was_top {
+            self.find_top();
+        }
+        Some(removed.conv_pair())
+    }
+
+    #[inline]
+    fn data(&self) -> HeapSlice<'_, TPriority> {
+        HeapSlice::from(self.data.as_slice())
+    }
+
+    // Changes outer index for element and return old index
+    fn change_outer_pos(&mut self, outer_pos: MediatorIndex, position: HeapIndex) -> MediatorIndex {
+        debug_assert!(position < self.len(), "Out of index during changing key");
+
+        let old_pos = self.data[position.get()].outer_pos;
+        self.data[position.get()].outer_pos = outer_pos;
+        old_pos
+    }
+
+    /// Changes priority of queue item
+    /// Returns old priority
+    fn change_priority<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        updated: TPriority,
+        _change_handler: TChangeHandler,
+    ) -> TPriority {
+        debug_assert!(
+            position < self.len(),
+            "Out of index during changing priority"
+        );
+
+        let old = std::mem::replace(&mut self.data[position.get()].priority, updated);
+        self.move_to_bucket(position.get());
+        old
+    }
+
+    #[inline]
+    fn priority_mut(&mut self, position: HeapIndex) -> &mut TPriority {
+        &mut self.data[position.get()].priority
+    }
+
+    fn restore_order<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        _change_handler: TChangeHandler,
+    ) {
+        debug_assert!(position < self.len(), "Out of index during restoring order");
+        self.move_to_bucket(position.get());
+    }
+
+    fn most_prioritized_idx(&self) -> Option<(MediatorIndex, HeapIndex)> {
+        if self.top == NONE {
+            return None;
+        }
+        Some((self.data[self.top].outer_pos, HeapIndex::new(self.top)))
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.data.clear();
+        self.buckets.clear();
+        self.buckets.reset(MIN_BUCKETS);
+        self.width = 1;
+        self.top = NONE;
+    }
+
+    fn validate(&self) -> Result<(), InvariantError> {
+        let buckets = self.buckets.buckets();
+        if buckets < MIN_BUCKETS || !buckets.is_power_of_two() || self.width == 0 {
+            return Err(InvariantError::HeapStructure);
+        }
+        let is_valid_top = if self.data.is_empty() {
+            self.top == NONE
+        } else {
+            self.top < self.data.len()
+                && self.time_at(self.top) == self.last
+                && self
+                    .data
+                    .iter()
+                    .all(|x| x.priority.timestamp() >= self.last)
+        };
+        if !is_valid_top
+            || !self.buckets.is_valid(self.data.len(), |position| {
+                self.bucket_of(self.time_at(position))
+            })
+        {
+            return Err(InvariantError::HeapStructure);
+        }
+        Ok(())
+    }
+}
+
+impl<TPriority: Debug + CalendarPriority> Debug for CalendarQueue<TPriority> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        self.data.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heap_traits::EditableHeap;
+    use std::collections::HashMap;
+
+    type Queue = CalendarQueue<Reverse<u64>>;
+
+    fn new_queue() -> Queue {
+        <Queue as EditableHeap<Reverse<u64>>>::from_entries_vec(Vec::new())
+    }
+
+    fn check_positions(queue: &Queue, positions: &HashMap<MediatorIndex, HeapIndex>) {
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.data.len(), positions.len());
+        for (&outer_pos, &position) in positions.iter() {
+            assert_eq!(queue.data[position.get()].outer_pos, outer_pos);
+        }
+    }
+
+    fn pop(queue: &mut Queue, positions: &mut HashMap<MediatorIndex, HeapIndex>) -> Option<u64> {
+        let (_, position) = queue.most_prioritized_idx()?;
+        let (outer_pos, Reverse(time)) = queue
+            .remove(position, |o, p| {
+                positions.insert(o, p);
+            })
+            .unwrap();
+        positions.remove(&outer_pos);
+        check_positions(queue, positions);
+        Some(time)
+    }
+
+    // Simple xorshift to avoid dependency on rand
+    fn next_random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    #[test]
+    fn test_hold_model() {
+        let mut positions = HashMap::new();
+        let mut queue = new_queue();
+        let mut state = 0x2545_f491_4f6c_dd1d;
+        let mut next_id = 0;
+        for _ in 0..200 {
+            let time = next_random(&mut state) % 1000;
+            queue.push(MediatorIndex(next_id), Reverse(time), |o, p| {
+                positions.insert(o, p);
+            });
+            next_id += 1;
+            check_positions(&queue, &positions);
+        }
+        let mut now = 0;
+        for _ in 0..2000 {
+            let time = pop(&mut queue, &mut positions).unwrap();
+            assert!(time >= now);
+            now = time;
+            // Schedule next event of the same kind
+            let time = now + next_random(&mut state) % 1000;
+            queue.push(MediatorIndex(next_id), Reverse(time), |o, p| {
+                positions.insert(o, p);
+            });
+            next_id += 1;
+            check_positions(&queue, &positions);
+        }
+        let mut rest = Vec::new();
+        while let Some(time) = pop(&mut queue, &mut positions) {
+            rest.push(time);
+        }
+        assert_eq!(rest.len(), 200);
+        assert!(rest.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(rest[0] >= now);
+        assert_eq!(queue.buckets.buckets(), MIN_BUCKETS);
+    }
+
+    #[test]
+    fn test_reschedule_and_cancel() {
+        let mut positions = HashMap::new();
+        let mut queue = new_queue();
+        for i in 0..100u64 {
+            queue.push(MediatorIndex::new(i as usize), Reverse(i * 10), |o, p| {
+                positions.insert(o, p);
+            });
+        }
+        check_positions(&queue, &positions);
+        // Postpone the earliest event
+        let (outer_pos, position) = queue.most_prioritized_idx().unwrap();
+        assert_eq!(outer_pos, MediatorIndex(0));
+        assert_eq!(
+            queue.change_priority(position, Reverse(5000), |_, _| {}),
+            Reverse(0)
+        );
+        check_positions(&queue, &positions);
+        assert_eq!(queue.most_prioritized_idx().unwrap().0, MediatorIndex(1));
+        // Move event before all others
+        let position = positions[&MediatorIndex(50)];
+        *queue.priority_mut(position) = Reverse(3);
+        queue.restore_order(position, |_, _| {});
+        check_positions(&queue, &positions);
+        assert_eq!(queue.most_prioritized_idx().unwrap().0, MediatorIndex(50));
+        // Cancel every third event
+        for i in (1..100).step_by(3) {
+            let position = positions.remove(&MediatorIndex(i)).unwrap();
+            queue.remove(position, |o, p| {
+                positions.insert(o, p);
+            });
+            check_positions(&queue, &positions);
+        }
+        let mut popped = Vec::new();
+        while let Some(time) = pop(&mut queue, &mut positions) {
+            popped.push(time);
+        }
+        assert_eq!(popped.len(), 67);
+        assert_eq!(popped[0], 3);
+        assert_eq!(popped.last(), Some(&5000));
+        assert!(popped.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_far_events() {
+        let entries: Vec<_> = [u64::MAX, 7, 1 << 40, 7, 0, 1 << 63]
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| HeapEntry {
+                outer_pos: MediatorIndex::new(i),
+                priority: Reverse(x),
+            })
+            .collect();
+        let mut queue = <Queue as EditableHeap<Reverse<u64>>>::from_entries_vec(entries);
+        let mut positions = (0..6).map(|i| (MediatorIndex(i), HeapIndex(i))).collect();
+        check_positions(&queue, &positions);
+        let mut popped = Vec::new();
+        while let Some(time) = pop(&mut queue, &mut positions) {
+            popped.push(time);
+        }
+        assert_eq!(popped, vec![0, 7, 7, 1 << 40, 1 << 63, u64::MAX]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut queue = new_queue();
+        for i in 0..10u64 {
+            queue.push(
+                MediatorIndex::new(i as usize),
+                Reverse(i * 100 + 50),
+                |_, _| {},
+            );
+        }
+        assert_eq!(queue.validate(), Ok(()));
+        *queue.priority_mut(HeapIndex(3)) = Reverse(1);
+        assert_eq!(queue.validate(), Err(InvariantError::HeapStructure));
+        queue.restore_order(HeapIndex(3), |_, _| {});
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(
+            queue.most_prioritized_idx(),
+            Some((MediatorIndex(3), HeapIndex(3)))
+        );
+    }
+}
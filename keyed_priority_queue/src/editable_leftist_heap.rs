@@ -0,0 +1,641 @@
+use crate::heap_storage::HeapSlice;
+use crate::heap_traits::{EditableHeap, HeapEntry, HeapIndex};
+use crate::keyed_priority_queue::InvariantError;
+use crate::keyed_priority_queue::TryReserveError;
+use allocator_api2::alloc::Global;
+use std::fmt::Debug;
+use std::vec::Vec;
+
+use crate::mediator::MediatorIndex;
+
+const NONE: usize = usize::MAX;
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    parent: usize,
+    left: usize,
+    right: usize,
+    // Length of the shortest path to missing child
+    rank: usize,
+}
+
+const SINGLE: Node = Node {
+    parent: NONE,
+    left: NONE,
+    right: NONE,
+    rank: 1,
+};
+
+/// Leftist heap which supports merging of heaps in ***O(log n)*** time.
+///
+/// Items are kept in vector like in other heaps, but order of items is kept
+/// by links between them, so [`meld`] only appends items of other heap and
+/// merges right spines of both trees.
+///
+/// [`meld`]: struct.KeyedPriorityQueue.html#method.meld
+#[derive(Clone)]
+pub struct LeftistHeap<TPriority>
+where
+    TPriority: Ord,
+{
+    data: Vec<HeapEntry<TPriority>>,
+    nodes: Vec<Node>,
+    root: usize,
+    // Buffer for merged right spine
+    spine: Vec<usize>,
+}
+
+// Finishes merge of detached tree into root without comparisons if comparison panics
+struct RootMergeGuard<'a, TPriority: Ord> {
+    heap: &'a mut LeftistHeap<TPriority>,
+    other: usize,
+}
+
+impl<'a, TPriority: Ord> Drop for RootMergeGuard<'a, TPriority> {
+    fn drop(&mut self) {
+        if self.other != NONE {
+            self.heap.fill_spine_unordered(self.heap.root, self.other);
+            let root = self.heap.link_spine();
+            self.heap.set_root(root);
+        }
+    }
+}
+
+// Finishes removal without comparisons if comparison panics
+// because outer index of removed item is already released.
+struct RemoveGuard<'a, TPriority: Ord, TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)> {
+    heap: &'a mut LeftistHeap<TPriority>,
+    position: usize,
+    change_handler: &'a mut TChangeHandler,
+}
+
+impl<'a, TPriority: Ord, TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)> Drop
+    for RemoveGuard<'a, TPriority, TChangeHandler>
+{
+    fn drop(&mut self) {
+        if self.position != NONE {
+            let Node { left, right, .. } = self.heap.nodes[self.position];
+            self.heap.fill_spine_unordered(left, right);
+            let merged = self.heap.link_spine();
+            self.heap
+                .finish_remove(self.position, merged, &mut *self.change_handler);
+        }
+    }
+}
+
+impl<TPriority: Ord> LeftistHeap<TPriority> {
+    #[inline(always)]
+    fn rank(&self, node: usize) -> usize {
+        if node == NONE {
+            0
+        } else {
+            self.nodes[node].rank
+        }
+    }
+
+    #[inline(always)]
+    fn set_root(&mut self, root: usize) {
+        self.root = root;
+        if root != NONE {
+            self.nodes[root].parent = NONE;
+        }
+    }
+
+    // Collects merged right spines of two trees using only comparisons.
+    // Nodes are not modified so panic leaves both trees untouched.
+    fn fill_spine(&mut self, mut a: usize, mut b: usize) {
+        self.spine.clear();
+        while a != NONE && b != NONE {
+            if self.data[a].priority >= self.data[b].priority {
+                self.spine.push(a);
+                a = self.nodes[a].right;
+            } else {
+                self.spine.push(b);
+                b = self.nodes[b].right;
+            }
+        }
+        // Rest of spine stays under the last node
+        self.spine.push(if a != NONE { a } else { b });
+    }
+
+    // Puts second tree at the end of right spine of first one without comparisons
+    fn fill_spine_unordered(&mut self, mut a: usize, b: usize) {
+        self.spine.clear();
+        while a != NONE {
+            self.spine.push(a);
+            a = self.nodes[a].right;
+        }
+        self.spine.push(b);
+    }
+
+    // Links collected spine and restores ranks bottom up
+    // Returns root of merged tree
+    fn link_spine(&mut self) -> usize {
+        let spine = std::mem::take(&mut self.spine);
+        for pair in spine.windows(2) {
+            self.nodes[pair[0]].right = pair[1];
+            if pair[1] != NONE {
+                self.nodes[pair[1]].parent = pair[0];
+            }
+        }
+        for &node in spine.iter().rev().skip(1) {
+            self.fix_rank(node);
+        }
+        let root = spine.first().copied().unwrap_or(NONE);
+        self.spine = spine;
+        root
+    }
+
+    // Swaps children if needed and returns true if rank of node changed
+    fn fix_rank(&mut self, node: usize) -> bool {
+        let Node { left, right, .. } = self.nodes[node];
+        if self.rank(left) < self.rank(right) {
+            self.nodes[node].left = right;
+            self.nodes[node].right = left;
+        }
+        let rank = std::cmp::min(self.rank(left), self.rank(right)) + 1;
+        let changed = rank != self.nodes[node].rank;
+        self.nodes[node].rank = rank;
+        changed
+    }
+
+    fn merge(&mut self, a: usize, b: usize) -> usize {
+        if a == NONE {
+            return b;
+        }
+        if b == NONE {
+            return a;
+        }
+        self.fill_spine(a, b);
+        self.link_spine()
+    }
+
+    fn merge_into_root(&mut self, other: usize) {
+        let mut guard = RootMergeGuard { heap: self, other };
+        let root = guard.heap.root;
+        let merged = guard.heap.merge(root, other);
+        guard.heap.set_root(merged);
+        guard.other = NONE;
+    }
+
+    // Puts tree in place of node in its parent and updates ranks of ancestors
+    fn replace(&mut self, node: usize, tree: usize) {
+        let parent = self.nodes[node].parent;
+        if tree != NONE {
+            self.nodes[tree].parent = parent;
+        }
+        if parent == NONE {
+            self.root = tree;
+            return;
+        }
+        if self.nodes[parent].left == node {
+            self.nodes[parent].left = tree;
+        } else {
+            self.nodes[parent].right = tree;
+        }
+        let mut current = parent;
+        while current != NONE && self.fix_rank(current) {
+            current = self.nodes[current].parent;
+        }
+    }
+
+    // Detaches node from tree merging its children in its place
+    fn detach(&mut self, node: usize) {
+        let Node { left, right, .. } = self.nodes[node];
+        let merged = self.merge(left, right);
+        self.replace(node, merged);
+        self.nodes[node] = SINGLE;
+    }
+
+    fn finish_remove<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: usize,
+        merged: usize,
+        change_handler: &mut TChangeHandler,
+    ) -> HeapEntry<TPriority> {
+        self.replace(position, merged);
+        let last = self.data.len() - 1;
+        if position != last {
+            // Links to the last node must point to its new position
+            let Node {
+                parent,
+                left,
+                right,
+                ..
+            } = self.nodes[last];
+            if parent == NONE {
+                self.root = position;
+            } else if self.nodes[parent].left == last {
+                self.nodes[parent].left = position;
+            } else {
+                self.nodes[parent].right = position;
+            }
+            for child in [left, right] {
+                if child != NONE {
+                    self.nodes[child].parent = position;
+                }
+            }
+        }
+        self.nodes.swap_remove(position);
+        let removed = self.data.swap_remove(position);
+        if position != last {
+            change_handler(self.data[position].outer_pos, HeapIndex::new(position));
+        }
+        removed
+    }
+
+    // Checks links and ranks of subtree and returns its size
+    fn validate_subtree(&self, node: usize) -> Result<usize, InvariantError> {
+        let mut size = 0;
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            size += 1;
+            if size > self.data.len() {
+                return Err(InvariantError::HeapStructure);
+            }
+            let Node { left, right, .. } = self.nodes[node];
+            if self.rank(left) < self.rank(right) || self.nodes[node].rank != self.rank(right) + 1 {
+                return Err(InvariantError::HeapStructure);
+            }
+            for child in [left, right] {
+                if child == NONE {
+                    continue;
+                }
+                if child >= self.data.len() || self.nodes[child].parent != node {
+                    return Err(InvariantError::HeapStructure);
+                }
+                if self.data[node].priority < self.data[child].priority {
+                    return Err(InvariantError::HeapOrder {
+                        parent: node,
+                        child,
+                    });
+                }
+                stack.push(child);
+            }
+        }
+        Ok(size)
+    }
+}
+
+impl<TPriority: Ord> EditableHeap<TPriority> for LeftistHeap<TPriority> {
+    type Alloc = Global;
+
+    fn from_entries_vec_in(heap_base: Vec<HeapEntry<TPriority>>, _alloc: Global) -> Self {
+        let len = heap_base.len();
+        let mut heap = LeftistHeap {
+            data: heap_base,
+            nodes: vec![SINGLE; len],
+            root: NONE,
+            spine: Vec::new(),
+        };
+        for position in 0..len {
+            heap.merge_into_root(position);
+        }
+        heap
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+        self.nodes.reserve(additional);
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.data
+            .try_reserve(additional)
+            .map_err(|_| TryReserveError {})?;
+        self.nodes
+            .try_reserve(additional)
+            .map_err(|_| TryReserveError {})
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        std::cmp::min(self.data.capacity(), self.nodes.capacity())
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        self.data.shrink_to_fit();
+        self.nodes.shrink_to_fit();
+    }
+
+    #[inline]
+    fn shrink_to(&mut self, min_capacity: usize) {
+        self.data.shrink_to(min_capacity);
+        self.nodes.shrink_to(min_capacity);
+    }
+
+    /// Puts outer index and priority in queue
+    /// outer_pos is assumed to be unique but not validated
+    /// because validation too expensive
+    /// Calls change_handler only for new item because items never move on push
+    fn push<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        outer_pos: MediatorIndex,
+        priority: TPriority,
+        mut change_handler: TChangeHandler,
+    ) {
+        let position = self.data.len();
+        self.data.push(HeapEntry {
+            outer_pos,
+            priority,
+        });
+        self.nodes.push(SINGLE);
+        change_handler(outer_pos, HeapIndex::new(position));
+        self.merge_into_root(position);
+    }
+
+    /// Removes item at position and returns it
+    /// Time complexity - O(log n) comparisons and 1 change_handler call
+    fn remove<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        mut change_handler: TChangeHandler,
+    ) -> Option<(MediatorIndex, TPriority)> {
+        if position >= self.len() {
+            return None;
+        }
+        let mut guard = RemoveGuard {
+            heap: self,
+            position: position.get(),
+            change_handler: &mut change_handler,
+        };
+        let Node { left, right, .. } = guard.heap.nodes[position.get()];
+        let merged = guard.heap.merge(left, right);
+        guard.position = NONE;
+        let removed = guard
+            .heap
+            .finish_remove(position.get(), merged, &mut *guard.change_handler);
+        Some(removed.conv_pair())
+    }
+
+    #[inline]
+    fn data(&self) -> HeapSlice<'_, TPriority> {
+        HeapSlice::from(self.data.as_slice())
+    }
+
+    // Changes outer index for element and return old index
+    fn change_outer_pos(&mut self, outer_pos: MediatorIndex, position: HeapIndex) -> MediatorIndex {
+        debug_assert!(position < self.len(), "Out of index during changing key");
+
+        let old_pos = self.data[position.get()].outer_pos;
+        self.data[position.get()].outer_pos = outer_pos;
+        old_pos
+    }
+
+    /// Changes priority of queue item
+    /// Returns old priority
+    fn change_priority<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        updated: TPriority,
+        change_handler: TChangeHandler,
+    ) -> TPriority {
+        debug_assert!(
+            position < self.len(),
+            "Out of index during changing priority"
+        );
+
+        let old = std::mem::replace(&mut self.data[position.get()].priority, updated);
+        self.restore_order(position, change_handler);
+        old
+    }
+
+    #[inline]
+    fn priority_mut(&mut self, position: HeapIndex) -> &mut TPriority {
+        &mut self.data[position.get()].priority
+    }
+
+    /// Items never move so change_handler is not called
+    fn restore_order<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        _change_handler: TChangeHandler,
+    ) {
+        debug_assert!(position < self.len(), "Out of index during restoring order");
+        let node = position.get();
+        let Node {
+            parent,
+            left,
+            right,
+            ..
+        } = self.nodes[node];
+        let priority = &self.data[node].priority;
+        let is_ordered = (parent == NONE || self.data[parent].priority >= *priority)
+            && (left == NONE || *priority >= self.data[left].priority)
+            && (right == NONE || *priority >= self.data[right].priority);
+        if is_ordered {
+            return;
+        }
+        // Panic in merge of children leaves tree untouched
+        self.detach(node);
+        self.merge_into_root(node);
+    }
+
+    fn most_prioritized_idx(&self) -> Option<(MediatorIndex, HeapIndex)> {
+        if self.root == NONE {
+            return None;
+        }
+        Some((self.data[self.root].outer_pos, HeapIndex::new(self.root)))
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.data.clear();
+        self.nodes.clear();
+        self.root = NONE;
+    }
+
+    /// Appends items of other heap and merges trees
+    /// Time complexity - O(m) change_handler calls and O(log(n + m)) comparisons
+    fn meld<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        other: Self,
+        mut change_handler: TChangeHandler,
+    ) {
+        let offset = self.data.len();
+        let shift = |index: usize| if index == NONE { NONE } else { index + offset };
+        self.nodes.extend(other.nodes.into_iter().map(|node| Node {
+            parent: shift(node.parent),
+            left: shift(node.left),
+            right: shift(node.right),
+            rank: node.rank,
+        }));
+        self.data.extend(other.data);
+        for (position, entry) in self.data.iter().enumerate().skip(offset) {
+            change_handler(entry.outer_pos, HeapIndex::new(position));
+        }
+        self.merge_into_root(shift(other.root));
+    }
+
+    fn validate(&self) -> Result<(), InvariantError> {
+        if self.nodes.len() != self.data.len() {
+            return Err(InvariantError::HeapStructure);
+        }
+        if self.root == NONE {
+            return if self.data.is_empty() {
+                Ok(())
+            } else {
+                Err(InvariantError::HeapStructure)
+            };
+        }
+        if self.root >= self.data.len() || self.nodes[self.root].parent != NONE {
+            return Err(InvariantError::HeapStructure);
+        }
+        if self.validate_subtree(self.root)? != self.data.len() {
+            return Err(InvariantError::HeapStructure);
+        }
+        Ok(())
+    }
+}
+
+impl<TPriority: Debug + Ord> Debug for LeftistHeap<TPriority> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        self.data.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heap_traits::EditableHeap;
+    use std::collections::HashMap;
+
+    fn new_heap() -> LeftistHeap<i32> {
+        <LeftistHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new())
+    }
+
+    fn check_positions(heap: &LeftistHeap<i32>, positions: &HashMap<MediatorIndex, HeapIndex>) {
+        assert_eq!(heap.validate(), Ok(()));
+        assert_eq!(heap.data.len(), positions.len());
+        for (&outer_pos, &position) in positions.iter() {
+            assert_eq!(heap.data[position.get()].outer_pos, outer_pos);
+        }
+    }
+
+    fn pop_all(
+        heap: &mut LeftistHeap<i32>,
+        positions: &mut HashMap<MediatorIndex, HeapIndex>,
+    ) -> Vec<i32> {
+        let mut popped = Vec::new();
+        while let Some((_, position)) = heap.most_prioritized_idx() {
+            let (outer_pos, priority) = heap
+                .remove(position, |o, p| {
+                    positions.insert(o, p);
+                })
+                .unwrap();
+            positions.remove(&outer_pos);
+            check_positions(heap, positions);
+            popped.push(priority);
+        }
+        popped
+    }
+
+    #[test]
+    fn test_pop_order() {
+        let items = [
+            70, 50, 0, 1, 2, 4, 6, 7, 9, 72, 4, 4, 87, 78, 72, 6, 7, 9, 2, -79, -6, -1,
+        ];
+        let mut positions = HashMap::new();
+        let mut heap = new_heap();
+        for (i, &x) in items.iter().enumerate() {
+            heap.push(MediatorIndex::new(i), x, |o, p| {
+                positions.insert(o, p);
+            });
+            check_positions(&heap, &positions);
+        }
+        let mut sorted = items;
+        sorted.sort_unstable_by(|a, b| b.cmp(a));
+        assert_eq!(pop_all(&mut heap, &mut positions), sorted.to_vec());
+    }
+
+    #[test]
+    fn test_change_priority_and_remove() {
+        let mut positions = HashMap::new();
+        let entries: Vec<_> = (0..100)
+            .map(|i| {
+                positions.insert(MediatorIndex(i), HeapIndex(i));
+                HeapEntry {
+                    outer_pos: MediatorIndex(i),
+                    priority: (i as i32 * 37) % 100,
+                }
+            })
+            .collect();
+        let mut heap = <LeftistHeap<i32> as EditableHeap<i32>>::from_entries_vec(entries);
+        check_positions(&heap, &positions);
+        for i in (0..100).step_by(7) {
+            let position = positions[&MediatorIndex(i)];
+            heap.change_priority(position, 150 - i as i32, |_, _| {});
+            check_positions(&heap, &positions);
+        }
+        for i in (0..100).step_by(11) {
+            let position = positions[&MediatorIndex(i)];
+            *heap.priority_mut(position) = -(i as i32);
+            heap.restore_order(position, |_, _| {});
+            check_positions(&heap, &positions);
+        }
+        for i in (1..100).step_by(5) {
+            let position = positions.remove(&MediatorIndex(i)).unwrap();
+            heap.remove(position, |o, p| {
+                positions.insert(o, p);
+            });
+            check_positions(&heap, &positions);
+        }
+        let popped = pop_all(&mut heap, &mut positions);
+        assert_eq!(popped.len(), 80);
+        assert_eq!(popped[0], 143);
+        assert!(popped.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    fn test_meld() {
+        let mut positions = HashMap::new();
+        let mut heap = new_heap();
+        let mut other = new_heap();
+        for i in 0..50 {
+            heap.push(MediatorIndex(i), (i as i32 * 13) % 50, |o, p| {
+                positions.insert(o, p);
+            });
+            other.push(MediatorIndex(50 + i), (i as i32 * 7) % 60, |_, _| {});
+        }
+        heap.meld(other, |o, p| {
+            positions.insert(o, p);
+        });
+        check_positions(&heap, &positions);
+        heap.meld(new_heap(), |_, _| unreachable!());
+        let mut empty = new_heap();
+        empty.meld(heap, |o, p| {
+            positions.insert(o, p);
+        });
+        check_positions(&empty, &positions);
+
+        let popped = pop_all(&mut empty, &mut positions);
+        assert_eq!(popped.len(), 100);
+        assert_eq!(popped[0], 59);
+        assert!(popped.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut heap = new_heap();
+        for i in 0..10 {
+            heap.push(MediatorIndex(i), i as i32, |_, _| {});
+        }
+        assert_eq!(heap.validate(), Ok(()));
+        let (_, root) = heap.most_prioritized_idx().unwrap();
+        *heap.priority_mut(root) = -1;
+        assert!(matches!(
+            heap.validate(),
+            Err(InvariantError::HeapOrder { parent, .. }) if parent == root.get()
+        ));
+        heap.restore_order(root, |_, _| {});
+        assert_eq!(heap.validate(), Ok(()));
+        assert_eq!(
+            heap.data[heap.most_prioritized_idx().unwrap().1.get()].priority,
+            8
+        );
+    }
+}
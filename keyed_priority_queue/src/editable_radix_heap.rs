@@ -0,0 +1,530 @@
+use crate::heap_storage::HeapSlice;
+use crate::heap_traits::{EditableHeap, HeapEntry, HeapIndex};
+use crate::keyed_priority_queue::InvariantError;
+use crate::keyed_priority_queue::TryReserveError;
+use allocator_api2::alloc::Global;
+use std::cmp::Reverse;
+use std::fmt::Debug;
+use std::vec::Vec;
+
+use crate::mediator::MediatorIndex;
+
+/// Priority which can be stored in [`RadixHeap`].
+///
+/// Priorities are mapped to unsigned integer keys and item with the smallest key
+/// is considered the most prioritized, so key must be consistent with `Ord`:
+/// `a > b` if and only if `a.radix_key() < b.radix_key()`.
+///
+/// It is implemented for `Reverse` of unsigned integers
+/// because they give the smallest numbers the biggest priority
+/// which is usual for shortest path algorithms.
+///
+/// [`RadixHeap`]: struct.RadixHeap.html
+pub trait RadixPriority: Ord {
+    fn radix_key(&self) -> u64;
+}
+
+macro_rules! impl_radix_priority_for_reverse {
+    ($($t:ty),*) => {
+        $(
+            impl RadixPriority for Reverse<$t> {
+                #[inline(always)]
+                fn radix_key(&self) -> u64 {
+                    self.0 as u64
+                }
+            }
+        )*
+    };
+}
+
+impl_radix_priority_for_reverse!(u8, u16, u32, u64, usize);
+
+// Bucket 0 keeps items with key equal to `last`,
+// bucket `i` keeps items which highest bit different from `last` is bit `i - 1`.
+const BUCKETS: usize = u64::BITS as usize + 1;
+
+#[inline(always)]
+fn bucket_of(key: u64, last: u64) -> usize {
+    (u64::BITS - (key ^ last).leading_zeros()) as usize
+}
+
+/// Radix heap for monotone priorities like distances in Dijkstra algorithm.
+///
+/// It works best when items never get priorities bigger than the last popped one.
+/// Such changes are still supported but require rebuilding of whole heap.
+///
+/// Items are kept in one vector split into consecutive buckets
+/// so moving item between buckets costs ***O(log C)*** swaps
+/// where ***C*** is the range of keys. The most prioritized item is always the first one.
+///
+/// ### Examples
+///
+/// ```
+/// use keyed_priority_queue::KeyedRadixPriorityQueue;
+/// use std::cmp::Reverse;
+///
+/// let mut distances = KeyedRadixPriorityQueue::new();
+/// distances.push("a", Reverse(10u32));
+/// distances.push("b", Reverse(7));
+/// distances.push("c", Reverse(12));
+/// assert_eq!(distances.pop(), Some(("b", Reverse(7))));
+///
+/// // Decrease key of item which is still in queue
+/// distances.set_priority(&"c", Reverse(8)).unwrap();
+/// assert_eq!(distances.pop(), Some(("c", Reverse(8))));
+/// assert_eq!(distances.pop(), Some(("a", Reverse(10))));
+/// ```
+#[derive(Clone)]
+pub struct RadixHeap<TPriority>
+where
+    TPriority: RadixPriority,
+{
+    data: Vec<HeapEntry<TPriority>>,
+    // Bucket `i` occupies `data[bounds[i]..bounds[i + 1]]`
+    bounds: [usize; BUCKETS + 1],
+    // Key of the most prioritized item
+    last: u64,
+}
+
+impl<TPriority: RadixPriority> RadixHeap<TPriority> {
+    #[inline(always)]
+    fn bucket_at(&self, position: usize) -> usize {
+        self.bounds.partition_point(|&start| start <= position) - 1
+    }
+
+    #[inline(always)]
+    fn bucket_of_entry(&self, position: usize) -> usize {
+        bucket_of(self.data[position].priority.radix_key(), self.last)
+    }
+
+    #[inline(always)]
+    fn swap<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: usize,
+        other: usize,
+        change_handler: &mut TChangeHandler,
+    ) {
+        if position != other {
+            self.data.swap(position, other);
+            change_handler(self.data[position].outer_pos, HeapIndex::new(position));
+        }
+    }
+
+    // Moves item through boundaries of buckets and returns its new position
+    fn move_between_buckets<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        mut position: usize,
+        from: usize,
+        to: usize,
+        change_handler: &mut TChangeHandler,
+    ) -> usize {
+        for bucket in (to + 1..=from).rev() {
+            // Swap with the first item of bucket and move boundary after it
+            let first = self.bounds[bucket];
+            self.swap(position, first, change_handler);
+            self.bounds[bucket] += 1;
+            position = first;
+        }
+        for bucket in from + 1..=to {
+            // Swap with the last item of previous bucket and move boundary before it
+            let last = self.bounds[bucket] - 1;
+            self.swap(position, last, change_handler);
+            self.bounds[bucket] -= 1;
+            position = last;
+        }
+        position
+    }
+
+    // Puts item at position to bucket matching its key
+    fn restore_bucket<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: usize,
+        mut change_handler: TChangeHandler,
+    ) {
+        let key = self.data[position].priority.radix_key();
+        if key < self.last {
+            // Item is more prioritized than already popped one
+            // so all buckets must be recalculated.
+            self.rebuild(&mut change_handler);
+            return;
+        }
+        let from = self.bucket_at(position);
+        let to = bucket_of(key, self.last);
+        let position = self.move_between_buckets(position, from, to, &mut change_handler);
+        change_handler(self.data[position].outer_pos, HeapIndex::new(position));
+        self.refill_first_bucket(&mut change_handler);
+    }
+
+    // Keeps the most prioritized items in the first bucket
+    // by redistributing the lowest non-empty bucket.
+    fn refill_first_bucket<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        change_handler: &mut TChangeHandler,
+    ) {
+        if self.data.is_empty() || self.bounds[1] > 0 {
+            return;
+        }
+        // All buckets before lowest non-empty one are empty so their bounds are zero.
+        let bucket = self.bucket_at(0);
+        let end = self.bounds[bucket + 1];
+        self.last = self.data[..end]
+            .iter()
+            .map(|entry| entry.priority.radix_key())
+            .min()
+            .expect("Bucket is not empty");
+        // Items of other buckets keep their buckets relative to new `last`
+        // because it shares their higher bits with previous one.
+        self.sort_into_buckets(end, bucket);
+        for (position, entry) in self.data[..end].iter().enumerate() {
+            change_handler(entry.outer_pos, HeapIndex::new(position));
+        }
+    }
+
+    // Sorts first `end` items by buckets and updates bounds of buckets up to `max_bucket`
+    fn sort_into_buckets(&mut self, end: usize, max_bucket: usize) {
+        let last = self.last;
+        self.data[..end].sort_unstable_by_key(|entry| bucket_of(entry.priority.radix_key(), last));
+        let mut position = 0;
+        for bucket in 0..=max_bucket {
+            self.bounds[bucket] = position;
+            while position < end && self.bucket_of_entry(position) == bucket {
+                position += 1;
+            }
+        }
+        debug_assert_eq!(position, end);
+    }
+
+    fn rebuild<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        change_handler: &mut TChangeHandler,
+    ) {
+        self.last = self
+            .data
+            .iter()
+            .map(|entry| entry.priority.radix_key())
+            .min()
+            .unwrap_or(0);
+        let len = self.data.len();
+        self.sort_into_buckets(len, BUCKETS - 1);
+        self.bounds[BUCKETS] = len;
+        for (position, entry) in self.data.iter().enumerate() {
+            change_handler(entry.outer_pos, HeapIndex::new(position));
+        }
+    }
+}
+
+impl<TPriority: RadixPriority> EditableHeap<TPriority> for RadixHeap<TPriority> {
+    type Alloc = Global;
+
+    fn from_entries_vec_in(heap_base: Vec<HeapEntry<TPriority>>, _alloc: Global) -> Self {
+        let mut heap = RadixHeap {
+            data: heap_base,
+            bounds: [0; BUCKETS + 1],
+            last: 0,
+        };
+        heap.rebuild(&mut |_, _| {});
+        heap
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional)
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.data
+            .try_reserve(additional)
+            .map_err(|_| TryReserveError {})
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        self.data.shrink_to_fit()
+    }
+
+    #[inline]
+    fn shrink_to(&mut self, min_capacity: usize) {
+        self.data.shrink_to(min_capacity)
+    }
+
+    /// Puts outer index and priority in queue
+    /// outer_pos is assumed to be unique but not validated
+    /// because validation too expensive
+    /// Calls change_handler for every move of old values
+    fn push<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        outer_pos: MediatorIndex,
+        priority: TPriority,
+        change_handler: TChangeHandler,
+    ) {
+        if self.data.is_empty() {
+            self.last = priority.radix_key();
+        }
+        // New item is placed into the last bucket
+        self.data.push(HeapEntry {
+            outer_pos,
+            priority,
+        });
+        self.bounds[BUCKETS] = self.data.len();
+        self.restore_bucket(self.data.len() - 1, change_handler);
+    }
+
+    /// Removes item at position and returns it
+    /// Time complexity - O(log C) swaps and change_handler calls
+    /// and amortized O(log C) for refilling of first bucket
+    fn remove<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        mut change_handler: TChangeHandler,
+    ) -> Option<(MediatorIndex, TPriority)> {
+        if position >= self.len() {
+            return None;
+        }
+        let from = self.bucket_at(position.get());
+        let position =
+            self.move_between_buckets(position.get(), from, BUCKETS - 1, &mut change_handler);
+        let last = self.data.len() - 1;
+        self.swap(position, last, &mut change_handler);
+        let result = self.data.pop().expect("At least 1 item");
+        self.bounds[BUCKETS] = self.data.len();
+        self.refill_first_bucket(&mut change_handler);
+        Some(result.conv_pair())
+    }
+
+    #[inline]
+    fn data(&self) -> HeapSlice<'_, TPriority> {
+        HeapSlice::from(self.data.as_slice())
+    }
+
+    // Changes outer index for element and return old index
+    fn change_outer_pos(&mut self, outer_pos: MediatorIndex, position: HeapIndex) -> MediatorIndex {
+        debug_assert!(position < self.len(), "Out of index during changing key");
+
+        let old_pos = self.data[position.get()].outer_pos;
+        self.data[position.get()].outer_pos = outer_pos;
+        old_pos
+    }
+
+    /// Changes priority of queue item
+    /// Returns old priority
+    fn change_priority<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        updated: TPriority,
+        change_handler: TChangeHandler,
+    ) -> TPriority {
+        debug_assert!(
+            position < self.len(),
+            "Out of index during changing priority"
+        );
+
+        let old = std::mem::replace(&mut self.data[position.get()].priority, updated);
+        self.restore_bucket(position.get(), change_handler);
+        old
+    }
+
+    #[inline]
+    fn priority_mut(&mut self, position: HeapIndex) -> &mut TPriority {
+        &mut self.data[position.get()].priority
+    }
+
+    fn restore_order<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        change_handler: TChangeHandler,
+    ) {
+        debug_assert!(position < self.len(), "Out of index during restoring order");
+        self.restore_bucket(position.get(), change_handler);
+    }
+
+    fn most_prioritized_idx(&self) -> Option<(MediatorIndex, HeapIndex)> {
+        self.data.first().map(|x| (x.outer_pos, HeapIndex(0)))
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.data.clear();
+        self.bounds = [0; BUCKETS + 1];
+    }
+
+    fn validate(&self) -> Result<(), InvariantError> {
+        if self.bounds[0] != 0
+            || self.bounds[BUCKETS] != self.data.len()
+            || self.bounds.windows(2).any(|pair| pair[0] > pair[1])
+            || (!self.data.is_empty() && self.bounds[1] == 0)
+        {
+            return Err(InvariantError::HeapStructure);
+        }
+        for bucket in 0..BUCKETS {
+            for position in self.bounds[bucket]..self.bounds[bucket + 1] {
+                let key = self.data[position].priority.radix_key();
+                if key < self.last || bucket_of(key, self.last) != bucket {
+                    return Err(InvariantError::HeapStructure);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<TPriority: Debug + RadixPriority> Debug for RadixHeap<TPriority> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        self.data.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heap_traits::EditableHeap;
+    use std::collections::HashMap;
+
+    fn new_heap() -> RadixHeap<Reverse<u32>> {
+        <RadixHeap<Reverse<u32>> as EditableHeap<Reverse<u32>>>::from_entries_vec(Vec::new())
+    }
+
+    fn check_positions(
+        heap: &RadixHeap<Reverse<u32>>,
+        positions: &HashMap<MediatorIndex, HeapIndex>,
+    ) {
+        assert_eq!(heap.validate(), Ok(()));
+        assert_eq!(heap.data.len(), positions.len());
+        for (&outer_pos, &position) in positions.iter() {
+            assert_eq!(heap.data[position.get()].outer_pos, outer_pos);
+        }
+    }
+
+    #[test]
+    fn test_monotone_pops() {
+        let items = [
+            70u32, 50, 0, 1, 2, 4, 6, 7, 9, 72, 4, 4, 87, 78, 72, 6, 7, 9, 2, 1000, 100_000, 3,
+        ];
+        let mut positions = HashMap::new();
+        let mut heap = new_heap();
+        for (i, &x) in items.iter().enumerate() {
+            heap.push(MediatorIndex::new(i), Reverse(x), |outer_pos, position| {
+                positions.insert(outer_pos, position);
+            });
+            check_positions(&heap, &positions);
+        }
+        let mut sorted = items;
+        sorted.sort_unstable();
+        for &expected in sorted.iter() {
+            let (_, HeapIndex(top)) = heap.most_prioritized_idx().unwrap();
+            let (outer_pos, Reverse(popped)) = heap
+                .remove(HeapIndex(top), |outer_pos, position| {
+                    positions.insert(outer_pos, position);
+                })
+                .unwrap();
+            positions.remove(&outer_pos);
+            assert_eq!(popped, expected);
+            check_positions(&heap, &positions);
+            // Dijkstra-like push of bigger distances
+            if popped < 10 {
+                let outer_pos = MediatorIndex::new(items.len() + popped as usize);
+                heap.push(outer_pos, Reverse(popped + 1000), |outer_pos, position| {
+                    positions.insert(outer_pos, position);
+                });
+                check_positions(&heap, &positions);
+                heap.remove(positions[&outer_pos], |outer_pos, position| {
+                    positions.insert(outer_pos, position);
+                });
+                positions.remove(&outer_pos);
+                check_positions(&heap, &positions);
+            }
+        }
+        assert!(heap.data.is_empty());
+    }
+
+    #[test]
+    fn test_change_priority() {
+        let mut positions = HashMap::new();
+        let mut heap = new_heap();
+        for i in 0..100u32 {
+            heap.push(
+                MediatorIndex::new(i as usize),
+                Reverse(i * 7 % 100 + 50),
+                |o, p| {
+                    positions.insert(o, p);
+                },
+            );
+        }
+        check_positions(&heap, &positions);
+        // Decrease keys
+        for i in (0..100).step_by(3) {
+            let position = positions[&MediatorIndex::new(i)];
+            heap.change_priority(position, Reverse(60 + i as u32 / 10), |o, p| {
+                positions.insert(o, p);
+            });
+            check_positions(&heap, &positions);
+        }
+        // Increase keys including the most prioritized one
+        for _ in 0..10 {
+            let (outer_pos, position) = heap.most_prioritized_idx().unwrap();
+            let Reverse(old) = heap.data[position.get()].priority;
+            heap.change_priority(position, Reverse(old + 500), |o, p| {
+                positions.insert(o, p);
+            });
+            check_positions(&heap, &positions);
+            assert_ne!(heap.most_prioritized_idx().unwrap().0, outer_pos);
+        }
+        // Non-monotone change rebuilds the heap
+        let position = positions[&MediatorIndex(42)];
+        heap.change_priority(position, Reverse(1), |o, p| {
+            positions.insert(o, p);
+        });
+        check_positions(&heap, &positions);
+        assert_eq!(heap.most_prioritized_idx().unwrap().0, MediatorIndex(42));
+
+        let mut popped = Vec::new();
+        while let Some((_, position)) = heap.most_prioritized_idx() {
+            let (outer_pos, Reverse(priority)) = heap
+                .remove(position, |o, p| {
+                    positions.insert(o, p);
+                })
+                .unwrap();
+            positions.remove(&outer_pos);
+            check_positions(&heap, &positions);
+            popped.push(priority);
+        }
+        assert!(popped.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(popped.len(), 100);
+    }
+
+    #[test]
+    fn test_from_entries_vec() {
+        let entries: Vec<_> = [5u32, 1, 9, 1 << 20, 3, 3]
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| HeapEntry {
+                outer_pos: MediatorIndex::new(i),
+                priority: Reverse(x),
+            })
+            .collect();
+        let heap =
+            <RadixHeap<Reverse<u32>> as EditableHeap<Reverse<u32>>>::from_entries_vec(entries);
+        assert_eq!(heap.validate(), Ok(()));
+        assert_eq!(heap.data[0].priority, Reverse(1));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut heap = new_heap();
+        for i in 0..10u32 {
+            heap.push(MediatorIndex::new(i as usize), Reverse(i * 10), |_, _| {});
+        }
+        assert_eq!(heap.validate(), Ok(()));
+        *heap.priority_mut(HeapIndex(0)) = Reverse(1 << 30);
+        assert_eq!(heap.validate(), Err(InvariantError::HeapStructure));
+        heap.restore_order(HeapIndex(0), |_, _| {});
+        assert_eq!(heap.validate(), Ok(()));
+        assert_eq!(heap.data[0].priority, Reverse(10));
+    }
+}
@@ -1,52 +1,148 @@
-use crate::heap_traits::{EditableHeap, HeapEntry, HeapIndex};
+use crate::heap_storage::{HeapSlice, HeapStorage};
+use crate::heap_traits::{EditableHeap, HeapEntry, HeapIndex, SiftGuard};
+use crate::keyed_priority_queue::InvariantError;
+use crate::keyed_priority_queue::TryReserveError;
 use crate::mediator::MediatorIndex;
+use allocator_api2::alloc::Allocator;
+use allocator_api2::vec::Vec as AllocVec;
 use std::cmp::{Ord, Ordering};
 use std::fmt::Debug;
+use std::marker::PhantomData;
 use std::vec::Vec;
 
-/// Enum which determines which side the sibling node is on. The child node is on the other side.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum SiblingSide {
-    Left,
-    Right,
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Bitset which determines which side the sibling node is on. The child node is on the other side.
+/// Set bit means that sibling is on the right side.
+#[derive(Clone, Debug)]
+struct SiblingSides<A: Allocator + Clone> {
+    words: AllocVec<u64, A>,
+    len: usize,
 }
 
-impl SiblingSide {
-    fn flip(&mut self) {
-        match self {
-            SiblingSide::Left => *self = SiblingSide::Right,
-            SiblingSide::Right => *self = SiblingSide::Left,
-        };
+impl<A: Allocator + Clone> SiblingSides<A> {
+    #[inline(always)]
+    fn words_for(bits: usize) -> usize {
+        bits / WORD_BITS + (bits % WORD_BITS != 0) as usize
     }
 
-    fn as_bool(&self) -> bool {
-        *self == SiblingSide::Right
+    fn with_len_in(len: usize, capacity: usize, alloc: A) -> Self {
+        let mut words = AllocVec::with_capacity_in(Self::words_for(capacity), alloc);
+        words.resize(Self::words_for(len), 0);
+        SiblingSides { words, len }
+    }
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline(always)]
+    fn is_right(&self, position: usize) -> bool {
+        debug_assert!(position < self.len, "Out of index in sibling sides");
+        self.words[position / WORD_BITS] & (1 << (position % WORD_BITS)) != 0
+    }
+
+    #[inline(always)]
+    fn flip(&mut self, position: usize) {
+        debug_assert!(position < self.len, "Out of index in sibling sides");
+        self.words[position / WORD_BITS] ^= 1 << (position % WORD_BITS);
+    }
+
+    #[inline(always)]
+    fn reset(&mut self, position: usize) {
+        debug_assert!(position < self.len, "Out of index in sibling sides");
+        self.words[position / WORD_BITS] &= !(1 << (position % WORD_BITS));
+    }
+
+    /// Changes length keeping bits beyond it unset, so new bits are unset too
+    fn resize(&mut self, len: usize) {
+        for position in len..self.len {
+            self.reset(position);
+        }
+        self.words.resize(Self::words_for(len), 0);
+        self.len = len;
+    }
+
+    #[inline]
+    fn push(&mut self) {
+        self.resize(self.len + 1);
+    }
+
+    #[inline]
+    fn pop(&mut self) {
+        self.resize(self.len - 1);
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.words.clear();
+        self.len = 0;
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        let words = Self::words_for(self.len + additional) - self.words.len();
+        self.words.reserve(words);
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let words = Self::words_for(self.len.saturating_add(additional)) - self.words.len();
+        self.words
+            .try_reserve(words)
+            .map_err(|_| TryReserveError {})
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.words.capacity().saturating_mul(WORD_BITS)
     }
-}
 
-impl Default for SiblingSide {
-    fn default() -> Self {
-        SiblingSide::Left
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        self.words.shrink_to_fit();
+    }
+
+    #[inline]
+    fn shrink_to(&mut self, min_capacity: usize) {
+        self.words.shrink_to(Self::words_for(min_capacity));
     }
 }
 
+/// Weak heap which keeps items in `TStorage`.
+///
+/// By default priority of every item is stored next to its outer index.
+/// [`SoaStorage`] can be used to keep priorities in separate array.
+///
+/// [`SoaStorage`]: struct.SoaStorage.html
 #[derive(Clone)]
-pub struct WeakHeap<TPriority>
+pub struct WeakHeap<TPriority, TStorage = Vec<HeapEntry<TPriority>>>
 where
     TPriority: Ord,
+    TStorage: HeapStorage<TPriority>,
 {
-    sides: Vec<SiblingSide>,
-    data: Vec<HeapEntry<TPriority>>,
+    sides: SiblingSides<TStorage::Alloc>,
+    data: TStorage,
+    _phantom: PhantomData<fn() -> TPriority>,
 }
 
-impl<TPriority: Ord> WeakHeap<TPriority> {
+impl<TPriority: Ord, TStorage: HeapStorage<TPriority>> WeakHeap<TPriority, TStorage> {
     fn distinguished_ancestor(&self, position: HeapIndex) -> HeapIndex {
-        let HeapIndex(mut position) = position;
+        Self::distinguished_ancestor_in(&self.sides, position)
+    }
+
+    // Takes only sides so it can be used while data is borrowed by SiftGuard
+    fn distinguished_ancestor_in(
+        sides: &SiblingSides<TStorage::Alloc>,
+        position: HeapIndex,
+    ) -> HeapIndex {
+        let mut position = position.get();
         while position > 0 {
             let binary_parent_pos = position / 2;
-            let is_direct_child = (position % 2 == 0) == self.sides[binary_parent_pos].as_bool();
+            let is_direct_child = (position % 2 == 0) == sides.is_right(binary_parent_pos);
             if is_direct_child {
-                return HeapIndex(binary_parent_pos);
+                return HeapIndex::new(binary_parent_pos);
             } else {
                 // This binary parent is actually our sibling
                 position = binary_parent_pos;
@@ -58,105 +154,91 @@ impl<TPriority: Ord> WeakHeap<TPriority> {
     }
 
     fn next_sibling(&self, position: HeapIndex) -> HeapIndex {
-        let HeapIndex(position) = position;
-        HeapIndex(position * 2 + self.sides[position].as_bool() as usize)
+        let position = position.get();
+        HeapIndex::new(position * 2 + self.sides.is_right(position) as usize)
     }
 
     fn first_child(&self, position: HeapIndex) -> HeapIndex {
-        let HeapIndex(position) = position;
-        HeapIndex(position * 2 + (!self.sides[position].as_bool()) as usize)
+        let position = position.get();
+        HeapIndex::new(position * 2 + (!self.sides.is_right(position)) as usize)
     }
 
     fn heapify_up<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
         &mut self,
         position: HeapIndex,
-        mut change_handler: TChangeHandler,
+        change_handler: TChangeHandler,
     ) {
         debug_assert!(position < self.len(), "Out of index in heapify_up");
-        let HeapIndex(mut position) = position;
-        while position > 0 {
-            let HeapIndex(parent_pos) = self.distinguished_ancestor(HeapIndex(position));
-            if self.data[parent_pos].priority >= self.data[position].priority {
+        let sides = &mut self.sides;
+        let mut item = SiftGuard::new(&mut self.data, position.get(), change_handler);
+        while item.position > 0 {
+            let parent_pos =
+                Self::distinguished_ancestor_in(sides, HeapIndex::new(item.position)).get();
+            if item.data.priority(parent_pos) >= item.data.priority(item.position) {
                 break;
             }
-            self.data.swap(parent_pos, position);
-            self.sides[position].flip();
-            change_handler(self.data[position].outer_pos, HeapIndex(position));
-            position = parent_pos;
+            sides.flip(item.position);
+            item.move_to(parent_pos);
         }
-        change_handler(self.data[position].outer_pos, HeapIndex(position));
     }
 
     fn heapify_down<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
         &mut self,
         position: HeapIndex,
-        mut change_handler: TChangeHandler,
+        change_handler: TChangeHandler,
     ) {
         debug_assert!(position < self.len(), "Out of index in heapify_down");
-        let HeapIndex(position) = position;
-        let first_child_idx = self.first_child(HeapIndex(position));
-        if first_child_idx.0 < self.data.len() {
-            let max_child_idx = {
-                let mut current_child_idx = first_child_idx;
-                loop {
-                    let cand_child_idx = self.next_sibling(current_child_idx);
-                    if cand_child_idx.0 >= self.data.len() {
-                        break current_child_idx;
-                    } else {
-                        current_child_idx = cand_child_idx;
-                    }
-                }
-            };
-            let mut current_child_idx = max_child_idx;
-            while current_child_idx.0 > position {
-                if self.data[position].priority < self.data[current_child_idx.0].priority {
-                    self.data.swap(current_child_idx.0, position);
-                    self.sides[current_child_idx.0].flip();
-                    change_handler(self.data[current_child_idx.0].outer_pos, current_child_idx);
+        let position = position.get();
+        let first_child_idx = self.first_child(HeapIndex::new(position));
+        // Path to the last descendant depends only on sides, so find it before any moves
+        let max_child_idx = if first_child_idx.get() < self.data.len() {
+            let mut current_child_idx = first_child_idx;
+            loop {
+                let cand_child_idx = self.next_sibling(current_child_idx);
+                if cand_child_idx.get() >= self.data.len() {
+                    break current_child_idx;
+                } else {
+                    current_child_idx = cand_child_idx;
                 }
-                current_child_idx.0 /= 2;
-            }
-        }
-        change_handler(self.data[position].outer_pos, HeapIndex(position));
-    }
-
-    /*
-    fn format_recursive(
-        &self,
-        head: &str,
-        i: usize,
-        f: &mut std::fmt::Formatter,
-    ) -> Result<(), std::fmt::Error>
-    where
-        TPriority: Debug,
-    {
-        writeln!(f, "{}{:?}", head, self.data[i])?;
-        if i == 0 {
-            if self.data.len() > 1 {
-                self.format_recursive("    ", 1, f)?;
             }
         } else {
-            let HeapIndex(next_child_ind) = self.first_child(HeapIndex(i));
-            if next_child_ind < self.data.len() {
-                let head_more = format!("{}    ", head);
-                self.format_recursive(&head_more, next_child_ind, f)?;
-            }
-            let HeapIndex(next_sibling_ind) = self.next_sibling(HeapIndex(i));
-            if next_sibling_ind < self.data.len() {
-                self.format_recursive(head, next_sibling_ind, f)?;
+            HeapIndex::new(position)
+        };
+        let sides = &mut self.sides;
+        let mut item = SiftGuard::new(&mut self.data, position, change_handler);
+        let mut current_child_idx = max_child_idx;
+        while current_child_idx.get() > position {
+            if item.data.priority(position) < item.data.priority(current_child_idx.get()) {
+                sides.flip(current_child_idx.get());
+                item.exchange(current_child_idx.get());
             }
+            current_child_idx = HeapIndex::new(current_child_idx.get() / 2);
+        }
+    }
+
+    // Left child of binary parent must become its first child when it's added
+    #[inline(always)]
+    fn reset_parent_side(&mut self, new_index: usize) {
+        #[allow(clippy::manual_is_multiple_of)]
+        if new_index % 2 == 0 {
+            self.sides.reset(new_index / 2);
         }
-        Result::Ok(())
     }
-    */
 }
 
-impl<TPriority: Ord> EditableHeap<TPriority> for WeakHeap<TPriority> {
-    fn from_entries_vec(heap_base: Vec<HeapEntry<TPriority>>) -> Self {
+impl<TPriority: Ord, TStorage: HeapStorage<TPriority>> EditableHeap<TPriority>
+    for WeakHeap<TPriority, TStorage>
+{
+    type Alloc = TStorage::Alloc;
+
+    fn from_entries_vec_in(heap_base: Vec<HeapEntry<TPriority>>, alloc: TStorage::Alloc) -> Self {
         let heap_len = heap_base.len();
+        // Keep sides capacity in sync with data capacity
+        let sides = SiblingSides::with_len_in(heap_len, heap_base.capacity(), alloc.clone());
         let mut heap = WeakHeap {
-            data: heap_base,
-            sides: vec![SiblingSide::default(); heap_len],
+            data: TStorage::from_entries_vec_in(heap_base, alloc),
+            sides,
+            _phantom: PhantomData,
         };
         let ignorant_distinguished_ancestor = |mut position| {
             while position > 0 {
@@ -173,9 +255,9 @@ impl<TPriority: Ord> EditableHeap<TPriority> for WeakHeap<TPriority> {
         };
         for pos in (1..heap_len).rev() {
             let ancestor_pos = ignorant_distinguished_ancestor(pos);
-            if heap.data[ancestor_pos].priority < heap.data[pos].priority {
+            if heap.data.priority(ancestor_pos) < heap.data.priority(pos) {
                 heap.data.swap(ancestor_pos, pos);
-                heap.sides[pos].flip();
+                heap.sides.flip(pos);
             }
         }
 
@@ -183,8 +265,32 @@ impl<TPriority: Ord> EditableHeap<TPriority> for WeakHeap<TPriority> {
     }
 
     #[inline]
-    fn reserve(&mut self, capacity: usize) {
-        self.data.reserve(capacity)
+    fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+        self.sides.reserve(additional);
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.data.try_reserve(additional)?;
+        self.sides.try_reserve(additional)
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        std::cmp::min(self.data.capacity(), self.sides.capacity())
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        self.data.shrink_to_fit();
+        self.sides.shrink_to_fit();
+    }
+
+    #[inline]
+    fn shrink_to(&mut self, min_capacity: usize) {
+        self.data.shrink_to(min_capacity);
+        self.sides.shrink_to(min_capacity);
     }
 
     /// Puts outer index and priority in queue
@@ -198,15 +304,10 @@ impl<TPriority: Ord> EditableHeap<TPriority> for WeakHeap<TPriority> {
         change_handler: TChangeHandler,
     ) {
         let new_index = self.data.len();
-        self.data.push(HeapEntry {
-            outer_pos,
-            priority,
-        });
-        self.sides.push(SiblingSide::default());
-        if new_index % 2 == 0 {
-            self.sides[new_index / 2] = SiblingSide::default();
-        }
-        self.heapify_up(HeapIndex(new_index), change_handler);
+        self.data.push(outer_pos, priority);
+        self.sides.push();
+        self.reset_parent_side(new_index);
+        self.heapify_up(HeapIndex::new(new_index), change_handler);
     }
 
     /// Removes item at position and returns it
@@ -214,35 +315,41 @@ impl<TPriority: Ord> EditableHeap<TPriority> for WeakHeap<TPriority> {
     fn remove<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
         &mut self,
         position: HeapIndex,
-        change_handler: TChangeHandler,
+        mut change_handler: TChangeHandler,
     ) -> Option<(MediatorIndex, TPriority)> {
         if position >= self.len() {
             return None;
         }
 
-        if HeapIndex(position.0 + 1) == self.len() {
-            let result = self.data.pop().expect("At least 1 item");
+        if HeapIndex::new(position.get() + 1) == self.len() {
             self.sides.pop();
-            return Some(result.conv_pair());
+            return self.data.pop();
         }
 
-        let result = self.data.swap_remove(position.0);
+        let result = self.data.swap_remove(position.get());
         self.sides.pop();
-        self.heapify_down(position, change_handler);
-        Some(result.conv_pair())
+        // Report move before any comparison so panic in it cannot leave stale index
+        change_handler(self.data.outer_pos(position.get()), position);
+        // Moved item can be bigger than ancestor of removed one
+        self.restore_order(position, change_handler);
+        Some(result)
     }
 
-    fn data(&self) -> &[HeapEntry<TPriority>] {
-        &self.data
+    #[inline]
+    fn data(&self) -> HeapSlice<'_, TPriority> {
+        self.data.view()
+    }
+
+    #[inline]
+    fn len(&self) -> HeapIndex {
+        HeapIndex::new(self.data.len())
     }
 
     // Changes outer index for element and return old index
     fn change_outer_pos(&mut self, outer_pos: MediatorIndex, position: HeapIndex) -> MediatorIndex {
         debug_assert!(position < self.len(), "Out of index during changing key");
 
-        let old_pos = self.data[position.0].outer_pos;
-        self.data[position.0].outer_pos = outer_pos;
-        old_pos
+        self.data.replace_outer_pos(position.get(), outer_pos)
     }
 
     /// Changes priority of queue item
@@ -258,8 +365,8 @@ impl<TPriority: Ord> EditableHeap<TPriority> for WeakHeap<TPriority> {
             "Out of index during changing priority"
         );
 
-        let old = std::mem::replace(&mut self.data[position.0].priority, updated);
-        match old.cmp(&self.data[position.0].priority) {
+        let old = std::mem::replace(self.data.priority_mut(position.get()), updated);
+        match old.cmp(self.data.priority(position.get())) {
             Ordering::Less => {
                 self.heapify_up(position, change_handler);
             }
@@ -271,21 +378,85 @@ impl<TPriority: Ord> EditableHeap<TPriority> for WeakHeap<TPriority> {
         old
     }
 
+    #[inline]
+    fn priority_mut(&mut self, position: HeapIndex) -> &mut TPriority {
+        self.data.priority_mut(position.get())
+    }
+
+    fn restore_order<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        change_handler: TChangeHandler,
+    ) {
+        debug_assert!(position < self.len(), "Out of index during restoring order");
+
+        let ancestor_pos = self.distinguished_ancestor(position).get();
+        if position.get() > 0
+            && self.data.priority(ancestor_pos) < self.data.priority(position.get())
+        {
+            self.heapify_up(position, change_handler);
+        } else {
+            self.heapify_down(position, change_handler);
+        }
+    }
+
     #[inline]
     fn most_prioritized_idx(&self) -> Option<(MediatorIndex, HeapIndex)> {
-        self.data.get(0).map(|x| (x.outer_pos, HeapIndex(0)))
+        if self.data.is_empty() {
+            return None;
+        }
+        Some((self.data.outer_pos(0), HeapIndex(0)))
     }
 
     fn clear(&mut self) {
         self.data.clear();
         self.sides.clear();
     }
+
+    /// Appends items of other heap and sifts them up like on push
+    /// Calls change_handler for every item of other heap and every move of old values
+    fn meld<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        other: Self,
+        mut change_handler: TChangeHandler,
+    ) {
+        let start = self.data.len();
+        self.data.append(other.data);
+        self.sides.resize(self.data.len());
+        // Report all new items before any comparison
+        for position in start..self.data.len() {
+            change_handler(self.data.outer_pos(position), HeapIndex::new(position));
+        }
+        for position in start..self.data.len() {
+            self.reset_parent_side(position);
+            self.heapify_up(HeapIndex::new(position), &mut change_handler);
+        }
+    }
+
+    fn validate(&self) -> Result<(), InvariantError> {
+        if self.sides.len() != self.data.len() {
+            return Err(InvariantError::HeapStructure);
+        }
+        // Root has only one child so it must be at index 1
+        if !self.data.is_empty() && self.sides.is_right(0) {
+            return Err(InvariantError::HeapStructure);
+        }
+        for child in 1..self.data.len() {
+            let parent = self.distinguished_ancestor(HeapIndex::new(child)).get();
+            if self.data.priority(parent) < self.data.priority(child) {
+                return Err(InvariantError::HeapOrder { parent, child });
+            }
+        }
+        Ok(())
+    }
 }
 
-impl<TPriority: Debug + Ord> Debug for WeakHeap<TPriority> {
+impl<TPriority: Debug + Ord, TStorage: HeapStorage<TPriority>> Debug
+    for WeakHeap<TPriority, TStorage>
+{
     #[inline]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        self.data.fmt(f)
+        self.data.view().fmt(f)
     }
 }
 
@@ -299,12 +470,12 @@ mod tests {
     fn is_valid_weak_heap<TP: Ord + Debug>(heap: &WeakHeap<TP>) -> bool {
         for (i, current) in heap.data.iter().enumerate().skip(1) {
             let heap_parent_ind = i / 2;
-            if heap.first_child(HeapIndex(heap_parent_ind)) == HeapIndex(i) {
+            if heap.first_child(HeapIndex::new(heap_parent_ind)) == HeapIndex::new(i) {
                 let parent = &heap.data[heap_parent_ind];
                 if parent.priority < current.priority {
                     println!(
                         "Heap condition violated at mediator index {}",
-                        current.outer_pos.0
+                        current.outer_pos.get()
                     );
                     println!("{:?}", heap);
                     return false;
@@ -320,14 +491,14 @@ mod tests {
             70, 50, 0, 1, 2, 4, 6, 7, 9, 72, 4, 4, 87, 78, 72, 6, 7, 9, 2, -50, -72, -50, -42, -1,
             -3, -13,
         ];
-        let mut maximum = std::i32::MIN;
+        let mut maximum = i32::MIN;
         let mut heap = <WeakHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
-        assert!(heap.data().get(0).is_none());
+        assert!(heap.data.is_empty());
         assert!(is_valid_weak_heap(&heap), "Heap state is invalid");
         for (key, x) in items
             .iter()
             .enumerate()
-            .map(|(i, &x)| (MediatorIndex(i), x))
+            .map(|(i, &x)| (MediatorIndex::new(i), x))
         {
             if x > maximum {
                 maximum = x;
@@ -338,8 +509,8 @@ mod tests {
                 "Heap state is invalid after pushing {}",
                 x
             );
-            assert!(heap.data().get(0).is_some());
-            let heap_max = heap.data().get(0).unwrap().priority;
+            assert!(!heap.data.is_empty());
+            let heap_max = heap.data.first().unwrap().priority;
             assert_eq!(maximum, heap_max)
         }
     }
@@ -356,21 +527,21 @@ mod tests {
             last_positions.insert(outer_pos, position);
         };
         for (i, &x) in items.iter().enumerate() {
-            heap.push(MediatorIndex(i), x, &mut on_pos_change);
+            heap.push(MediatorIndex::new(i), x, &mut on_pos_change);
         }
-        assert_eq!(heap.data().len(), last_positions.len());
+        assert_eq!(heap.data.len(), last_positions.len());
         for i in 0..items.len() {
-            let rem_idx = MediatorIndex(i);
+            let rem_idx = MediatorIndex::new(i);
             assert!(
                 last_positions.contains_key(&rem_idx),
                 "Not for all items change_handler called"
             );
             let position = last_positions[&rem_idx];
             assert_eq!(
-                items[(heap.data().get(position.0).unwrap()).outer_pos.0],
-                heap.data().get(position.0).unwrap().priority
+                items[(heap.data.get(position.get()).unwrap()).outer_pos.get()],
+                heap.data.get(position.get()).unwrap().priority
             );
-            assert_eq!((heap.data().get(position.0).unwrap()).outer_pos, rem_idx);
+            assert_eq!((heap.data.get(position.get()).unwrap()).outer_pos, rem_idx);
         }
 
         let mut removed = HashSet::<MediatorIndex>::new();
@@ -385,22 +556,19 @@ mod tests {
             let (key, _) = popped.unwrap();
             last_positions.remove(&key);
             removed.insert(key);
-            assert_eq!(heap.data().len(), last_positions.len());
-            for i in (0..items.len())
-                .into_iter()
-                .filter(|i| !removed.contains(&MediatorIndex(*i)))
-            {
-                let rem_idx = MediatorIndex(i);
+            assert_eq!(heap.data.len(), last_positions.len());
+            for i in (0..items.len()).filter(|i| !removed.contains(&MediatorIndex::new(*i))) {
+                let rem_idx = MediatorIndex::new(i);
                 assert!(
                     last_positions.contains_key(&rem_idx),
                     "Not for all items change_handler called"
                 );
                 let position = last_positions[&rem_idx];
                 assert_eq!(
-                    items[(heap.data().get(position.0).unwrap()).outer_pos.0],
-                    heap.data().get(position.0).unwrap().priority
+                    items[(heap.data.get(position.get()).unwrap()).outer_pos.get()],
+                    heap.data.get(position.get()).unwrap().priority
                 );
-                assert_eq!((heap.data().get(position.0).unwrap()).outer_pos, rem_idx);
+                assert_eq!((heap.data.get(position.get()).unwrap()).outer_pos, rem_idx);
             }
         }
     }
@@ -423,7 +591,7 @@ mod tests {
         println!("{}", items.len());
         let mut heap = <WeakHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
         for (i, &x) in items.iter().enumerate() {
-            heap.push(MediatorIndex(i), x, |_, _| {});
+            heap.push(MediatorIndex::new(i), x, |_, _| {});
         }
         assert!(is_valid_weak_heap(&heap), "Heap is invalid before pops");
 
@@ -434,7 +602,7 @@ mod tests {
             assert!(pop_res.is_some());
             let (rem_idx, val) = pop_res.unwrap();
             assert_eq!(val, x);
-            assert_eq!(items[rem_idx.0], val);
+            assert_eq!(items[rem_idx.get()], val);
             assert!(is_valid_weak_heap(&heap), "Heap is invalid after {}", x);
         }
 
@@ -462,18 +630,77 @@ mod tests {
         assert!(is_valid_weak_heap(&heap), "Invalid after lowering");
     }
 
+    #[test]
+    fn test_restore_order() {
+        let mut heap = <WeakHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
+        for x in 0..10 {
+            heap.push(MediatorIndex(x), x as i32, |_, _| {});
+        }
+        assert!(is_valid_weak_heap(&heap), "Invalid before change");
+        *heap.priority_mut(HeapIndex(7)) = 20;
+        heap.restore_order(HeapIndex(7), |_, _| {});
+        assert!(is_valid_weak_heap(&heap), "Invalid after upping");
+        assert_eq!(heap.data[0].priority, 20);
+        *heap.priority_mut(HeapIndex(0)) = -20;
+        heap.restore_order(HeapIndex(0), |_, _| {});
+        assert!(is_valid_weak_heap(&heap), "Invalid after lowering");
+        assert_eq!(heap.data[0].priority, 9);
+    }
+
+    #[test]
+    fn test_remove_middle() {
+        let mut heap = <WeakHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
+        for x in 0..30 {
+            heap.push(MediatorIndex(x), ((x * 7) % 30) as i32, |_, _| {});
+        }
+        assert_eq!(heap.validate(), Ok(()));
+        while !heap.is_empty() {
+            let position = HeapIndex(heap.len().0 / 2);
+            heap.remove(position, |_, _| {});
+            assert_eq!(
+                heap.validate(),
+                Ok(()),
+                "Invalid after removing from the middle"
+            );
+        }
+
+        heap.push(MediatorIndex(0), 0, |_, _| {});
+        heap.sides.flip(0);
+        assert_eq!(heap.validate(), Err(InvariantError::HeapStructure));
+    }
+
     #[test]
     fn test_clear() {
         let mut heap = <WeakHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
         for x in 0..5 {
             heap.push(MediatorIndex(x), x as i32, |_, _| {});
         }
-        assert!(!heap.data().is_empty(), "Heap must be non empty");
+        assert!(!heap.data.is_empty(), "Heap must be non empty");
         heap.data.clear();
-        assert!(heap.data().is_empty(), "Heap must be empty");
+        assert!(heap.data.is_empty(), "Heap must be empty");
         assert_eq!(heap.remove(HeapIndex(0), |_, _| {}), None);
     }
 
+    #[test]
+    fn test_capacity() {
+        let mut heap =
+            <WeakHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::with_capacity(20));
+        assert!(heap.capacity() >= 20);
+        heap.reserve(100);
+        assert!(heap.capacity() >= 100 && heap.sides.capacity() >= 100);
+        for x in 0..5 {
+            heap.push(MediatorIndex(x), x as i32, |_, _| {});
+        }
+        heap.shrink_to(10);
+        assert!(heap.capacity() >= 10 && heap.sides.capacity() < 100);
+        heap.shrink_to_fit();
+        // Sides are packed into words of 64 bits
+        assert!(heap.capacity() >= 5 && heap.data.capacity() < 10 && heap.sides.capacity() <= 64);
+        assert!(heap.try_reserve(50).is_ok());
+        assert!(heap.capacity() >= 55);
+        assert!(heap.try_reserve(usize::MAX).is_err());
+    }
+
     #[test]
     fn test_change_change_outer_pos() {
         let mut heap = <WeakHeap<i32> as EditableHeap<i32>>::from_entries_vec(Vec::new());
@@ -481,7 +708,7 @@ mod tests {
             heap.push(MediatorIndex(x), x as i32, |_, _| {});
         }
         assert_eq!(
-            heap.data().get(0).map(|n| *n),
+            heap.data.first().copied(),
             Some(HeapEntry {
                 outer_pos: MediatorIndex(4),
                 priority: 4i32
@@ -492,7 +719,7 @@ mod tests {
             MediatorIndex(4)
         );
         assert_eq!(
-            heap.data().get(0).map(|n| *n),
+            heap.data.first().copied(),
             Some(HeapEntry {
                 outer_pos: MediatorIndex(10),
                 priority: 4i32
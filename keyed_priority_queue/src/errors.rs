@@ -0,0 +1,103 @@
+use core::fmt::{self, Display};
+
+/// This is error type for [`set_priority`] method of [`KeyedPriorityQueue`].
+/// It means that queue doesn't contain such key.
+///
+/// [`KeyedPriorityQueue`]: struct.KeyedPriorityQueue.html
+/// [`set_priority`]: struct.KeyedPriorityQueue.html#method.set_priority
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Default)]
+pub struct SetPriorityNotFoundError;
+
+impl Display for SetPriorityNotFoundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "Key not found in KeyedPriorityQueue during set_priority")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SetPriorityNotFoundError {}
+
+/// This is error type for [`try_reserve`] method of [`KeyedPriorityQueue`].
+/// It means that memory allocation failed or capacity overflowed `usize`.
+///
+/// [`KeyedPriorityQueue`]: struct.KeyedPriorityQueue.html
+/// [`try_reserve`]: struct.KeyedPriorityQueue.html#method.try_reserve
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Default)]
+pub struct TryReserveError;
+
+impl Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "Memory allocation failed in KeyedPriorityQueue during try_reserve"
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TryReserveError {}
+
+/// This is error type for [`validate`] method of [`KeyedPriorityQueue`].
+/// It describes first found violation of internal invariants.
+///
+/// Indexes are positions in internal storages and only useful for debugging.
+///
+/// [`KeyedPriorityQueue`]: struct.KeyedPriorityQueue.html
+/// [`validate`]: struct.KeyedPriorityQueue.html#method.validate
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
+pub enum InvariantError {
+    /// Number of keys differs from number of heap items.
+    LengthMismatch { keys: usize, heap: usize },
+    /// Key points to position outside of heap.
+    IndexOutOfBounds { key_index: usize, heap_index: usize },
+    /// Key points to heap item which belongs to other key.
+    BrokenBackReference {
+        key_index: usize,
+        heap_index: usize,
+        back_index: usize,
+    },
+    /// Heap item has bigger priority than its parent.
+    HeapOrder { parent: usize, child: usize },
+    /// Auxiliary data of heap backend is inconsistent.
+    HeapStructure,
+}
+
+impl Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match *self {
+            InvariantError::LengthMismatch { keys, heap } => write!(
+                f,
+                "KeyedPriorityQueue has {} keys but {} heap items",
+                keys, heap
+            ),
+            InvariantError::IndexOutOfBounds {
+                key_index,
+                heap_index,
+            } => write!(
+                f,
+                "Key {} of KeyedPriorityQueue points to heap position {} out of bounds",
+                key_index, heap_index
+            ),
+            InvariantError::BrokenBackReference {
+                key_index,
+                heap_index,
+                back_index,
+            } => write!(
+                f,
+                "Key {} of KeyedPriorityQueue points to heap position {} which belongs to key {}",
+                key_index, heap_index, back_index
+            ),
+            InvariantError::HeapOrder { parent, child } => write!(
+                f,
+                "Heap item {} of KeyedPriorityQueue has bigger priority than its parent {}",
+                child, parent
+            ),
+            InvariantError::HeapStructure => {
+                write!(f, "Heap of KeyedPriorityQueue has inconsistent structure")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvariantError {}
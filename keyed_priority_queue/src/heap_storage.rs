@@ -0,0 +1,569 @@
+use crate::heap_traits::HeapEntry;
+use crate::keyed_priority_queue::TryReserveError;
+use crate::mediator::MediatorIndex;
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::vec::Vec as AllocVec;
+use std::fmt::Debug;
+use std::vec::Vec;
+
+/// Storage of heap items addressed by their positions in heap.
+///
+/// Heaps move items only using `swap`, `swap_remove` and `pop`,
+/// so they work with any layout of items.
+/// `Vec<HeapEntry>` keeps every priority next to its outer index,
+/// while [`SoaStorage`] keeps priorities and outer indexes in separate arrays.
+///
+/// [`SoaStorage`]: struct.SoaStorage.html
+pub trait HeapStorage<TPriority> {
+    /// Allocator which provides memory for items
+    type Alloc: Allocator + Clone;
+
+    fn from_entries_vec_in(entries: Vec<HeapEntry<TPriority>>, alloc: Self::Alloc) -> Self;
+
+    fn from_entries_vec(entries: Vec<HeapEntry<TPriority>>) -> Self
+    where
+        Self: Sized,
+        Self::Alloc: Default,
+    {
+        Self::from_entries_vec_in(entries, Self::Alloc::default())
+    }
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn priority(&self, position: usize) -> &TPriority;
+
+    fn priority_mut(&mut self, position: usize) -> &mut TPriority;
+
+    fn outer_pos(&self, position: usize) -> MediatorIndex;
+
+    /// Changes outer index of item and returns old one
+    fn replace_outer_pos(&mut self, position: usize, outer_pos: MediatorIndex) -> MediatorIndex;
+
+    fn push(&mut self, outer_pos: MediatorIndex, priority: TPriority);
+
+    fn pop(&mut self) -> Option<(MediatorIndex, TPriority)>;
+
+    fn swap_remove(&mut self, position: usize) -> (MediatorIndex, TPriority);
+
+    fn swap(&mut self, a: usize, b: usize);
+
+    /// Moves all items of other storage to the end of this one
+    fn append(&mut self, other: Self);
+
+    /// Returns readonly view of all items
+    fn view(&self) -> HeapSlice<'_, TPriority>;
+
+    fn reserve(&mut self, additional: usize);
+
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError>;
+
+    fn capacity(&self) -> usize;
+
+    fn shrink_to_fit(&mut self);
+
+    fn shrink_to(&mut self, min_capacity: usize);
+
+    fn clear(&mut self);
+}
+
+// Both vectors have same API so one implementation is shared.
+macro_rules! impl_vec_storage {
+    ($vec:ident) => {
+        #[inline(always)]
+        fn len(&self) -> usize {
+            $vec::len(self)
+        }
+
+        #[inline(always)]
+        fn priority(&self, position: usize) -> &TPriority {
+            &self[position].priority
+        }
+
+        #[inline(always)]
+        fn priority_mut(&mut self, position: usize) -> &mut TPriority {
+            &mut self[position].priority
+        }
+
+        #[inline(always)]
+        fn outer_pos(&self, position: usize) -> MediatorIndex {
+            self[position].outer_pos
+        }
+
+        #[inline]
+        fn replace_outer_pos(
+            &mut self,
+            position: usize,
+            outer_pos: MediatorIndex,
+        ) -> MediatorIndex {
+            std::mem::replace(&mut self[position].outer_pos, outer_pos)
+        }
+
+        #[inline]
+        fn push(&mut self, outer_pos: MediatorIndex, priority: TPriority) {
+            $vec::push(
+                self,
+                HeapEntry {
+                    outer_pos,
+                    priority,
+                },
+            )
+        }
+
+        #[inline]
+        fn pop(&mut self) -> Option<(MediatorIndex, TPriority)> {
+            $vec::pop(self).map(HeapEntry::conv_pair)
+        }
+
+        #[inline]
+        fn swap_remove(&mut self, position: usize) -> (MediatorIndex, TPriority) {
+            $vec::swap_remove(self, position).conv_pair()
+        }
+
+        #[inline(always)]
+        fn swap(&mut self, a: usize, b: usize) {
+            self.as_mut_slice().swap(a, b)
+        }
+
+        #[inline]
+        fn append(&mut self, mut other: Self) {
+            $vec::append(self, &mut other)
+        }
+
+        #[inline]
+        fn view(&self) -> HeapSlice<'_, TPriority> {
+            HeapSlice::from(self.as_slice())
+        }
+
+        #[inline]
+        fn reserve(&mut self, additional: usize) {
+            $vec::reserve(self, additional)
+        }
+
+        #[inline]
+        fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+            $vec::try_reserve(self, additional).map_err(|_| TryReserveError {})
+        }
+
+        #[inline]
+        fn capacity(&self) -> usize {
+            $vec::capacity(self)
+        }
+
+        #[inline]
+        fn shrink_to_fit(&mut self) {
+            $vec::shrink_to_fit(self)
+        }
+
+        #[inline]
+        fn shrink_to(&mut self, min_capacity: usize) {
+            $vec::shrink_to(self, min_capacity)
+        }
+
+        #[inline]
+        fn clear(&mut self) {
+            $vec::clear(self)
+        }
+    };
+}
+
+impl<TPriority> HeapStorage<TPriority> for Vec<HeapEntry<TPriority>> {
+    type Alloc = Global;
+
+    #[inline]
+    fn from_entries_vec_in(entries: Vec<HeapEntry<TPriority>>, _alloc: Global) -> Self {
+        entries
+    }
+
+    impl_vec_storage!(Vec);
+}
+
+/// Keeps items in memory of allocator `A`.
+impl<TPriority, A: Allocator + Clone> HeapStorage<TPriority> for AllocVec<HeapEntry<TPriority>, A> {
+    type Alloc = A;
+
+    #[inline]
+    fn from_entries_vec_in(entries: Vec<HeapEntry<TPriority>>, alloc: A) -> Self {
+        let mut data = AllocVec::with_capacity_in(entries.capacity(), alloc);
+        data.extend(entries);
+        data
+    }
+
+    impl_vec_storage!(AllocVec);
+}
+
+/// Storage which keeps priorities and outer indexes in separate arrays.
+///
+/// Sifting compares only priorities, so with big priorities
+/// it touches fewer cache lines than `Vec<HeapEntry>` which is used by default.
+///
+/// ### Examples
+///
+///
+/// ```
+/// use keyed_priority_queue::{BinaryHeap, KeyedPriorityQueue, SoaStorage};
+/// let mut queue: KeyedPriorityQueue<&str, [u64; 4], BinaryHeap<_, SoaStorage<_>>> =
+///     KeyedPriorityQueue::new();
+/// queue.push("first", [1, 0, 0, 0]);
+/// queue.push("second", [2, 0, 0, 0]);
+/// assert_eq!(queue.pop(), Some(("second", [2, 0, 0, 0])));
+/// ```
+#[derive(Clone)]
+pub struct SoaStorage<TPriority, A: Allocator + Clone = Global> {
+    outer_positions: AllocVec<MediatorIndex, A>,
+    priorities: AllocVec<TPriority, A>,
+}
+
+impl<TPriority, A: Allocator + Clone> HeapStorage<TPriority> for SoaStorage<TPriority, A> {
+    type Alloc = A;
+
+    fn from_entries_vec_in(entries: Vec<HeapEntry<TPriority>>, alloc: A) -> Self {
+        let mut outer_positions = AllocVec::with_capacity_in(entries.capacity(), alloc.clone());
+        let mut priorities = AllocVec::with_capacity_in(entries.capacity(), alloc);
+        for HeapEntry {
+            outer_pos,
+            priority,
+        } in entries
+        {
+            outer_positions.push(outer_pos);
+            priorities.push(priority);
+        }
+        SoaStorage {
+            outer_positions,
+            priorities,
+        }
+    }
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.priorities.len()
+    }
+
+    #[inline(always)]
+    fn priority(&self, position: usize) -> &TPriority {
+        &self.priorities[position]
+    }
+
+    #[inline(always)]
+    fn priority_mut(&mut self, position: usize) -> &mut TPriority {
+        &mut self.priorities[position]
+    }
+
+    #[inline(always)]
+    fn outer_pos(&self, position: usize) -> MediatorIndex {
+        self.outer_positions[position]
+    }
+
+    #[inline]
+    fn replace_outer_pos(&mut self, position: usize, outer_pos: MediatorIndex) -> MediatorIndex {
+        std::mem::replace(&mut self.outer_positions[position], outer_pos)
+    }
+
+    #[inline]
+    fn push(&mut self, outer_pos: MediatorIndex, priority: TPriority) {
+        self.outer_positions.push(outer_pos);
+        self.priorities.push(priority);
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Option<(MediatorIndex, TPriority)> {
+        let priority = self.priorities.pop()?;
+        let outer_pos = self.outer_positions.pop().expect("Arrays have same length");
+        Some((outer_pos, priority))
+    }
+
+    #[inline]
+    fn swap_remove(&mut self, position: usize) -> (MediatorIndex, TPriority) {
+        let priority = self.priorities.swap_remove(position);
+        (self.outer_positions.swap_remove(position), priority)
+    }
+
+    #[inline(always)]
+    fn swap(&mut self, a: usize, b: usize) {
+        self.priorities.swap(a, b);
+        self.outer_positions.swap(a, b);
+    }
+
+    #[inline]
+    fn append(&mut self, mut other: Self) {
+        self.priorities.append(&mut other.priorities);
+        self.outer_positions.append(&mut other.outer_positions);
+    }
+
+    #[inline]
+    fn view(&self) -> HeapSlice<'_, TPriority> {
+        HeapSlice {
+            repr: SliceRepr::Split {
+                outer_positions: &self.outer_positions,
+                priorities: &self.priorities,
+            },
+        }
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        self.priorities.reserve(additional);
+        self.outer_positions.reserve(additional);
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.priorities
+            .try_reserve(additional)
+            .map_err(|_| TryReserveError {})?;
+        self.outer_positions
+            .try_reserve(additional)
+            .map_err(|_| TryReserveError {})
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        std::cmp::min(self.priorities.capacity(), self.outer_positions.capacity())
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        self.priorities.shrink_to_fit();
+        self.outer_positions.shrink_to_fit();
+    }
+
+    #[inline]
+    fn shrink_to(&mut self, min_capacity: usize) {
+        self.priorities.shrink_to(min_capacity);
+        self.outer_positions.shrink_to(min_capacity);
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.priorities.clear();
+        self.outer_positions.clear();
+    }
+}
+
+impl<TPriority: Debug, A: Allocator + Clone> Debug for SoaStorage<TPriority, A> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        self.view().fmt(f)
+    }
+}
+
+/// Readonly view of heap items in order of their positions.
+pub struct HeapSlice<'a, TPriority> {
+    repr: SliceRepr<'a, TPriority>,
+}
+
+enum SliceRepr<'a, TPriority> {
+    Entries(&'a [HeapEntry<TPriority>]),
+    Split {
+        outer_positions: &'a [MediatorIndex],
+        priorities: &'a [TPriority],
+    },
+}
+
+impl<'a, TPriority> HeapSlice<'a, TPriority> {
+    #[inline(always)]
+    pub(crate) fn len(&self) -> usize {
+        match self.repr {
+            SliceRepr::Entries(entries) => entries.len(),
+            SliceRepr::Split { priorities, .. } => priorities.len(),
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline(always)]
+    pub(crate) fn outer_pos(&self, position: usize) -> MediatorIndex {
+        match self.repr {
+            SliceRepr::Entries(entries) => entries[position].outer_pos,
+            SliceRepr::Split {
+                outer_positions, ..
+            } => outer_positions[position],
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn priority(&self, position: usize) -> &'a TPriority {
+        match self.repr {
+            SliceRepr::Entries(entries) => &entries[position].priority,
+            SliceRepr::Split { priorities, .. } => &priorities[position],
+        }
+    }
+
+    /// Returns outer index and priority of item or `None` if position is out of bounds
+    #[inline]
+    pub(crate) fn get(&self, position: usize) -> Option<(MediatorIndex, &'a TPriority)> {
+        if position < self.len() {
+            Some((self.outer_pos(position), self.priority(position)))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub(crate) fn iter(&self) -> HeapSliceIter<'a, TPriority> {
+        HeapSliceIter {
+            slice: *self,
+            range: 0..self.len(),
+        }
+    }
+}
+
+impl<'a, TPriority> From<&'a [HeapEntry<TPriority>]> for HeapSlice<'a, TPriority> {
+    #[inline]
+    fn from(entries: &'a [HeapEntry<TPriority>]) -> Self {
+        HeapSlice {
+            repr: SliceRepr::Entries(entries),
+        }
+    }
+}
+
+// Derive would require `TPriority: Copy`
+impl<'a, TPriority> Clone for HeapSlice<'a, TPriority> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, TPriority> Copy for HeapSlice<'a, TPriority> {}
+
+impl<'a, TPriority> Clone for SliceRepr<'a, TPriority> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, TPriority> Copy for SliceRepr<'a, TPriority> {}
+
+impl<'a, TPriority: Debug> Debug for HeapSlice<'a, TPriority> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self.repr {
+            SliceRepr::Entries(entries) => entries.fmt(f),
+            SliceRepr::Split { .. } => f
+                .debug_list()
+                .entries(self.iter().map(|(outer_pos, priority)| HeapEntry {
+                    outer_pos,
+                    priority,
+                }))
+                .finish(),
+        }
+    }
+}
+
+/// Iterator over outer indexes and priorities of items in heap
+pub(crate) struct HeapSliceIter<'a, TPriority> {
+    slice: HeapSlice<'a, TPriority>,
+    range: std::ops::Range<usize>,
+}
+
+impl<'a, TPriority> Iterator for HeapSliceIter<'a, TPriority> {
+    type Item = (MediatorIndex, &'a TPriority);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let position = self.range.next()?;
+        Some((
+            self.slice.outer_pos(position),
+            self.slice.priority(position),
+        ))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<'a, TPriority> ExactSizeIterator for HeapSliceIter<'a, TPriority> {}
+
+impl<'a, TPriority> Clone for HeapSliceIter<'a, TPriority> {
+    #[inline]
+    fn clone(&self) -> Self {
+        HeapSliceIter {
+            slice: self.slice,
+            range: self.range.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_storage<TStorage>()
+    where
+        TStorage: HeapStorage<u32>,
+        TStorage::Alloc: Default,
+    {
+        let entries = (0..10)
+            .map(|i| HeapEntry {
+                outer_pos: MediatorIndex::new(i),
+                priority: i as u32 * 10,
+            })
+            .collect();
+        let mut storage = TStorage::from_entries_vec(entries);
+        storage.swap(0, 9);
+        assert_eq!(storage.swap_remove(1), (MediatorIndex(1), 10));
+        assert_eq!(storage.pop(), Some((MediatorIndex(8), 80)));
+        storage.push(MediatorIndex(20), 200);
+        assert_eq!(
+            storage.replace_outer_pos(2, MediatorIndex(30)),
+            MediatorIndex(2)
+        );
+        *storage.priority_mut(3) += 1;
+        let mut other = TStorage::from_entries_vec(Vec::new());
+        other.push(MediatorIndex(40), 400);
+        storage.append(other);
+
+        let items: Vec<_> = storage
+            .view()
+            .iter()
+            .map(|(outer_pos, &priority)| (outer_pos.get(), priority))
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                (9, 90),
+                (0, 0),
+                (30, 20),
+                (3, 31),
+                (4, 40),
+                (5, 50),
+                (6, 60),
+                (7, 70),
+                (20, 200),
+                (40, 400)
+            ]
+        );
+        assert_eq!(storage.len(), storage.view().len());
+        assert_eq!(storage.view().get(9), Some((MediatorIndex(40), &400)));
+        assert_eq!(storage.view().get(10), None);
+
+        storage.clear();
+        assert!(storage.is_empty() && storage.view().is_empty());
+    }
+
+    #[test]
+    fn test_storages() {
+        check_storage::<Vec<HeapEntry<u32>>>();
+        check_storage::<SoaStorage<u32>>();
+        check_storage::<AllocVec<HeapEntry<u32>, Global>>();
+    }
+
+    #[test]
+    fn test_debug() {
+        let entries = vec![HeapEntry {
+            outer_pos: MediatorIndex(3),
+            priority: 5,
+        }];
+        let soa: SoaStorage<i32> = SoaStorage::from_entries_vec(entries.clone());
+        assert_eq!(format!("{:?}", soa), format!("{:?}", entries));
+    }
+}
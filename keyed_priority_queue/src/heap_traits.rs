@@ -1,12 +1,81 @@
+use crate::heap_storage::{HeapSlice, HeapStorage};
+use crate::keyed_priority_queue::InvariantError;
+use crate::keyed_priority_queue::TryReserveError;
 use crate::mediator::MediatorIndex;
+use allocator_api2::alloc::Allocator;
 use std::fmt::Debug;
+use std::marker::PhantomData;
 
-/// Wrapper around usize that can be used only as index of `BinaryHeap`
+/// Integer type which keeps indexes inside of queue.
+/// It is `u32` with `compact-index` feature
+/// so every stored index takes 4 bytes instead of 8 on 64-bit targets.
+#[cfg(not(feature = "compact-index"))]
+pub(crate) type IndexRepr = usize;
+#[cfg(feature = "compact-index")]
+pub(crate) type IndexRepr = u32;
+
+/// Converts index to stored form.
+/// Panics if it doesn't fit into `u32` with `compact-index` feature.
+#[inline(always)]
+pub(crate) fn index_to_repr(index: usize) -> IndexRepr {
+    #[cfg(feature = "compact-index")]
+    {
+        use std::convert::TryFrom;
+        match IndexRepr::try_from(index) {
+            Ok(index) => index,
+            Err(_) => too_many_items(),
+        }
+    }
+    #[cfg(not(feature = "compact-index"))]
+    {
+        index
+    }
+}
+
+#[inline(always)]
+pub(crate) fn index_from_repr(index: IndexRepr) -> usize {
+    #[cfg(feature = "compact-index")]
+    {
+        index as usize
+    }
+    #[cfg(not(feature = "compact-index"))]
+    {
+        index
+    }
+}
+
+#[cfg(feature = "compact-index")]
+#[cold]
+#[inline(never)]
+fn too_many_items() -> ! {
+    panic!(
+        "Queue can't contain more than {} items with `compact-index` feature",
+        IndexRepr::MAX
+    )
+}
+
+/// Wrapper around index that can be used only as index of `BinaryHeap`
 /// Mostly needed to statically check that
 /// Heap is not indexed by any other collection index
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
-pub struct HeapIndex(pub(crate) usize);
+pub struct HeapIndex(pub(crate) IndexRepr);
+
+impl HeapIndex {
+    #[inline(always)]
+    pub(crate) fn new(index: usize) -> Self {
+        HeapIndex(index_to_repr(index))
+    }
 
+    #[inline(always)]
+    pub(crate) fn get(self) -> usize {
+        index_from_repr(self.0)
+    }
+}
+
+/// Item of heap which keeps priority along with position of its key.
+/// Vectors of entries are used as [`HeapStorage`].
+///
+/// [`HeapStorage`]: trait.HeapStorage.html
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub struct HeapEntry<TPriority> {
     pub(crate) outer_pos: MediatorIndex,
@@ -20,15 +89,73 @@ impl<TPriority> HeapEntry<TPriority> {
     pub(crate) fn conv_pair(self) -> (MediatorIndex, TPriority) {
         (self.outer_pos, self.priority)
     }
+}
+
+/// Tracks item which is moved through heap during sifting.
+/// Reports position of the tracked item to change handler when dropped,
+/// so mediator stays consistent with heap even if comparison panics.
+pub(crate) struct SiftGuard<'a, TPriority, TStorage, TChangeHandler>
+where
+    TStorage: HeapStorage<TPriority>,
+    TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex),
+{
+    pub(crate) data: &'a mut TStorage,
+    pub(crate) position: usize,
+    change_handler: TChangeHandler,
+    _phantom: PhantomData<fn() -> TPriority>,
+}
+
+impl<'a, TPriority, TStorage, TChangeHandler> SiftGuard<'a, TPriority, TStorage, TChangeHandler>
+where
+    TStorage: HeapStorage<TPriority>,
+    TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex),
+{
+    #[inline(always)]
+    pub(crate) fn new(
+        data: &'a mut TStorage,
+        position: usize,
+        change_handler: TChangeHandler,
+    ) -> Self {
+        debug_assert!(position < data.len(), "Out of index in SiftGuard");
+        Self {
+            data,
+            position,
+            change_handler,
+            _phantom: PhantomData,
+        }
+    }
 
+    /// Moves tracked item to `target`
+    /// and reports new position of item which was there
     #[inline(always)]
-    pub(crate) fn priority_ref(&self) -> &TPriority {
-        &self.priority
+    pub(crate) fn move_to(&mut self, target: usize) {
+        self.data.swap(self.position, target);
+        (self.change_handler)(
+            self.data.outer_pos(self.position),
+            HeapIndex::new(self.position),
+        );
+        self.position = target;
     }
 
+    /// Swaps tracked position with `other` keeping position tracked
+    /// and reports new position of item which moved to `other`
     #[inline(always)]
-    pub(crate) fn to_outer(&self) -> MediatorIndex {
-        self.outer_pos
+    pub(crate) fn exchange(&mut self, other: usize) {
+        self.data.swap(self.position, other);
+        (self.change_handler)(self.data.outer_pos(other), HeapIndex::new(other));
+    }
+}
+
+impl<'a, TPriority, TStorage, TChangeHandler> Drop
+    for SiftGuard<'a, TPriority, TStorage, TChangeHandler>
+where
+    TStorage: HeapStorage<TPriority>,
+    TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex),
+{
+    #[inline(always)]
+    fn drop(&mut self) {
+        let position = self.position;
+        (self.change_handler)(self.data.outer_pos(position), HeapIndex::new(position));
     }
 }
 
@@ -45,10 +172,32 @@ impl<TPriority: Debug> Debug for HeapEntry<TPriority> {
 }
 
 pub trait EditableHeap<TPriority: Ord> {
-    fn from_entries_vec(heap_base: Vec<HeapEntry<TPriority>>) -> Self;
+    /// Allocator which provides memory for heap and for keys of queue
+    type Alloc: Allocator + Clone;
+
+    fn from_entries_vec_in(heap_base: Vec<HeapEntry<TPriority>>, alloc: Self::Alloc) -> Self;
+
+    fn from_entries_vec(heap_base: Vec<HeapEntry<TPriority>>) -> Self
+    where
+        Self: Sized,
+        Self::Alloc: Default,
+    {
+        Self::from_entries_vec_in(heap_base, Self::Alloc::default())
+    }
 
     fn reserve(&mut self, additional: usize);
 
+    /// Tries to reserve space for at least `additional` new elements
+    /// Returns error instead of aborting if allocation fails
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError>;
+
+    /// Returns number of elements heap can hold without reallocation
+    fn capacity(&self) -> usize;
+
+    fn shrink_to_fit(&mut self);
+
+    fn shrink_to(&mut self, min_capacity: usize);
+
     /// Puts outer index and priority in queue
     /// outer_pos is assumed to be unique but not validated
     /// because validation too expensive
@@ -68,10 +217,11 @@ pub trait EditableHeap<TPriority: Ord> {
         change_handler: TChangeHandler,
     ) -> Option<(MediatorIndex, TPriority)>;
 
-    fn data(&self) -> &[HeapEntry<TPriority>];
+    /// Returns readonly view of items in order of their positions
+    fn data(&self) -> HeapSlice<'_, TPriority>;
 
     fn len(&self) -> HeapIndex {
-        HeapIndex(self.data().len())
+        HeapIndex::new(self.data().len())
     }
 
     fn is_empty(&self) -> bool {
@@ -90,7 +240,76 @@ pub trait EditableHeap<TPriority: Ord> {
         change_handler: TChangeHandler,
     ) -> TPriority;
 
+    /// Gives mutable access to priority of queue item
+    /// Heap can become invalid after modification
+    /// so `restore_order` must be called for same position after it
+    fn priority_mut(&mut self, position: HeapIndex) -> &mut TPriority;
+
+    /// Moves item up or down to make heap valid again
+    /// after its priority was modified through `priority_mut`
+    /// Calls change_handler for every move of old values
+    fn restore_order<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        position: HeapIndex,
+        change_handler: TChangeHandler,
+    );
+
     fn most_prioritized_idx(&self) -> Option<(MediatorIndex, HeapIndex)>;
 
     fn clear(&mut self);
+
+    /// Moves all items of other heap into this one
+    /// Calls change_handler for every item of other heap and every move of old values
+    /// Implementations should put all items of other heap and report them
+    /// before any comparison, so no item is lost if comparison panics
+    /// Default implementation pushes items one by one, so items which aren't
+    /// pushed yet are dropped if comparison panics and queue removes their keys
+    fn meld<TChangeHandler: std::ops::FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        other: Self,
+        mut change_handler: TChangeHandler,
+    ) where
+        Self: Sized,
+    {
+        let mut other = other;
+        self.reserve(other.data().len());
+        while let Some(last) = other.data().len().checked_sub(1) {
+            let (outer_pos, priority) = other
+                .remove(HeapIndex::new(last), |_, _| {})
+                .expect("Checked by len");
+            self.push(outer_pos, priority, &mut change_handler);
+        }
+    }
+
+    /// Checks that heap property holds and internal structures are consistent
+    /// Time complexity - O(n)
+    fn validate(&self) -> Result<(), InvariantError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HeapIndex;
+    use crate::mediator::MediatorIndex;
+
+    #[test]
+    fn test_index_conversion() {
+        assert_eq!(HeapIndex::new(12345).get(), 12345);
+        assert_eq!(MediatorIndex::new(54321).get(), 54321);
+        let max = u32::MAX as usize;
+        assert_eq!(HeapIndex::new(max).get(), max);
+    }
+
+    #[cfg(feature = "compact-index")]
+    #[test]
+    fn test_compact_entry_size() {
+        assert_eq!(std::mem::size_of::<HeapIndex>(), 4);
+        assert_eq!(std::mem::size_of::<super::HeapEntry<u32>>(), 8);
+    }
+
+    #[cfg(feature = "compact-index")]
+    #[test]
+    #[should_panic(expected = "compact-index")]
+    fn test_compact_index_overflow() {
+        HeapIndex::new(u32::MAX as usize + 1);
+    }
 }
@@ -1,14 +1,17 @@
-use crate::heap_traits::{EditableHeap, HeapEntry, HeapIndex};
+use crate::heap_traits::EditableHeap;
+use crate::keyed_value_priority_queue::{
+    KeyedValuePriorityQueue, KeyedValuePriorityQueueBorrowIter, OccupiedValueEntry, PriorityGuard,
+    VacantValueEntry, ValueEntry,
+};
+use allocator_api2::alloc::{Allocator, Global};
 use std::borrow::Borrow;
 use std::collections::hash_map::RandomState;
-use std::fmt::{Debug, Display};
+use std::fmt::Debug;
 use std::hash::{BuildHasher, Hash};
 use std::iter::FromIterator;
+use std::ops::Index;
 
-use crate::mediator::{
-    Mediator, MediatorEntry, MediatorIndex, OccupiedEntry as MediatorOccupiedEntry,
-    VacantEntry as MediatorVacantEntry,
-};
+pub use crate::errors::{InvariantError, SetPriorityNotFoundError, TryReserveError};
 
 /// A priority queue that support lookup by key.
 ///
@@ -18,6 +21,10 @@ use crate::mediator::{
 /// It is logic error if key values changes somehow while in queue.
 /// This changes normally possible only through `Cell`, `RefCell`, global state, IO, or unsafe code.
 ///
+/// If `Ord` implementation of `TPriority` is inconsistent or panics,
+/// queue may return items in wrong order but it stays internally consistent:
+/// every key keeps its priority and can be still accessed, changed or removed.
+///
 /// If you feel KeyedPriorityQueue slow, it can be because it uses RandomState (relatably slow but strong against HashDoS attack) hasher by default.
 /// You can try [fnv] or [fxhash] crates hashers.
 ///
@@ -125,13 +132,16 @@ where
     THeap: EditableHeap<TPriority>,
     S: BuildHasher,
 {
-    heap: THeap,
-    key_to_pos: Mediator<TKey, S>,
-    _phantom: std::marker::PhantomData<TPriority>,
+    // Queue with `()` values keeps all the logic so both queues behave the same
+    pub(crate) inner: KeyedValuePriorityQueue<TKey, (), TPriority, THeap, S>,
 }
 
-impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>>
-    KeyedPriorityQueue<TKey, TPriority, THeap, RandomState>
+impl<TKey, TPriority, THeap> KeyedPriorityQueue<TKey, TPriority, THeap, RandomState>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    THeap::Alloc: Default,
 {
     /// Creates an empty queue
     ///
@@ -165,8 +175,53 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>>
     }
 }
 
-impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHasher>
-    KeyedPriorityQueue<TKey, TPriority, THeap, S>
+impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>>
+    KeyedPriorityQueue<TKey, TPriority, THeap, RandomState>
+{
+    /// Creates an empty queue which takes memory from allocator of heap.
+    /// Both heap and keys are stored in memory of `alloc`.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use allocator_api2::alloc::Global;
+    /// use keyed_priority_queue::KeyedBinaryPriorityQueueIn;
+    /// let mut queue: KeyedBinaryPriorityQueueIn<&str, i32, Global> =
+    ///     KeyedBinaryPriorityQueueIn::new_in(Global);
+    /// queue.push("Key", 4);
+    /// ```
+    #[inline]
+    pub fn new_in(alloc: THeap::Alloc) -> Self {
+        Self::with_capacity_and_hasher_in(0, RandomState::default(), alloc)
+    }
+
+    /// Creates an empty queue which takes memory from allocator of heap
+    /// with allocated memory enough to keep `capacity` elements without reallocation.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use allocator_api2::alloc::Global;
+    /// use keyed_priority_queue::KeyedWeakPriorityQueueIn;
+    /// let mut queue: KeyedWeakPriorityQueueIn<&str, i32, Global> =
+    ///     KeyedWeakPriorityQueueIn::with_capacity_in(10, Global);
+    /// queue.push("Key", 4);
+    /// ```
+    #[inline]
+    pub fn with_capacity_in(capacity: usize, alloc: THeap::Alloc) -> Self {
+        Self::with_capacity_and_hasher_in(capacity, RandomState::default(), alloc)
+    }
+}
+
+impl<TKey, TPriority, THeap, S> KeyedPriorityQueue<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    THeap::Alloc: Default,
+    S: BuildHasher,
 {
     /// Creates an empty queue with specific Hasher
     ///
@@ -199,13 +254,69 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
     /// ```
     #[inline]
     pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        Self::with_capacity_and_hasher_in(capacity, hasher, THeap::Alloc::default())
+    }
+}
+
+impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHasher>
+    KeyedPriorityQueue<TKey, TPriority, THeap, S>
+{
+    /// Creates an empty queue with specific Hasher
+    /// which takes memory from allocator of heap.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use allocator_api2::alloc::Global;
+    /// use keyed_priority_queue::KeyedBinaryPriorityQueueIn;
+    /// use std::collections::hash_map::RandomState;
+    /// let mut queue: KeyedBinaryPriorityQueueIn<&str, i32, Global> =
+    ///     KeyedBinaryPriorityQueueIn::with_hasher_in(RandomState::default(), Global);
+    /// queue.push("Key", 4);
+    /// ```
+    #[inline]
+    pub fn with_hasher_in(hasher: S, alloc: THeap::Alloc) -> Self {
+        Self::with_capacity_and_hasher_in(0, hasher, alloc)
+    }
+
+    /// Creates an empty queue with specific Hasher
+    /// which takes memory from allocator of heap
+    /// with allocated memory enough to keep `capacity` elements without reallocation.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use allocator_api2::alloc::Global;
+    /// use keyed_priority_queue::KeyedWeakPriorityQueueIn;
+    /// use std::collections::hash_map::RandomState;
+    /// let mut queue: KeyedWeakPriorityQueueIn<&str, i32, Global> =
+    ///     KeyedWeakPriorityQueueIn::with_capacity_and_hasher_in(10, RandomState::default(), Global);
+    /// queue.push("Key", 4);
+    /// ```
+    pub fn with_capacity_and_hasher_in(capacity: usize, hasher: S, alloc: THeap::Alloc) -> Self {
         Self {
-            heap: THeap::from_entries_vec(Vec::with_capacity(capacity)),
-            key_to_pos: Mediator::with_capacity_and_hasher(capacity, hasher),
-            _phantom: std::marker::PhantomData,
+            inner: KeyedValuePriorityQueue::with_capacity_and_hasher_in(capacity, hasher, alloc),
         }
     }
 
+    /// Returns reference to the allocator which provides memory for queue.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use allocator_api2::alloc::Global;
+    /// use keyed_priority_queue::KeyedBinaryPriorityQueue;
+    /// let queue: KeyedBinaryPriorityQueue<&str, i32> = KeyedBinaryPriorityQueue::new();
+    /// let _: &Global = queue.allocator();
+    /// ```
+    #[inline]
+    pub fn allocator(&self) -> &THeap::Alloc {
+        self.inner.allocator()
+    }
+
     /// Reserves space for at least `additional` new elements.
     ///
     /// ### Panics
@@ -224,8 +335,93 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
     /// ```
     #[inline]
     pub fn reserve(&mut self, additional: usize) {
-        self.heap.reserve(additional);
-        self.key_to_pos.reserve(additional);
+        self.inner.reserve(additional)
+    }
+
+    /// Tries to reserve space for at least `additional` new elements.
+    /// Returns [`TryReserveError`] instead of aborting if allocation fails
+    /// or the new capacity overflows `usize`.
+    ///
+    /// ### Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedWeakPriorityQueue;
+    /// let mut queue = KeyedWeakPriorityQueue::new();
+    /// assert!(queue.try_reserve(100).is_ok());
+    /// queue.push(4, 4);
+    /// assert!(queue.try_reserve(usize::MAX).is_err());
+    /// ```
+    ///
+    /// [`TryReserveError`]: struct.TryReserveError.html
+    #[inline]
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve(additional)
+    }
+
+    /// Returns the number of elements queue can hold without reallocation.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedBinaryPriorityQueue;
+    /// let queue: KeyedBinaryPriorityQueue<i32, i32> = KeyedBinaryPriorityQueue::with_capacity(100);
+    /// assert!(queue.capacity() >= 100);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Shrinks the capacity of the queue as much as possible.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedWeakPriorityQueue;
+    /// let mut queue: KeyedWeakPriorityQueue<i32, i32> = KeyedWeakPriorityQueue::with_capacity(100);
+    /// queue.push(1, 1);
+    /// queue.push(2, 2);
+    /// queue.shrink_to_fit();
+    /// assert!(queue.capacity() >= 2);
+    /// assert!(queue.capacity() < 100);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(n)*** if reallocation happens.
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    /// Shrinks the capacity of the queue with a lower bound.
+    ///
+    /// The capacity will remain at least as large as both the length
+    /// and the supplied value.
+    ///
+    /// ### Examples
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedBinaryPriorityQueue;
+    /// let mut queue: KeyedBinaryPriorityQueue<i32, i32> = KeyedBinaryPriorityQueue::with_capacity(100);
+    /// queue.push(1, 1);
+    /// queue.shrink_to(10);
+    /// assert!(queue.capacity() >= 10);
+    /// assert!(queue.capacity() < 100);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(n)*** if reallocation happens.
+    #[inline]
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.inner.shrink_to(min_capacity)
     }
 
     /// Adds new element to queue if missing key or replace its priority if key exists.
@@ -251,13 +447,8 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
     /// The worst case is when reallocation appears.
     /// In this case complexity of single call is ***O(n)***.
     pub fn push(&mut self, key: TKey, priority: TPriority) -> Option<TPriority> {
-        match self.entry(key) {
-            Entry::Vacant(entry) => {
-                entry.set_priority(priority);
-                None
-            }
-            Entry::Occupied(entry) => Some(entry.set_priority(priority)),
-        }
+        let ((), old_priority) = self.inner.push(key, (), priority)?;
+        Some(old_priority)
     }
 
     /// Remove and return item with the maximal priority.
@@ -279,8 +470,8 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
     ///
     /// Cost of pop is always ***O(log n)***
     pub fn pop(&mut self) -> Option<(TKey, TPriority)> {
-        let (to_remove, _) = self.heap.most_prioritized_idx()?;
-        Some(self.remove_internal(to_remove))
+        let (key, (), priority) = self.inner.pop()?;
+        Some((key, priority))
     }
 
     /// Get reference to the pair with the maximal priority.
@@ -298,38 +489,18 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
     ///
     /// Always ***O(1)***
     pub fn peek(&self) -> Option<(&TKey, &TPriority)> {
-        let (first_idx, heap_idx) = self.heap.most_prioritized_idx()?;
-        let (key, _idx_chk) = self.key_to_pos.get_index(first_idx);
-        debug_assert_eq!(heap_idx, _idx_chk);
-        let entry = self
-            .heap
-            .data()
-            .get(heap_idx.0)
-            .expect("Checked using key_to_pos");
-        Some((key, entry.priority_ref()))
+        let (key, (), priority) = self.inner.peek()?;
+        Some((key, priority))
     }
 
     /// Gets the given key's corresponding entry in the map for in-place manipulation.
     ///
     /// ## Time complexity
     /// Amortized ***O(1)***, uses only one hash lookup
-    pub fn entry(&mut self, key: TKey) -> Entry<TKey, TPriority, THeap, S> {
-        // Borrow checker treats borrowing a field as borrowing whole structure
-        // so we need to get references to fields to borrow them individually.
-        let key_to_pos = &mut self.key_to_pos;
-        let heap = &mut self.heap;
-
-        match key_to_pos.entry(key) {
-            MediatorEntry::Vacant(internal_entry) => Entry::Vacant(VacantEntry {
-                internal_entry,
-                heap,
-                _phantom: std::marker::PhantomData,
-            }),
-            MediatorEntry::Occupied(internal_entry) => Entry::Occupied(OccupiedEntry {
-                internal_entry,
-                heap,
-                _phantom: std::marker::PhantomData,
-            }),
+    pub fn entry(&mut self, key: TKey) -> Entry<'_, TKey, TPriority, THeap, S> {
+        match self.inner.entry(key) {
+            ValueEntry::Vacant(inner) => Entry::Vacant(VacantEntry { inner }),
+            ValueEntry::Occupied(inner) => Entry::Occupied(OccupiedEntry { inner }),
         }
     }
 
@@ -353,14 +524,39 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
         TKey: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
-        let heap_idx = self.key_to_pos.get(key)?;
-        Some(
-            self.heap
-                .data()
-                .get(heap_idx.0)
-                .expect("Must contain if key_to_pos contain")
-                .priority_ref(),
-        )
+        self.inner.get_priority(key)
+    }
+
+    /// Get guarded mutable reference to the priority by key.
+    /// Queue reorders the item when returned [`PriorityGuard`] is dropped.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedBinaryPriorityQueue;
+    /// let mut queue: KeyedBinaryPriorityQueue<&str, i32> = [("first", 0), ("second", 1), ("third", 2)]
+    ///                             .iter().cloned().collect();
+    /// if let Some(mut priority) = queue.get_mut(&"first") {
+    ///     *priority += 10;
+    /// }
+    /// assert_eq!(queue.get_priority(&"first"), Some(&10));
+    /// assert_eq!(queue.pop(), Some(("first", 10)));
+    /// assert!(queue.get_mut(&"Missing").is_none());
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Lookup is ***O(1)*** in average (limited by hash map key lookup),
+    /// dropping of guard costs up to ***O(log n)***.
+    ///
+    /// [`PriorityGuard`]: struct.PriorityGuard.html
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<PriorityGuard<'_, TKey, TPriority, THeap, S>>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.get_mut(key)
     }
 
     /// Set new priority for existing key and reorder the queue.
@@ -394,12 +590,7 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
         TKey: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
-        let map_pos = match self.key_to_pos.get_full(key) {
-            None => return Err(SetPriorityNotFoundError {}),
-            Some((idx, _, _)) => idx,
-        };
-
-        Ok(self.set_priority_internal(map_pos, priority))
+        self.inner.set_priority(key, priority)
     }
 
     /// Allow removing item by key.
@@ -460,8 +651,8 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
         TKey: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
-        let (index, _, _) = self.key_to_pos.get_full(key)?;
-        Some(self.remove_internal(index))
+        let (key, (), priority) = self.inner.remove_entry(key)?;
+        Some((key, priority))
     }
 
     /// Get the number of elements in queue.
@@ -480,8 +671,7 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
     /// Always ***O(1)***
     #[inline]
     pub fn len(&self) -> usize {
-        debug_assert_eq!(self.key_to_pos.len(), self.heap.data().len());
-        self.key_to_pos.len()
+        self.inner.len()
     }
 
     /// Returns true if queue is empty.
@@ -498,8 +688,7 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
     /// Always ***O(1)***
     #[inline]
     pub fn is_empty(&self) -> bool {
-        debug_assert_eq!(self.heap.data().is_empty(), self.key_to_pos.is_empty());
-        self.key_to_pos.is_empty()
+        self.inner.is_empty()
     }
 
     /// Make the queue empty.
@@ -517,8 +706,39 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
     /// Always ***O(n)***
     #[inline]
     pub fn clear(&mut self) {
-        self.heap.clear();
-        self.key_to_pos.clear();
+        self.inner.clear()
+    }
+
+    /// Checks internal consistency of the queue.
+    ///
+    /// Queue can be corrupted only if `Ord` implementation of priorities misbehaves,
+    /// e.g. it is inconsistent or priority was changed through interior mutability.
+    /// It may be used to diagnose such problems.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::{InvariantError, KeyedWeakPriorityQueue};
+    /// use std::cell::Cell;
+    ///
+    /// let priority = Cell::new(1);
+    /// let other_priority = Cell::new(2);
+    /// let mut queue = KeyedWeakPriorityQueue::new();
+    /// queue.push("first", &priority);
+    /// queue.push("second", &other_priority);
+    /// assert_eq!(queue.validate(), Ok(()));
+    ///
+    /// // Priority is changed without queue knowing it
+    /// priority.set(5);
+    /// assert_eq!(queue.validate(), Err(InvariantError::HeapOrder { parent: 0, child: 1 }));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(n)***
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        self.inner.validate()
     }
 
     /// Create readonly borrowing iterator over heap
@@ -538,50 +758,40 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
     /// ### Time complexity
     ///
     /// Iterating over whole queue is ***O(n)***
-    pub fn iter(&self) -> KeyedPriorityQueueBorrowIter<TKey, TPriority, S> {
+    pub fn iter(&self) -> KeyedPriorityQueueBorrowIter<'_, TKey, TPriority, S, THeap::Alloc> {
         KeyedPriorityQueueBorrowIter {
-            key_to_pos: &self.key_to_pos,
-            heap_iterator: self.heap.data().iter(),
+            inner: self.inner.iter(),
         }
     }
 
-    // Removes entry from by index of map
-    fn remove_internal(&mut self, position: MediatorIndex) -> (TKey, TPriority) {
-        // Borrow checker treats borrowing a field as borrowing whole structure
-        // so we need to get references to fields to borrow them individually.
-        let key_to_pos = &mut self.key_to_pos;
-        let heap = &mut self.heap;
-
-        let (_, heap_to_rem) = key_to_pos.get_index(position);
-
-        let (removed_idx, priority) = heap
-            .remove(heap_to_rem, |index, heap_idx| {
-                *key_to_pos.get_index_mut(index) = heap_idx
-            })
-            .expect("Checked by key_to_pos");
-        debug_assert_eq!(position, removed_idx);
-
-        let (removed_key, _) = key_to_pos.swap_remove_index(position);
-        if MediatorIndex(key_to_pos.len()) != removed_idx {
-            let (_, heap_idx_of_moved) = key_to_pos.get_index(removed_idx);
-            heap.change_outer_pos(removed_idx, heap_idx_of_moved);
-        }
-
-        (removed_key, priority)
-    }
-
-    // Do O(log n) heap updates and by-index map changes
-    fn set_priority_internal(&mut self, position: MediatorIndex, priority: TPriority) -> TPriority {
-        // Borrow checker treats borrowing a field as borrowing whole structure
-        // so we need to get references to fields to borrow them individually.
-        let heap = &mut self.heap;
-        let key_to_pos = &mut self.key_to_pos;
-
-        let (_, heap_idx) = key_to_pos.get_index(position);
-
-        heap.change_priority(heap_idx, priority, |index, heap_idx| {
-            *key_to_pos.get_index_mut(index) = heap_idx
-        })
+    /// Moves all items of `other` queue into this one.
+    /// If key is present in both queues, priority from `other` is kept.
+    ///
+    /// With [`LeftistHeap`] backend heaps are merged without sifting items one by one.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedLeftistPriorityQueue;
+    /// let mut queue: KeyedLeftistPriorityQueue<i32, i32> = (0..5).map(|x|(x,x)).collect();
+    /// let other: KeyedLeftistPriorityQueue<i32, i32> = (3..8).map(|x|(x,x * 2)).collect();
+    /// queue.meld(other);
+    /// assert_eq!(queue.len(), 8);
+    /// assert_eq!(queue.get_priority(&3), Some(&6));
+    /// assert_eq!(queue.pop(), Some((7, 14)));
+    /// assert_eq!(queue.pop(), Some((6, 12)));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Items of `other` are moved in ***O(m)***, plus ***O(log n)*** for every key
+    /// present in both queues. Merging of heaps costs ***O(log(n + m))***
+    /// for [`LeftistHeap`] and ***O(m log(n + m))*** for other heaps.
+    ///
+    /// [`LeftistHeap`]: struct.LeftistHeap.html
+    pub fn meld(&mut self, other: Self) {
+        self.inner.meld(other.inner)
     }
 }
 
@@ -612,9 +822,7 @@ where
     THeap: EditableHeap<TPriority>,
     S: BuildHasher,
 {
-    internal_entry: MediatorOccupiedEntry<'a, TKey, S>,
-    heap: &'a mut THeap,
-    _phantom: std::marker::PhantomData<TPriority>,
+    inner: OccupiedValueEntry<'a, TKey, (), TPriority, THeap, S>,
 }
 
 impl<'a, TKey, TPriority, THeap, S> OccupiedEntry<'a, TKey, TPriority, THeap, S>
@@ -630,12 +838,7 @@ where
     /// ***O(1)*** instant access
     #[inline]
     pub fn get_priority(&self) -> &TPriority {
-        let heap_idx = self.internal_entry.get_heap_idx();
-        self.heap
-            .data()
-            .get(heap_idx.0)
-            .expect("Must be in queue")
-            .priority_ref()
+        self.inner.get_priority()
     }
 
     /// Changes priority of key and returns old priority
@@ -644,19 +847,8 @@ where
     /// Up to ***O(log n)*** operations in worst case
     /// ***O(1)*** in best case
     #[inline]
-    pub fn set_priority(mut self, priority: TPriority) -> TPriority {
-        let heap_idx = self.internal_entry.get_heap_idx();
-
-        let heap = &mut self.heap;
-        let key_to_pos = unsafe {
-            // Safety: reference used only inside the method and never leaked away
-            // This method can be called only when Mediator field alive along with queue itself.
-            self.internal_entry.transform_to_map()
-        };
-
-        heap.change_priority(heap_idx, priority, |index, heap_idx| {
-            *key_to_pos.get_index_mut(index) = heap_idx;
-        })
+    pub fn set_priority(self, priority: TPriority) -> TPriority {
+        self.inner.set_priority(priority)
     }
 
     /// Get the reference to actual key
@@ -665,36 +857,16 @@ where
     /// ***O(1)*** instant access
     #[inline]
     pub fn get_key(&self) -> &TKey {
-        self.internal_entry.get_key()
+        self.inner.get_key()
     }
 
     /// Remove entry from queue
     ///
     /// ## Time complexity
     /// Up to ***O(log n)*** operations
-    pub fn remove(mut self) -> (TKey, TPriority) {
-        let heap_idx = self.internal_entry.get_heap_idx();
-        // Look `Mediator` `entry` method
-        let key_to_pos = unsafe {
-            // Safety: reference used only inside the method and never leaked away
-            // This method can be called only when Mediator field alive along with queue itself.
-            self.internal_entry.transform_to_map()
-        };
-        let heap = &mut self.heap;
-
-        let (removed_idx, priority) = heap
-            .remove(heap_idx, |index, heap_idx| {
-                *key_to_pos.get_index_mut(index) = heap_idx
-            })
-            .expect("Checked by key_to_pos");
-
-        let (removed_key, _) = key_to_pos.swap_remove_index(removed_idx);
-        if MediatorIndex(key_to_pos.len()) != removed_idx {
-            let (_, heap_idx_of_moved) = key_to_pos.get_index(removed_idx);
-            heap.change_outer_pos(removed_idx, heap_idx_of_moved);
-        }
-
-        (removed_key, priority)
+    pub fn remove(self) -> (TKey, TPriority) {
+        let (key, (), priority) = self.inner.remove();
+        (key, priority)
     }
 }
 
@@ -710,9 +882,7 @@ where
     THeap: EditableHeap<TPriority>,
     S: BuildHasher,
 {
-    internal_entry: MediatorVacantEntry<'a, TKey, S>,
-    heap: &'a mut THeap,
-    _phantom: std::marker::PhantomData<TPriority>,
+    inner: VacantValueEntry<'a, TKey, (), TPriority, THeap, S>,
 }
 
 impl<'a, TKey, TPriority, THeap, S> VacantEntry<'a, TKey, TPriority, THeap, S>
@@ -728,16 +898,15 @@ where
     /// Up to ***O(log n)*** operations
     #[inline]
     pub fn set_priority(self, priority: TPriority) {
-        let heap = self.heap;
-        let internal_entry = self.internal_entry;
-        let (key_to_pos, mediator_index) = unsafe {
-            // Safety: reference used only inside the method and never leaked away
-            // This method can be called only when Mediator field alive along with queue itself.
-            internal_entry.insert(HeapIndex(heap.data().len()))
-        };
-        heap.push(mediator_index, priority, |index, val| {
-            *key_to_pos.get_index_mut(index) = val
-        });
+        self.inner.insert((), priority);
+    }
+
+    // Puts key in place of item with the maximal priority and returns that item.
+    // Queue must not be empty.
+    #[inline]
+    pub(crate) fn replace_top(self, priority: TPriority) -> (TKey, TPriority) {
+        let (key, (), old_priority) = self.inner.replace_top((), priority);
+        (key, old_priority)
     }
 
     /// Get the reference to actual key
@@ -746,7 +915,16 @@ where
     /// ***O(1)*** instant access
     #[inline]
     pub fn get_key(&self) -> &TKey {
-        self.internal_entry.get_key()
+        self.inner.get_key()
+    }
+
+    /// Take ownership of the key without inserting it to queue
+    ///
+    /// ## Time complexity
+    /// ***O(1)*** instant access
+    #[inline]
+    pub fn into_key(self) -> TKey {
+        self.inner.into_key()
     }
 }
 
@@ -766,8 +944,13 @@ impl<
     }
 }
 
-impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHasher + Default>
-    Default for KeyedPriorityQueue<TKey, TPriority, THeap, S>
+impl<TKey, TPriority, THeap, S> Default for KeyedPriorityQueue<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    THeap::Alloc: Default,
+    S: BuildHasher + Default,
 {
     #[inline]
     fn default() -> Self {
@@ -775,8 +958,50 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
     }
 }
 
-impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHasher + Default>
-    FromIterator<(TKey, TPriority)> for KeyedPriorityQueue<TKey, TPriority, THeap, S>
+impl<TKey, TPriority, THeap, S, Q> Index<&Q> for KeyedPriorityQueue<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq + Borrow<Q>,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+    Q: Hash + Eq + ?Sized,
+{
+    type Output = TPriority;
+
+    /// Returns a reference to the priority corresponding to the supplied key.
+    ///
+    /// ### Panics
+    ///
+    /// Panics if the key is not present in the queue.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedWeakPriorityQueue;
+    /// let queue: KeyedWeakPriorityQueue<&str, i32> = [("first", 0), ("second", 1), ("third", 2)]
+    ///                             .iter().cloned().collect();
+    /// assert_eq!(queue[&"second"], 1);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(1)*** in average (limited by hash map key lookup).
+    #[inline]
+    fn index(&self, key: &Q) -> &TPriority {
+        self.get_priority(key)
+            .expect("Key not found in KeyedPriorityQueue")
+    }
+}
+
+impl<TKey, TPriority, THeap, S> FromIterator<(TKey, TPriority)>
+    for KeyedPriorityQueue<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    THeap::Alloc: Default,
+    S: BuildHasher + Default,
 {
     /// Allows building queue from iterator using `collect()`.
     /// At result it will be valid queue with unique keys.
@@ -799,46 +1024,46 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
     ///
     /// ***O(n log n)*** in average.
     fn from_iter<T: IntoIterator<Item = (TKey, TPriority)>>(i: T) -> Self {
-        let iter = i.into_iter();
-        let (min_size, _) = iter.size_hint();
-
-        let mut heap_base: Vec<HeapEntry<TPriority>> = Vec::with_capacity(min_size);
-        let mut key_to_pos: Mediator<TKey, S> =
-            Mediator::with_capacity_and_hasher(min_size, S::default());
-
-        for (key, priority) in iter {
-            match key_to_pos.entry(key) {
-                MediatorEntry::Vacant(entry) => {
-                    let outer_pos = entry.index();
-                    unsafe {
-                        // Safety: resulting reference never used
-                        entry.insert(HeapIndex(heap_base.len()));
-                    }
-                    heap_base.push(HeapEntry {
-                        outer_pos,
-                        priority,
-                    });
-                }
-                MediatorEntry::Occupied(entry) => {
-                    let HeapIndex(heap_pos) = entry.get_heap_idx();
-                    heap_base[heap_pos].priority = priority;
-                }
-            }
-        }
-        let heap = THeap::from_entries_vec(heap_base);
-
-        for (i, pos) in heap.data().iter().map(HeapEntry::to_outer).enumerate() {
-            let heap_idx = key_to_pos.get_index_mut(pos);
-            *heap_idx = HeapIndex(i);
-        }
         Self {
-            heap,
-            key_to_pos,
-            _phantom: std::marker::PhantomData,
+            inner: i
+                .into_iter()
+                .map(|(key, priority)| (key, (), priority))
+                .collect(),
         }
     }
 }
 
+impl<TKey, TPriority, THeap, S> Extend<(TKey, TPriority)>
+    for KeyedPriorityQueue<TKey, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    /// Pushes all items of iterator to queue.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedBinaryPriorityQueue;
+    /// let mut queue = KeyedBinaryPriorityQueue::new();
+    /// queue.push("first", 5);
+    /// queue.extend(vec![("first", 0), ("second", 1)]);
+    /// assert_eq!(queue.pop(), Some(("second", 1)));
+    /// assert_eq!(queue.pop(), Some(("first", 0)));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(k log n)*** in average where k is number of pushed items.
+    fn extend<T: IntoIterator<Item = (TKey, TPriority)>>(&mut self, iter: T) {
+        self.inner
+            .extend(iter.into_iter().map(|(key, priority)| (key, (), priority)))
+    }
+}
+
 impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>> IntoIterator
     for KeyedPriorityQueue<TKey, TPriority, THeap>
 {
@@ -912,34 +1137,30 @@ impl<TKey: Hash + Eq, TPriority: Ord, THeap: EditableHeap<TPriority>, S: BuildHa
 ///
 /// ### Time complexity
 /// Overall complexity of iteration is ***O(n)***
-pub struct KeyedPriorityQueueBorrowIter<'a, TKey, TPriority, S = RandomState>
+pub struct KeyedPriorityQueueBorrowIter<'a, TKey, TPriority, S = RandomState, A = Global>
 where
     TKey: 'a + Hash + Eq,
     TPriority: 'a,
     S: BuildHasher,
+    A: Allocator + Clone,
 {
-    heap_iterator: std::slice::Iter<'a, HeapEntry<TPriority>>,
-    key_to_pos: &'a Mediator<TKey, S>,
+    inner: KeyedValuePriorityQueueBorrowIter<'a, TKey, (), TPriority, S, A>,
 }
 
-impl<'a, TKey: 'a + Hash + Eq, TPriority: 'a, S: BuildHasher> Iterator
-    for KeyedPriorityQueueBorrowIter<'a, TKey, TPriority, S>
+impl<'a, TKey: 'a + Hash + Eq, TPriority: 'a, S: BuildHasher, A: Allocator + Clone> Iterator
+    for KeyedPriorityQueueBorrowIter<'a, TKey, TPriority, S, A>
 {
     type Item = (&'a TKey, &'a TPriority);
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        let heap_iterator = &mut self.heap_iterator;
-        let key_to_pos = &self.key_to_pos;
-        heap_iterator.next().map(|heap_entry| {
-            let (key, _) = key_to_pos.get_index(heap_entry.outer_pos);
-            (key, heap_entry.priority_ref())
-        })
+        let (key, (), priority) = self.inner.next()?;
+        Some((key, priority))
     }
 
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
-        self.heap_iterator.size_hint()
+        self.inner.size_hint()
     }
 
     #[inline]
@@ -947,25 +1168,203 @@ impl<'a, TKey: 'a + Hash + Eq, TPriority: 'a, S: BuildHasher> Iterator
     where
         Self: Sized,
     {
-        self.heap_iterator.count()
+        self.inner.count()
     }
 }
 
-/// This is error type for [`set_priority`] method of [`KeyedPriorityQueue`].
-/// It means that queue doesn't contain such key.
-///
-/// [`KeyedPriorityQueue`]: struct.KeyedPriorityQueue.html
-/// [`set_priority`]: struct.KeyedPriorityQueue.html#method.set_priority
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Default)]
-pub struct SetPriorityNotFoundError;
+#[cfg(feature = "rayon")]
+mod rayon_impls {
+    use super::KeyedPriorityQueue;
+    use crate::heap_traits::EditableHeap;
+    use crate::keyed_value_priority_queue::KeyedValuePriorityQueueParIter;
+    use allocator_api2::alloc::{Allocator, Global};
+    use rayon::iter::plumbing::{Consumer, ProducerCallback, UnindexedConsumer};
+    use rayon::prelude::*;
+    use std::hash::{BuildHasher, Hash};
+
+    impl<TKey, TPriority, THeap, S> FromParallelIterator<(TKey, TPriority)>
+        for KeyedPriorityQueue<TKey, TPriority, THeap, S>
+    where
+        TKey: Hash + Eq + Send,
+        TPriority: Ord + Send,
+        THeap: EditableHeap<TPriority>,
+        THeap::Alloc: Default,
+        S: BuildHasher + Default + Sync,
+    {
+        /// Allows building queue from parallel iterator using `collect()`.
+        /// Keys are hashed in parallel, later duplicates replace priority of earlier ones
+        /// like in sequential `collect()`.
+        ///
+        /// ### Examples
+        ///
+        ///
+        /// ```
+        /// use keyed_priority_queue::KeyedWeakPriorityQueue;
+        /// use rayon::prelude::*;
+        /// let mut queue: KeyedWeakPriorityQueue<u32, u32> =
+        ///     (0..1000u32).into_par_iter().map(|x| (x % 100, x)).collect();
+        /// assert_eq!(queue.len(), 100);
+        /// assert_eq!(queue.pop(), Some((99, 999)));
+        /// ```
+        ///
+        /// ### Time complexity
+        ///
+        /// ***O(n log n)*** in average.
+        fn from_par_iter<I>(par_iter: I) -> Self
+        where
+            I: IntoParallelIterator<Item = (TKey, TPriority)>,
+        {
+            Self {
+                inner: par_iter
+                    .into_par_iter()
+                    .map(|(key, priority)| (key, (), priority))
+                    .collect(),
+            }
+        }
+    }
 
-impl Display for SetPriorityNotFoundError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(f, "Key not found in KeyedPriorityQueue during set_priority")
+    impl<TKey, TPriority, THeap, S> ParallelExtend<(TKey, TPriority)>
+        for KeyedPriorityQueue<TKey, TPriority, THeap, S>
+    where
+        TKey: Hash + Eq + Send,
+        TPriority: Ord + Send,
+        THeap: EditableHeap<TPriority>,
+        S: BuildHasher + Sync,
+    {
+        /// Pushes all items of parallel iterator to queue.
+        /// Keys are hashed in parallel and inserted in order of iterator.
+        ///
+        /// ### Examples
+        ///
+        ///
+        /// ```
+        /// use keyed_priority_queue::KeyedBinaryPriorityQueue;
+        /// use rayon::prelude::*;
+        /// let mut queue = KeyedBinaryPriorityQueue::new();
+        /// queue.push(5, 0);
+        /// queue.par_extend((0..10).into_par_iter().map(|x| (x, x)));
+        /// assert_eq!(queue.len(), 10);
+        /// assert_eq!(queue.get_priority(&5), Some(&5));
+        /// ```
+        ///
+        /// ### Time complexity
+        ///
+        /// ***O(k log n)*** in average where k is number of pushed items.
+        fn par_extend<I>(&mut self, par_iter: I)
+        where
+            I: IntoParallelIterator<Item = (TKey, TPriority)>,
+        {
+            self.inner.par_extend(
+                par_iter
+                    .into_par_iter()
+                    .map(|(key, priority)| (key, (), priority)),
+            )
+        }
+    }
+
+    impl<'a, TKey, TPriority, THeap, S> IntoParallelIterator
+        for &'a KeyedPriorityQueue<TKey, TPriority, THeap, S>
+    where
+        TKey: 'a + Hash + Eq + Sync,
+        TPriority: 'a + Ord + Sync,
+        THeap: EditableHeap<TPriority>,
+        THeap::Alloc: Sync,
+        S: BuildHasher + Sync,
+    {
+        type Item = (&'a TKey, &'a TPriority);
+        type Iter = KeyedPriorityQueueParIter<'a, TKey, TPriority, S, THeap::Alloc>;
+
+        fn into_par_iter(self) -> Self::Iter {
+            KeyedPriorityQueueParIter {
+                inner: self.inner.par_iter(),
+            }
+        }
+    }
+
+    /// This is unordered parallel borrowing iterator over queue.
+    /// Returned by `par_iter` method when `rayon::prelude` is imported.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedBinaryPriorityQueue;
+    /// use rayon::prelude::*;
+    /// let queue: KeyedBinaryPriorityQueue<u64, u64> = (0..100).map(|x| (x, x * 2)).collect();
+    /// let sum: u64 = queue.par_iter().map(|(_, &priority)| priority).sum();
+    /// assert_eq!(sum, 9900);
+    /// ```
+    ///
+    /// ### Time complexity
+    /// Overall complexity of iteration is ***O(n)***
+    pub struct KeyedPriorityQueueParIter<'a, TKey, TPriority, S, A = Global>
+    where
+        TKey: 'a + Hash + Eq,
+        TPriority: 'a,
+        S: BuildHasher,
+        A: Allocator + Clone,
+    {
+        inner: KeyedValuePriorityQueueParIter<'a, TKey, (), TPriority, S, A>,
+    }
+
+    impl<'a, TKey, TPriority, S, A> KeyedPriorityQueueParIter<'a, TKey, TPriority, S, A>
+    where
+        TKey: 'a + Hash + Eq + Sync,
+        TPriority: 'a + Sync,
+        S: BuildHasher + Sync,
+        A: Allocator + Clone + Sync,
+    {
+        fn inner(self) -> impl IndexedParallelIterator<Item = (&'a TKey, &'a TPriority)> + 'a {
+            self.inner.map(|(key, (), priority)| (key, priority))
+        }
+    }
+
+    impl<'a, TKey, TPriority, S, A> ParallelIterator
+        for KeyedPriorityQueueParIter<'a, TKey, TPriority, S, A>
+    where
+        TKey: 'a + Hash + Eq + Sync,
+        TPriority: 'a + Sync,
+        S: BuildHasher + Sync,
+        A: Allocator + Clone + Sync,
+    {
+        type Item = (&'a TKey, &'a TPriority);
+
+        fn drive_unindexed<C>(self, consumer: C) -> C::Result
+        where
+            C: UnindexedConsumer<Self::Item>,
+        {
+            self.inner().drive_unindexed(consumer)
+        }
+
+        fn opt_len(&self) -> Option<usize> {
+            Some(self.inner.len())
+        }
+    }
+
+    impl<'a, TKey, TPriority, S, A> IndexedParallelIterator
+        for KeyedPriorityQueueParIter<'a, TKey, TPriority, S, A>
+    where
+        TKey: 'a + Hash + Eq + Sync,
+        TPriority: 'a + Sync,
+        S: BuildHasher + Sync,
+        A: Allocator + Clone + Sync,
+    {
+        fn len(&self) -> usize {
+            self.inner.len()
+        }
+
+        fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+            self.inner().drive(consumer)
+        }
+
+        fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+            self.inner().with_producer(callback)
+        }
     }
 }
 
-impl std::error::Error for SetPriorityNotFoundError {}
+#[cfg(feature = "rayon")]
+pub use self::rayon_impls::KeyedPriorityQueueParIter;
 
 #[cfg(test)]
 mod tests {
@@ -1002,7 +1401,7 @@ mod tests {
         let mut queue: KeyedPriorityQueue<&str, i32, BinaryHeap<i32>> =
             items.iter().cloned().collect();
 
-        while queue.len() > 0 {
+        while !queue.is_empty() {
             let (&key, &priority) = queue.peek().unwrap();
             let (key1, priority1) = queue.pop().unwrap();
             assert_eq!(key, key1);
@@ -1027,7 +1426,7 @@ mod tests {
             assert_eq!(real, priority);
         }
         let mut queue = queue;
-        while let Some(_) = queue.pop() {}
+        while queue.pop().is_some() {}
         for &(key, _) in items.iter() {
             assert_eq!(queue.get_priority(&key), None);
         }
@@ -1047,7 +1446,7 @@ mod tests {
             items.iter().cloned().collect();
         assert_eq!(
             queue.set_priority(&"HELLO", 64),
-            Err(super::SetPriorityNotFoundError::default())
+            Err(super::SetPriorityNotFoundError)
         );
         let old_priority = *queue.get_priority(&"fifth").unwrap();
         assert_eq!(queue.set_priority(&"fifth", old_priority + 10), Ok(1));
@@ -1151,6 +1550,14 @@ mod tests {
             }
         };
         assert_eq!(queue.len(), 6);
+        match queue.entry("Wool") {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.into_key(), "Wool");
+            }
+        };
+        assert_eq!(queue.len(), 6);
+        assert_eq!(queue.get_priority(&"Wool"), None);
         assert_eq!(queue.get_priority(&"Cotton"), Some(&10));
         match queue.entry("Cotton") {
             Entry::Occupied(entry) => {
@@ -1228,6 +1635,151 @@ mod tests {
         assert_eq!(map, other_map);
     }
 
+    #[test]
+    fn test_index() {
+        let items = [("first", 5i32), ("second", 4), ("third", 3)];
+
+        let queue: KeyedPriorityQueue<String, i32, WeakHeap<i32>> =
+            items.iter().map(|&(k, p)| (k.to_owned(), p)).collect();
+        for &(key, priority) in items.iter() {
+            assert_eq!(queue[key], priority);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_missing() {
+        let queue: KeyedPriorityQueue<i32, i32, BinaryHeap<i32>> = (0..5).map(|x| (x, x)).collect();
+        let _ = queue[&10];
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut queue: KeyedPriorityQueue<i32, i32, BinaryHeap<i32>> =
+            (0..10).map(|x| (x, x)).collect();
+        assert!(queue.get_mut(&20).is_none());
+        {
+            let mut guard = queue.get_mut(&3).unwrap();
+            assert_eq!(guard.get_key(), &3);
+            assert_eq!(*guard, 3);
+            *guard = 20;
+        }
+        assert_eq!(queue.get_priority(&3), Some(&20));
+        assert_eq!(queue.peek(), Some((&3, &20)));
+        {
+            let mut guard = queue.get_mut(&3).unwrap();
+            *guard -= 40;
+        }
+        assert_eq!(queue.len(), 10);
+        let mut res = Vec::with_capacity(10);
+        while let Some(item) = queue.pop() {
+            res.push(item);
+        }
+        let mut expected: Vec<_> = (0..10).filter(|&x| x != 3).map(|x| (x, x)).rev().collect();
+        expected.push((3, -20));
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut queue = KeyedPriorityQueue::<i32, i32, WeakHeap<i32>>::with_capacity(100);
+        assert!(queue.capacity() >= 100);
+        for x in 0..10 {
+            queue.push(x, x);
+        }
+        queue.shrink_to(20);
+        assert!(queue.capacity() >= 20 && queue.capacity() < 100);
+        queue.shrink_to_fit();
+        assert!(queue.capacity() >= 10 && queue.capacity() < 20);
+        assert_eq!(queue.try_reserve(100), Ok(()));
+        assert!(queue.capacity() >= 110);
+        assert_eq!(queue.try_reserve(usize::MAX), Err(super::TryReserveError));
+        assert_eq!(queue.len(), 10);
+        assert_eq!(queue.pop(), Some((9, 9)));
+    }
+
+    #[test]
+    fn test_shrink_with_surviving_high_index_key() {
+        fn check<THeap: crate::EditableHeap<i32, Alloc = allocator_api2::alloc::Global>>() {
+            let mut queue: KeyedPriorityQueue<i32, i32, THeap> = KeyedPriorityQueue::new();
+            for x in 0..1000 {
+                queue.push(x, x % 7);
+            }
+            for x in 0..998 {
+                queue.remove(&x);
+            }
+            queue.shrink_to_fit();
+            assert!(queue.capacity() >= 2 && queue.capacity() < 100);
+            assert_eq!(queue.validate(), Ok(()));
+            assert_eq!(queue.get_priority(&999), Some(&5));
+            queue.push(5000, 10);
+            assert_eq!(queue.validate(), Ok(()));
+            assert_eq!(queue.pop(), Some((5000, 10)));
+            assert_eq!(queue.pop(), Some((999, 5)));
+            assert_eq!(queue.pop(), Some((998, 4)));
+            assert_eq!(queue.pop(), None);
+        }
+        check::<BinaryHeap<i32>>();
+        check::<WeakHeap<i32>>();
+        check::<crate::editable_leftist_heap::LeftistHeap<i32>>();
+        check::<crate::editable_blocked_heap::BlockedHeap<i32>>();
+    }
+
+    #[test]
+    fn test_validate() {
+        use super::InvariantError;
+        use crate::heap_traits::{EditableHeap, HeapIndex};
+        use crate::mediator::MediatorIndex;
+
+        let mut weak: KeyedPriorityQueue<i32, i32, WeakHeap<i32>> =
+            (0..50).map(|x| (x, (x * 37) % 50)).collect();
+        let mut binary: KeyedPriorityQueue<i32, i32, BinaryHeap<i32>> =
+            (0..50).map(|x| (x, (x * 37) % 50)).collect();
+        for x in 0..25 {
+            weak.set_priority(&(x * 2), x * 3).unwrap();
+            binary.set_priority(&(x * 2), x * 3).unwrap();
+            weak.remove(&(x * 2 + 1));
+            binary.remove(&(x * 2 + 1));
+            assert_eq!(weak.validate(), Ok(()));
+            assert_eq!(binary.validate(), Ok(()));
+        }
+
+        // Corrupt heap order
+        *binary.inner.heap.priority_mut(HeapIndex(0)) = -1;
+        assert_eq!(
+            binary.validate(),
+            Err(InvariantError::HeapOrder {
+                parent: 0,
+                child: 1
+            })
+        );
+
+        // Corrupt mediator
+        *weak.inner.key_to_pos.get_index_mut(MediatorIndex(0)) = HeapIndex(100);
+        assert_eq!(
+            weak.validate(),
+            Err(InvariantError::IndexOutOfBounds {
+                key_index: 0,
+                heap_index: 100
+            })
+        );
+        let (_, other) = weak.inner.key_to_pos.get_index(MediatorIndex(2));
+        *weak.inner.key_to_pos.get_index_mut(MediatorIndex(0)) = other;
+        assert_eq!(
+            weak.validate(),
+            Err(InvariantError::BrokenBackReference {
+                key_index: 0,
+                heap_index: other.get(),
+                back_index: 2
+            })
+        );
+        weak.inner.heap.clear();
+        assert_eq!(
+            weak.validate(),
+            Err(InvariantError::LengthMismatch { keys: 25, heap: 0 })
+        );
+    }
+
     #[test]
     fn test_sync() {
         fn assert_sync<T: Sync>() {}
@@ -1274,4 +1826,206 @@ mod tests {
         }
         assert_eq!(&res, &[(1, 10), (5, 5), (4, 4), (2, 2), (0, 0)]);
     }
+
+    #[test]
+    fn test_panicking_comparison() {
+        use super::InvariantError;
+        use crate::heap_traits::{EditableHeap, HeapIndex};
+        use crate::mediator::MediatorIndex;
+        use std::cell::Cell;
+        use std::cmp::Ordering;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        thread_local! {
+            // Number of comparisons left before panic
+            static FUSE: Cell<Option<usize>> = const { Cell::new(None) };
+        }
+
+        #[derive(Debug, PartialEq, Eq)]
+        struct Bomb(i32);
+
+        impl PartialOrd for Bomb {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for Bomb {
+            fn cmp(&self, other: &Self) -> Ordering {
+                FUSE.with(|fuse| match fuse.get() {
+                    Some(0) => panic!("Comparison exploded"),
+                    Some(left) => fuse.set(Some(left - 1)),
+                    None => {}
+                });
+                self.0.cmp(&other.0)
+            }
+        }
+
+        fn check<THeap>()
+        where
+            THeap: EditableHeap<Bomb>,
+            THeap::Alloc: Default,
+        {
+            type Operation<THeap> = fn(&mut KeyedPriorityQueue<i32, Bomb, THeap>);
+            let operations: [Operation<THeap>; 6] = [
+                |queue| {
+                    queue.push(100, Bomb(1000));
+                },
+                |queue| {
+                    queue.pop();
+                },
+                |queue| {
+                    queue.remove(&3);
+                },
+                |queue| {
+                    queue.set_priority(&20, Bomb(-1)).unwrap();
+                },
+                |queue| {
+                    queue.set_priority(&7, Bomb(500)).unwrap();
+                },
+                |queue| {
+                    queue.meld((25..35).map(|x| (x, Bomb((x * 7) % 40))).collect());
+                },
+            ];
+            for operation in operations.iter() {
+                for fuse in 0..40 {
+                    let mut queue: KeyedPriorityQueue<i32, Bomb, THeap> = KeyedPriorityQueue::new();
+                    for x in 0..30 {
+                        queue.push(x, Bomb((x * 17) % 30));
+                    }
+                    FUSE.with(|f| f.set(Some(fuse)));
+                    let result = catch_unwind(AssertUnwindSafe(|| operation(&mut queue)));
+                    FUSE.with(|f| f.set(None));
+                    if result.is_ok() {
+                        assert_eq!(queue.validate(), Ok(()));
+                        continue;
+                    }
+                    // Order may be broken but indexes must stay consistent
+                    match queue.validate() {
+                        Ok(()) | Err(InvariantError::HeapOrder { .. }) => {}
+                        Err(err) => panic!("Queue corrupted after panic: {}", err),
+                    }
+                    let len = queue.len();
+                    let mut keys: Vec<i32> =
+                        std::iter::from_fn(|| queue.pop().map(|(key, _)| key)).collect();
+                    assert_eq!(keys.len(), len);
+                    keys.sort_unstable();
+                    keys.dedup();
+                    assert_eq!(keys.len(), len);
+                }
+            }
+        }
+
+        // Uses default implementation of meld which pushes items one by one
+        struct DefaultMeld(BinaryHeap<Bomb>);
+
+        impl EditableHeap<Bomb> for DefaultMeld {
+            type Alloc = allocator_api2::alloc::Global;
+
+            fn from_entries_vec_in(
+                heap_base: Vec<crate::heap_traits::HeapEntry<Bomb>>,
+                alloc: Self::Alloc,
+            ) -> Self {
+                DefaultMeld(BinaryHeap::from_entries_vec_in(heap_base, alloc))
+            }
+            fn reserve(&mut self, additional: usize) {
+                self.0.reserve(additional)
+            }
+            fn try_reserve(&mut self, additional: usize) -> Result<(), super::TryReserveError> {
+                self.0.try_reserve(additional)
+            }
+            fn capacity(&self) -> usize {
+                self.0.capacity()
+            }
+            fn shrink_to_fit(&mut self) {
+                self.0.shrink_to_fit()
+            }
+            fn shrink_to(&mut self, min_capacity: usize) {
+                self.0.shrink_to(min_capacity)
+            }
+            fn push<F: FnMut(MediatorIndex, HeapIndex)>(
+                &mut self,
+                outer_pos: MediatorIndex,
+                priority: Bomb,
+                change_handler: F,
+            ) {
+                self.0.push(outer_pos, priority, change_handler)
+            }
+            fn remove<F: FnMut(MediatorIndex, HeapIndex)>(
+                &mut self,
+                position: HeapIndex,
+                change_handler: F,
+            ) -> Option<(MediatorIndex, Bomb)> {
+                self.0.remove(position, change_handler)
+            }
+            fn data(&self) -> crate::heap_storage::HeapSlice<'_, Bomb> {
+                self.0.data()
+            }
+            fn change_outer_pos(
+                &mut self,
+                outer_pos: MediatorIndex,
+                position: HeapIndex,
+            ) -> MediatorIndex {
+                self.0.change_outer_pos(outer_pos, position)
+            }
+            fn change_priority<F: FnMut(MediatorIndex, HeapIndex)>(
+                &mut self,
+                position: HeapIndex,
+                updated: Bomb,
+                change_handler: F,
+            ) -> Bomb {
+                self.0.change_priority(position, updated, change_handler)
+            }
+            fn priority_mut(&mut self, position: HeapIndex) -> &mut Bomb {
+                self.0.priority_mut(position)
+            }
+            fn restore_order<F: FnMut(MediatorIndex, HeapIndex)>(
+                &mut self,
+                position: HeapIndex,
+                change_handler: F,
+            ) {
+                self.0.restore_order(position, change_handler)
+            }
+            fn most_prioritized_idx(&self) -> Option<(MediatorIndex, HeapIndex)> {
+                self.0.most_prioritized_idx()
+            }
+            fn clear(&mut self) {
+                self.0.clear()
+            }
+            fn validate(&self) -> Result<(), InvariantError> {
+                self.0.validate()
+            }
+        }
+
+        check::<BinaryHeap<Bomb>>();
+        check::<WeakHeap<Bomb>>();
+        check::<crate::LeftistHeap<Bomb>>();
+        check::<crate::BlockedHeap<Bomb, 2>>();
+        check::<DefaultMeld>();
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_rayon() {
+        use rayon::prelude::*;
+        use std::collections::HashMap;
+
+        let items: Vec<(u32, u32)> = (0..10_000u32).map(|x| (x % 3001, x * 7 % 101)).collect();
+        let sequential: KeyedPriorityQueue<u32, u32, WeakHeap<u32>> =
+            items.iter().cloned().collect();
+        let parallel: KeyedPriorityQueue<u32, u32, WeakHeap<u32>> =
+            items.par_iter().cloned().collect();
+        assert_eq!(parallel.validate(), Ok(()));
+        let expected: HashMap<_, _> = sequential.iter().collect();
+        let actual: HashMap<_, _> = parallel.par_iter().collect();
+        assert_eq!(actual, expected);
+
+        let mut extended: KeyedPriorityQueue<u32, u32, BinaryHeap<u32>> =
+            items[..5000].iter().cloned().collect();
+        extended.par_extend(items[5000..].par_iter().cloned());
+        assert_eq!(extended.validate(), Ok(()));
+        let actual: HashMap<_, _> = extended.iter().collect();
+        assert_eq!(actual, expected);
+        assert_eq!(extended.par_iter().len(), extended.len());
+    }
 }
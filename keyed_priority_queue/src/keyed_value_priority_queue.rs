@@ -0,0 +1,1579 @@
+use crate::errors::{InvariantError, SetPriorityNotFoundError, TryReserveError};
+use crate::heap_storage::HeapSliceIter;
+use crate::heap_traits::{EditableHeap, HeapEntry, HeapIndex};
+use crate::make_hash;
+use crate::mediator::{
+    Mediator, MediatorEntry, MediatorIndex, OccupiedEntry as MediatorOccupiedEntry,
+    VacantEntry as MediatorVacantEntry,
+};
+use allocator_api2::alloc::{Allocator, Global};
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut};
+
+/// A priority queue where every key owns a value which is separate from its priority.
+///
+/// It works like [`KeyedPriorityQueue`] but payload doesn't need to be stored
+/// in a side `HashMap` or packed into key or priority.
+/// Values are kept inline next to keys so lookup of value costs the same single hash lookup.
+/// Value may be changed freely because it doesn't affect order of queue.
+/// [`KeyedPriorityQueue`] is this queue with `()` values.
+///
+/// Bigger `TPriority` values will have more priority.
+///
+/// [`KeyedPriorityQueue`]: struct.KeyedPriorityQueue.html
+///
+/// # Examples
+///
+/// ```
+/// use keyed_priority_queue::{KeyedValueBinaryPriorityQueue, ValueEntry};
+///
+/// let mut jobs = KeyedValueBinaryPriorityQueue::new();
+/// jobs.push("backup", vec!["/home"], 1);
+/// jobs.push("deploy", vec!["server"], 5);
+///
+/// // Values are changed without touching the priorities
+/// jobs.get_value_mut(&"backup").unwrap().push("/etc");
+/// assert_eq!(jobs.get_value(&"backup"), Some(&vec!["/home", "/etc"]));
+///
+/// match jobs.entry("deploy") {
+///     ValueEntry::Occupied(mut entry) => {
+///         entry.get_value_mut().push("client");
+///         entry.set_priority(0);
+///     }
+///     ValueEntry::Vacant(_) => unreachable!(),
+/// }
+///
+/// assert_eq!(jobs.pop(), Some(("backup", vec!["/home", "/etc"], 1)));
+/// assert_eq!(jobs.pop(), Some(("deploy", vec!["server", "client"], 0)));
+/// assert_eq!(jobs.pop(), None);
+/// ```
+#[derive(Clone)]
+pub struct KeyedValuePriorityQueue<TKey, TValue, TPriority, THeap, S = RandomState>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    pub(crate) heap: THeap,
+    pub(crate) key_to_pos: Mediator<TKey, S, THeap::Alloc, TValue>,
+    _phantom: std::marker::PhantomData<TPriority>,
+}
+
+impl<TKey, TValue, TPriority, THeap>
+    KeyedValuePriorityQueue<TKey, TValue, TPriority, THeap, RandomState>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    THeap::Alloc: Default,
+{
+    /// Creates an empty queue
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedValueBinaryPriorityQueue;
+    /// let mut queue = KeyedValueBinaryPriorityQueue::new();
+    /// queue.push("Key", "Value", 4);
+    /// ```
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_capacity_and_hasher(0, RandomState::default())
+    }
+
+    /// Creates an empty queue with allocated memory enough
+    /// to keep `capacity` elements without reallocation.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedValueWeakPriorityQueue;
+    /// let mut queue = KeyedValueWeakPriorityQueue::with_capacity(10);
+    /// queue.push("Key", "Value", 4);
+    /// ```
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::default())
+    }
+}
+
+impl<TKey, TValue, TPriority, THeap>
+    KeyedValuePriorityQueue<TKey, TValue, TPriority, THeap, RandomState>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+{
+    /// Creates an empty queue which takes memory from allocator of heap.
+    /// Heap, keys and values are stored in memory of `alloc`.
+    #[inline]
+    pub fn new_in(alloc: THeap::Alloc) -> Self {
+        Self::with_capacity_and_hasher_in(0, RandomState::default(), alloc)
+    }
+
+    /// Creates an empty queue which takes memory from allocator of heap
+    /// with allocated memory enough to keep `capacity` elements without reallocation.
+    #[inline]
+    pub fn with_capacity_in(capacity: usize, alloc: THeap::Alloc) -> Self {
+        Self::with_capacity_and_hasher_in(capacity, RandomState::default(), alloc)
+    }
+}
+
+impl<TKey, TValue, TPriority, THeap, S> KeyedValuePriorityQueue<TKey, TValue, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    THeap::Alloc: Default,
+    S: BuildHasher,
+{
+    /// Creates an empty queue with specific Hasher
+    #[inline]
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::with_capacity_and_hasher(0, hasher)
+    }
+
+    /// Creates an empty queue with allocated memory enough
+    /// to keep `capacity` elements without reallocation.
+    /// Also useful when Hasher cannot be defaulted.
+    #[inline]
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        Self::with_capacity_and_hasher_in(capacity, hasher, THeap::Alloc::default())
+    }
+}
+
+impl<TKey, TValue, TPriority, THeap, S> KeyedValuePriorityQueue<TKey, TValue, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    /// Creates an empty queue with specific Hasher
+    /// which takes memory from allocator of heap.
+    #[inline]
+    pub fn with_hasher_in(hasher: S, alloc: THeap::Alloc) -> Self {
+        Self::with_capacity_and_hasher_in(0, hasher, alloc)
+    }
+
+    /// Creates an empty queue with specific Hasher
+    /// which takes memory from allocator of heap
+    /// with allocated memory enough to keep `capacity` elements without reallocation.
+    pub fn with_capacity_and_hasher_in(capacity: usize, hasher: S, alloc: THeap::Alloc) -> Self {
+        let mut heap = THeap::from_entries_vec_in(Vec::new(), alloc.clone());
+        heap.reserve(capacity);
+        Self {
+            heap,
+            key_to_pos: Mediator::with_capacity_and_hasher_in(capacity, hasher, alloc),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns reference to the allocator which provides memory for queue.
+    #[inline]
+    pub fn allocator(&self) -> &THeap::Alloc {
+        self.key_to_pos.allocator()
+    }
+
+    /// Reserves space for at least `additional` new elements.
+    ///
+    /// ### Panics
+    ///
+    /// Panics if the new capacity overflows `usize`.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.heap.reserve(additional);
+        self.key_to_pos.reserve(additional);
+    }
+
+    /// Tries to reserve space for at least `additional` new elements.
+    /// Returns [`TryReserveError`] instead of aborting if allocation fails
+    /// or the new capacity overflows `usize`.
+    ///
+    /// [`TryReserveError`]: struct.TryReserveError.html
+    #[inline]
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.heap.try_reserve(additional)?;
+        self.key_to_pos.try_reserve(additional)
+    }
+
+    /// Returns the number of elements queue can hold without reallocation.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        std::cmp::min(self.heap.capacity(), self.key_to_pos.capacity())
+    }
+
+    /// Shrinks the capacity of the queue as much as possible.
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    /// Shrinks the capacity of the queue with a lower bound.
+    ///
+    /// The capacity will remain at least as large as both the length
+    /// and the supplied value.
+    #[inline]
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        // Borrow checker treats borrowing a field as borrowing whole structure
+        // so we need to get references to fields to borrow them individually.
+        let heap = &mut self.heap;
+        self.key_to_pos.shrink_to(min_capacity, |index, heap_idx| {
+            heap.change_outer_pos(index, heap_idx);
+        });
+        heap.shrink_to(min_capacity);
+    }
+
+    /// Adds new element to queue if missing key
+    /// or replaces its value and priority if key exists.
+    /// In second case doesn't replace key and returns old value and priority.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedValueWeakPriorityQueue;
+    /// let mut queue = KeyedValueWeakPriorityQueue::new();
+    /// assert_eq!(queue.push("First", 'a', 5), None);
+    /// assert_eq!(queue.push("First", 'b', 10), Some(('a', 5)));
+    /// assert_eq!(queue.peek(), Some((&"First", &'b', &10)));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Average complexity is ***O(log n)***
+    pub fn push(
+        &mut self,
+        key: TKey,
+        value: TValue,
+        priority: TPriority,
+    ) -> Option<(TValue, TPriority)> {
+        match self.entry(key) {
+            ValueEntry::Vacant(entry) => {
+                entry.insert(value, priority);
+                None
+            }
+            ValueEntry::Occupied(mut entry) => {
+                let old_value = std::mem::replace(entry.get_value_mut(), value);
+                Some((old_value, entry.set_priority(priority)))
+            }
+        }
+    }
+
+    /// Remove and return item with the maximal priority along with its key and value.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedValueBinaryPriorityQueue;
+    /// let mut queue = KeyedValueBinaryPriorityQueue::new();
+    /// queue.push(1, "one", 1);
+    /// queue.push(2, "two", 2);
+    /// assert_eq!(queue.pop(), Some((2, "two", 2)));
+    /// assert_eq!(queue.pop(), Some((1, "one", 1)));
+    /// assert_eq!(queue.pop(), None);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Cost of pop is always ***O(log n)***
+    pub fn pop(&mut self) -> Option<(TKey, TValue, TPriority)> {
+        let (to_remove, _) = self.heap.most_prioritized_idx()?;
+        Some(remove_from_both(
+            &mut self.heap,
+            &mut self.key_to_pos,
+            to_remove,
+        ))
+    }
+
+    /// Get references to the key, value and priority of item with the maximal priority.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    pub fn peek(&self) -> Option<(&TKey, &TValue, &TPriority)> {
+        let (first_idx, heap_idx) = self.heap.most_prioritized_idx()?;
+        let (key, _) = self.key_to_pos.get_index(first_idx);
+        let value = self.key_to_pos.get_value(first_idx);
+        Some((key, value, self.heap.data().priority(heap_idx.get())))
+    }
+
+    /// Gets the given key's corresponding entry in the queue for in-place manipulation.
+    ///
+    /// ## Time complexity
+    /// Amortized ***O(1)***, uses only one hash lookup
+    pub fn entry(&mut self, key: TKey) -> ValueEntry<'_, TKey, TValue, TPriority, THeap, S> {
+        let hash = make_hash(self.key_to_pos.hasher(), &key);
+        self.entry_hashed(hash, key)
+    }
+
+    // Same as `entry` but with hash of the key calculated beforehand
+    pub(crate) fn entry_hashed(
+        &mut self,
+        hash: u64,
+        key: TKey,
+    ) -> ValueEntry<'_, TKey, TValue, TPriority, THeap, S> {
+        // Borrow checker treats borrowing a field as borrowing whole structure
+        // so we need to get references to fields to borrow them individually.
+        let key_to_pos = &mut self.key_to_pos;
+        let heap = &mut self.heap;
+
+        match key_to_pos.entry_hashed(hash, key) {
+            MediatorEntry::Vacant(internal_entry) => ValueEntry::Vacant(VacantValueEntry {
+                internal_entry,
+                heap,
+                _phantom: std::marker::PhantomData,
+            }),
+            MediatorEntry::Occupied(internal_entry) => ValueEntry::Occupied(OccupiedValueEntry {
+                internal_entry,
+                heap,
+                _phantom: std::marker::PhantomData,
+            }),
+        }
+    }
+
+    /// Get reference to the priority by key.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(1)*** in average (limited by hash map key lookup).
+    pub fn get_priority<Q>(&self, key: &Q) -> Option<&TPriority>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let heap_idx = self.key_to_pos.get(key)?;
+        Some(self.heap.data().priority(heap_idx.get()))
+    }
+
+    /// Get guarded mutable reference to the priority by key.
+    /// Queue reorders the item when returned [`PriorityGuard`] is dropped.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedValueBinaryPriorityQueue;
+    /// let mut queue = KeyedValueBinaryPriorityQueue::new();
+    /// queue.push("first", 'a', 0);
+    /// queue.push("second", 'b', 1);
+    /// if let Some(mut priority) = queue.get_mut(&"first") {
+    ///     *priority += 10;
+    /// }
+    /// assert_eq!(queue.pop(), Some(("first", 'a', 10)));
+    /// assert!(queue.get_mut(&"Missing").is_none());
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Lookup is ***O(1)*** in average (limited by hash map key lookup),
+    /// dropping of guard costs up to ***O(log n)***.
+    ///
+    /// [`PriorityGuard`]: struct.PriorityGuard.html
+    pub fn get_mut<Q>(
+        &mut self,
+        key: &Q,
+    ) -> Option<PriorityGuard<'_, TKey, TPriority, THeap, S, TValue>>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let heap_idx = self.key_to_pos.get(key)?;
+        Some(PriorityGuard {
+            heap: &mut self.heap,
+            key_to_pos: &mut self.key_to_pos,
+            heap_idx,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+
+    /// Get reference to the value by key.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedValueBinaryPriorityQueue;
+    /// let mut queue = KeyedValueBinaryPriorityQueue::new();
+    /// queue.push("first", 'a', 0);
+    /// assert_eq!(queue.get_value(&"first"), Some(&'a'));
+    /// assert_eq!(queue.get_value(&"Missing"), None);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(1)*** in average (limited by hash map key lookup).
+    pub fn get_value<Q>(&self, key: &Q) -> Option<&TValue>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (index, _, _) = self.key_to_pos.get_full(key)?;
+        Some(self.key_to_pos.get_value(index))
+    }
+
+    /// Get mutable reference to the value by key.
+    /// Changing of value doesn't affect order of queue.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedValueWeakPriorityQueue;
+    /// let mut queue = KeyedValueWeakPriorityQueue::new();
+    /// queue.push("first", 1, 0);
+    /// *queue.get_value_mut(&"first").unwrap() += 10;
+    /// assert_eq!(queue.pop(), Some(("first", 11, 0)));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(1)*** in average (limited by hash map key lookup).
+    pub fn get_value_mut<Q>(&mut self, key: &Q) -> Option<&mut TValue>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (index, _, _) = self.key_to_pos.get_full(key)?;
+        Some(self.key_to_pos.get_value_mut(index))
+    }
+
+    /// Set new priority for existing key and reorder the queue.
+    /// Returns old priority if succeeds or [`SetPriorityNotFoundError`].
+    ///
+    /// ### Time complexity
+    ///
+    /// In best case ***O(1)***, in average costs ***O(log n)***.
+    ///
+    /// [`SetPriorityNotFoundError`]: struct.SetPriorityNotFoundError.html
+    pub fn set_priority<Q>(
+        &mut self,
+        key: &Q,
+        priority: TPriority,
+    ) -> Result<TPriority, SetPriorityNotFoundError>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let heap_idx = self
+            .key_to_pos
+            .get(key)
+            .ok_or(SetPriorityNotFoundError {})?;
+        let key_to_pos = &mut self.key_to_pos;
+        Ok(self
+            .heap
+            .change_priority(heap_idx, priority, |index, heap_idx| {
+                *key_to_pos.get_index_mut(index) = heap_idx
+            }))
+    }
+
+    /// Allow removing item by key.
+    /// Returns value and priority if succeeds.
+    ///
+    /// ### Time complexity
+    ///
+    /// On average the function will require ***O(log n)*** operations.
+    #[inline]
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<(TValue, TPriority)>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (_, value, priority) = self.remove_entry(key)?;
+        Some((value, priority))
+    }
+
+    /// Allow removing item by key.
+    /// Returns key, value and priority if succeeds.
+    ///
+    /// ### Time complexity
+    ///
+    /// On average the function will require ***O(log n)*** operations.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(TKey, TValue, TPriority)>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (index, _, _) = self.key_to_pos.get_full(key)?;
+        Some(remove_from_both(
+            &mut self.heap,
+            &mut self.key_to_pos,
+            index,
+        ))
+    }
+
+    /// Get the number of elements in queue.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn len(&self) -> usize {
+        debug_assert_eq!(self.key_to_pos.len(), self.heap.data().len());
+        self.key_to_pos.len()
+    }
+
+    /// Returns true if queue is empty.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        debug_assert_eq!(self.heap.data().is_empty(), self.key_to_pos.is_empty());
+        self.key_to_pos.is_empty()
+    }
+
+    /// Make the queue empty.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(n)***
+    #[inline]
+    pub fn clear(&mut self) {
+        self.heap.clear();
+        self.key_to_pos.clear();
+    }
+
+    /// Checks internal consistency of the queue.
+    /// See [`KeyedPriorityQueue::validate`] for details.
+    ///
+    /// [`KeyedPriorityQueue::validate`]: struct.KeyedPriorityQueue.html#method.validate
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(n)***
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        let heap_len = self.heap.data().len();
+        let keys_len = self.key_to_pos.len();
+        if heap_len != keys_len {
+            return Err(InvariantError::LengthMismatch {
+                keys: keys_len,
+                heap: heap_len,
+            });
+        }
+        let data = self.heap.data();
+        for (outer_index, heap_idx) in self.key_to_pos.indexes() {
+            let (key_index, heap_index) = (outer_index.get(), heap_idx.get());
+            let (outer_pos, _) = data
+                .get(heap_index)
+                .ok_or(InvariantError::IndexOutOfBounds {
+                    key_index,
+                    heap_index,
+                })?;
+            if outer_pos != outer_index {
+                return Err(InvariantError::BrokenBackReference {
+                    key_index,
+                    heap_index,
+                    back_index: outer_pos.get(),
+                });
+            }
+        }
+        self.heap.validate()
+    }
+
+    /// Create readonly borrowing iterator over keys, values and priorities in arbitrary order.
+    ///
+    /// ### Time complexity
+    ///
+    /// Iterating over whole queue is ***O(n)***
+    pub fn iter(
+        &self,
+    ) -> KeyedValuePriorityQueueBorrowIter<'_, TKey, TValue, TPriority, S, THeap::Alloc> {
+        KeyedValuePriorityQueueBorrowIter {
+            key_to_pos: &self.key_to_pos,
+            heap_iterator: self.heap.data().iter(),
+        }
+    }
+
+    /// Moves all items of `other` queue into this one.
+    /// If key is present in both queues, value and priority from `other` are kept.
+    ///
+    /// With [`LeftistHeap`] backend heaps are merged without sifting items one by one.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedValueBinaryPriorityQueue;
+    /// let mut queue = KeyedValueBinaryPriorityQueue::new();
+    /// queue.push(1, "one", 1);
+    /// queue.push(2, "two", 2);
+    /// let mut other = KeyedValueBinaryPriorityQueue::new();
+    /// other.push(2, "second", 0);
+    /// other.push(3, "three", 3);
+    /// queue.meld(other);
+    /// assert_eq!(queue.pop(), Some((3, "three", 3)));
+    /// assert_eq!(queue.pop(), Some((1, "one", 1)));
+    /// assert_eq!(queue.pop(), Some((2, "second", 0)));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Items of `other` are moved in ***O(m)***, plus ***O(log n)*** for every key
+    /// present in both queues. Merging of heaps costs ***O(log(n + m))***
+    /// for [`LeftistHeap`] and ***O(m log(n + m))*** for other heaps.
+    ///
+    /// [`LeftistHeap`]: struct.LeftistHeap.html
+    pub fn meld(&mut self, other: Self) {
+        let KeyedValuePriorityQueue {
+            heap: mut other_heap,
+            key_to_pos: other_keys,
+            ..
+        } = other;
+        let key_to_pos = &mut self.key_to_pos;
+        let heap = &mut self.heap;
+
+        // Remove shared keys first so panic in comparison leaves this queue consistent
+        for (index, _) in other_keys.indexes() {
+            let (key, _) = other_keys.get_index(index);
+            if let Some((position, _, _)) = key_to_pos.get_full(key) {
+                remove_from_both(heap, key_to_pos, position);
+            }
+        }
+
+        key_to_pos.reserve(other_keys.len());
+        let mut new_indexes = Vec::with_capacity(other_keys.len());
+        for (key, value, heap_idx) in other_keys.into_entries() {
+            // Real position is reported by heap during meld
+            let outer_pos = match key_to_pos.entry(key) {
+                MediatorEntry::Vacant(entry) => entry.insert(heap_idx, value).1,
+                MediatorEntry::Occupied(_) => unreachable!("Shared keys are removed"),
+            };
+            other_heap.change_outer_pos(outer_pos, heap_idx);
+            new_indexes.push(outer_pos);
+        }
+
+        let mut guard = MeldGuard {
+            heap,
+            key_to_pos,
+            new_indexes,
+            _phantom: std::marker::PhantomData,
+        };
+        let key_to_pos = &mut guard.key_to_pos;
+        guard.heap.meld(other_heap, |index, heap_idx| {
+            *key_to_pos.get_index_mut(index) = heap_idx
+        });
+        guard.new_indexes.clear();
+    }
+
+    // Builds queue from items placed in heap base in arbitrary order
+    fn from_unordered(
+        heap_base: Vec<HeapEntry<TPriority>>,
+        mut key_to_pos: Mediator<TKey, S, THeap::Alloc, TValue>,
+    ) -> Self {
+        let heap = THeap::from_entries_vec_in(heap_base, key_to_pos.allocator().clone());
+
+        for (i, pos) in heap
+            .data()
+            .iter()
+            .map(|(outer_pos, _)| outer_pos)
+            .enumerate()
+        {
+            let heap_idx = key_to_pos.get_index_mut(pos);
+            *heap_idx = HeapIndex::new(i);
+        }
+        Self {
+            heap,
+            key_to_pos,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+// Repairs mediator if meld of heaps panics.
+// Heap may lose items of other heap which it didn't take yet,
+// e.g. default `EditableHeap::meld` pushes them one by one,
+// so their keys are removed and positions of all kept items are restored.
+struct MeldGuard<'a, TKey, TValue, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    heap: &'a mut THeap,
+    key_to_pos: &'a mut Mediator<TKey, S, THeap::Alloc, TValue>,
+    // Indexes of keys from other queue, empty if meld succeeded
+    new_indexes: Vec<MediatorIndex>,
+    _phantom: std::marker::PhantomData<TPriority>,
+}
+
+impl<'a, TKey, TValue, TPriority, THeap, S> Drop
+    for MeldGuard<'a, TKey, TValue, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        if self.new_indexes.is_empty() {
+            return;
+        }
+        let data = self.heap.data();
+        for position in 0..data.len() {
+            *self.key_to_pos.get_index_mut(data.outer_pos(position)) = HeapIndex::new(position);
+        }
+        for &index in self.new_indexes.iter() {
+            let (_, heap_idx) = self.key_to_pos.get_index(index);
+            let position = heap_idx.get();
+            if position >= data.len() || data.outer_pos(position) != index {
+                self.key_to_pos.remove_index(index);
+            }
+        }
+    }
+}
+
+// Removes entry from both mediator and heap.
+// Mediator is updated first because heap removal compares priorities
+// and they may panic, which must not leave dangling indexes in mediator.
+fn remove_from_both<TKey, TValue, TPriority, THeap, S>(
+    heap: &mut THeap,
+    key_to_pos: &mut Mediator<TKey, S, THeap::Alloc, TValue>,
+    position: MediatorIndex,
+) -> (TKey, TValue, TPriority)
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    // Other keys keep their slots so heap needs no fix-ups of outer indexes
+    let (removed_key, removed_value, heap_to_rem) = key_to_pos.remove_index(position);
+    let (_, priority) = heap
+        .remove(heap_to_rem, |index, heap_idx| {
+            *key_to_pos.get_index_mut(index) = heap_idx
+        })
+        .expect("Checked by key_to_pos");
+
+    (removed_key, removed_value, priority)
+}
+
+// Puts item to the end of heap base without restoring heap order
+// or replaces value and priority if key is already present
+fn insert_unordered<TKey, TValue, TPriority, S, A>(
+    heap_base: &mut Vec<HeapEntry<TPriority>>,
+    entry: MediatorEntry<'_, TKey, S, A, TValue>,
+    value: TValue,
+    priority: TPriority,
+) where
+    TKey: Hash + Eq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    match entry {
+        MediatorEntry::Vacant(entry) => {
+            let (_, outer_pos) = entry.insert(HeapIndex::new(heap_base.len()), value);
+            heap_base.push(HeapEntry {
+                outer_pos,
+                priority,
+            });
+        }
+        MediatorEntry::Occupied(mut entry) => {
+            *entry.get_value_mut() = value;
+            let heap_pos = entry.get_heap_idx().get();
+            heap_base[heap_pos].priority = priority;
+        }
+    }
+}
+
+/// A view into a single entry in a [`KeyedValuePriorityQueue`],
+/// which may either be vacant or occupied.
+///
+/// This `enum` is constructed from the [`entry`] method on [`KeyedValuePriorityQueue`].
+///
+/// [`KeyedValuePriorityQueue`]: struct.KeyedValuePriorityQueue.html
+/// [`entry`]: struct.KeyedValuePriorityQueue.html#method.entry
+pub enum ValueEntry<'a, TKey, TValue, TPriority, THeap, S = RandomState>
+where
+    TKey: 'a + Eq + Hash,
+    TPriority: 'a + Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    /// An occupied entry.
+    Occupied(OccupiedValueEntry<'a, TKey, TValue, TPriority, THeap, S>),
+
+    /// A vacant entry.
+    Vacant(VacantValueEntry<'a, TKey, TValue, TPriority, THeap, S>),
+}
+
+/// A view into an occupied entry in a [`KeyedValuePriorityQueue`].
+/// It is part of the [`ValueEntry`] enum.
+///
+/// [`ValueEntry`]: enum.ValueEntry.html
+/// [`KeyedValuePriorityQueue`]: struct.KeyedValuePriorityQueue.html
+pub struct OccupiedValueEntry<'a, TKey, TValue, TPriority, THeap, S = RandomState>
+where
+    TKey: 'a + Eq + Hash,
+    TPriority: 'a + Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    internal_entry: MediatorOccupiedEntry<'a, TKey, S, THeap::Alloc, TValue>,
+    heap: &'a mut THeap,
+    _phantom: std::marker::PhantomData<TPriority>,
+}
+
+impl<'a, TKey, TValue, TPriority, THeap, S>
+    OccupiedValueEntry<'a, TKey, TValue, TPriority, THeap, S>
+where
+    TKey: 'a + Eq + Hash,
+    TPriority: 'a + Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    /// Get the reference to actual key
+    ///
+    /// ## Time complexity
+    /// ***O(1)*** instant access
+    #[inline]
+    pub fn get_key(&self) -> &TKey {
+        self.internal_entry.get_key()
+    }
+
+    /// Get the reference to value of the key
+    ///
+    /// ## Time complexity
+    /// ***O(1)*** instant access
+    #[inline]
+    pub fn get_value(&self) -> &TValue {
+        self.internal_entry.get_value()
+    }
+
+    /// Get the mutable reference to value of the key
+    ///
+    /// ## Time complexity
+    /// ***O(1)*** instant access
+    #[inline]
+    pub fn get_value_mut(&mut self) -> &mut TValue {
+        self.internal_entry.get_value_mut()
+    }
+
+    /// Converts entry into mutable reference to value
+    /// which lives as long as borrow of the queue.
+    ///
+    /// ## Time complexity
+    /// ***O(1)*** instant access
+    #[inline]
+    pub fn into_value_mut(self) -> &'a mut TValue {
+        let (key_to_pos, index) = self.internal_entry.into_mediator();
+        key_to_pos.get_value_mut(index)
+    }
+
+    /// Get the reference to priority of the key
+    ///
+    /// ## Time complexity
+    /// ***O(1)*** instant access
+    #[inline]
+    pub fn get_priority(&self) -> &TPriority {
+        let heap_idx = self.internal_entry.get_heap_idx();
+        self.heap.data().priority(heap_idx.get())
+    }
+
+    /// Changes priority of key and returns old one
+    ///
+    /// ## Time complexity
+    /// Up to ***O(log n)*** operations in worst case
+    /// ***O(1)*** in best case
+    #[inline]
+    pub fn set_priority(self, priority: TPriority) -> TPriority {
+        let heap_idx = self.internal_entry.get_heap_idx();
+
+        let heap = self.heap;
+        let (key_to_pos, _) = self.internal_entry.into_mediator();
+
+        heap.change_priority(heap_idx, priority, |index, heap_idx| {
+            *key_to_pos.get_index_mut(index) = heap_idx;
+        })
+    }
+
+    /// Remove entry from queue
+    ///
+    /// ## Time complexity
+    /// Up to ***O(log n)*** operations
+    pub fn remove(self) -> (TKey, TValue, TPriority) {
+        let (key_to_pos, position) = self.internal_entry.into_mediator();
+        remove_from_both(self.heap, key_to_pos, position)
+    }
+}
+
+/// A view into a vacant entry in a [`KeyedValuePriorityQueue`].
+/// It is part of the [`ValueEntry`] enum.
+///
+/// [`ValueEntry`]: enum.ValueEntry.html
+/// [`KeyedValuePriorityQueue`]: struct.KeyedValuePriorityQueue.html
+pub struct VacantValueEntry<'a, TKey, TValue, TPriority, THeap, S = RandomState>
+where
+    TKey: 'a + Eq + Hash,
+    TPriority: 'a + Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    internal_entry: MediatorVacantEntry<'a, TKey, S, THeap::Alloc, TValue>,
+    heap: &'a mut THeap,
+    _phantom: std::marker::PhantomData<TPriority>,
+}
+
+impl<'a, TKey, TValue, TPriority, THeap, S> VacantValueEntry<'a, TKey, TValue, TPriority, THeap, S>
+where
+    TKey: 'a + Eq + Hash,
+    TPriority: 'a + Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    /// Insert value and priority of key to queue
+    /// and return mutable reference to inserted value
+    ///
+    /// ## Time complexity
+    /// Up to ***O(log n)*** operations
+    #[inline]
+    pub fn insert(self, value: TValue, priority: TPriority) -> &'a mut TValue {
+        let heap = self.heap;
+        let (key_to_pos, mediator_index) = self
+            .internal_entry
+            .insert(HeapIndex::new(heap.data().len()), value);
+        heap.push(mediator_index, priority, |index, val| {
+            *key_to_pos.get_index_mut(index) = val
+        });
+        key_to_pos.get_value_mut(mediator_index)
+    }
+
+    // Puts key in place of item with the maximal priority and returns that item.
+    // Uses the lookup already done by entry and sifts only once.
+    // Queue must not be empty.
+    pub(crate) fn replace_top(
+        self,
+        value: TValue,
+        priority: TPriority,
+    ) -> (TKey, TValue, TPriority) {
+        let heap = self.heap;
+        let (top_index, heap_idx) = heap
+            .most_prioritized_idx()
+            .expect("Queue must not be empty");
+        let (key_to_pos, old_key, old_value) = self.internal_entry.replace_index(top_index, value);
+        let old_priority = heap.change_priority(heap_idx, priority, |index, heap_idx| {
+            *key_to_pos.get_index_mut(index) = heap_idx;
+        });
+        (old_key, old_value, old_priority)
+    }
+
+    /// Get the reference to actual key
+    ///
+    /// ## Time complexity
+    /// ***O(1)*** instant access
+    #[inline]
+    pub fn get_key(&self) -> &TKey {
+        self.internal_entry.get_key()
+    }
+
+    /// Take ownership of the key
+    ///
+    /// ## Time complexity
+    /// ***O(1)*** instant access
+    #[inline]
+    pub fn into_key(self) -> TKey {
+        self.internal_entry.into_key()
+    }
+}
+
+/// A guard which gives mutable access to the priority of item in queue.
+/// Item is moved to its new place in queue when guard is dropped.
+///
+/// This `struct` is created by the [`get_mut`] method on [`KeyedPriorityQueue`]
+/// and [`KeyedValuePriorityQueue`].
+///
+/// [`KeyedPriorityQueue`]: struct.KeyedPriorityQueue.html
+/// [`KeyedValuePriorityQueue`]: struct.KeyedValuePriorityQueue.html
+/// [`get_mut`]: struct.KeyedPriorityQueue.html#method.get_mut
+pub struct PriorityGuard<'a, TKey, TPriority, THeap, S = RandomState, TValue = ()>
+where
+    TKey: 'a + Eq + Hash,
+    TPriority: 'a + Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    heap: &'a mut THeap,
+    key_to_pos: &'a mut Mediator<TKey, S, THeap::Alloc, TValue>,
+    heap_idx: HeapIndex,
+    _phantom: std::marker::PhantomData<TPriority>,
+}
+
+impl<'a, TKey, TPriority, THeap, S, TValue> PriorityGuard<'a, TKey, TPriority, THeap, S, TValue>
+where
+    TKey: 'a + Eq + Hash,
+    TPriority: 'a + Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    /// Get the reference to actual key
+    ///
+    /// ## Time complexity
+    /// ***O(1)*** instant access
+    #[inline]
+    pub fn get_key(&self) -> &TKey {
+        let outer_pos = self.heap.data().outer_pos(self.heap_idx.get());
+        let (key, _) = self.key_to_pos.get_index(outer_pos);
+        key
+    }
+}
+
+impl<'a, TKey, TPriority, THeap, S, TValue> Deref
+    for PriorityGuard<'a, TKey, TPriority, THeap, S, TValue>
+where
+    TKey: 'a + Eq + Hash,
+    TPriority: 'a + Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    type Target = TPriority;
+
+    #[inline]
+    fn deref(&self) -> &TPriority {
+        self.heap.data().priority(self.heap_idx.get())
+    }
+}
+
+impl<'a, TKey, TPriority, THeap, S, TValue> DerefMut
+    for PriorityGuard<'a, TKey, TPriority, THeap, S, TValue>
+where
+    TKey: 'a + Eq + Hash,
+    TPriority: 'a + Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut TPriority {
+        self.heap.priority_mut(self.heap_idx)
+    }
+}
+
+impl<'a, TKey, TPriority, THeap, S, TValue> Drop
+    for PriorityGuard<'a, TKey, TPriority, THeap, S, TValue>
+where
+    TKey: 'a + Eq + Hash,
+    TPriority: 'a + Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        let key_to_pos = &mut self.key_to_pos;
+        self.heap.restore_order(self.heap_idx, |index, heap_idx| {
+            *key_to_pos.get_index_mut(index) = heap_idx
+        });
+    }
+}
+
+impl<TKey, TValue, TPriority, THeap, S> Debug
+    for KeyedValuePriorityQueue<TKey, TValue, TPriority, THeap, S>
+where
+    TKey: Hash + Eq + Debug,
+    TValue: Debug,
+    TPriority: Ord + Debug,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "[")?;
+        for entry in self.iter() {
+            write!(f, "{:?}", entry)?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl<TKey, TValue, TPriority, THeap, S> Default
+    for KeyedValuePriorityQueue<TKey, TValue, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    THeap::Alloc: Default,
+    S: BuildHasher + Default,
+{
+    #[inline]
+    fn default() -> Self {
+        Self::with_capacity_and_hasher(0, S::default())
+    }
+}
+
+impl<TKey, TValue, TPriority, THeap, S> FromIterator<(TKey, TValue, TPriority)>
+    for KeyedValuePriorityQueue<TKey, TValue, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    THeap::Alloc: Default,
+    S: BuildHasher + Default,
+{
+    /// Allows building queue from iterator using `collect()`.
+    /// Later duplicates of key replace its value and priority.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedValueWeakPriorityQueue;
+    /// let mut queue: KeyedValueWeakPriorityQueue<&str, char, i32> =
+    ///     vec![("first", 'a', 0), ("second", 'b', 1), ("first", 'c', 2)].into_iter().collect();
+    /// assert_eq!(queue.pop(), Some(("first", 'c', 2)));
+    /// assert_eq!(queue.pop(), Some(("second", 'b', 1)));
+    /// assert_eq!(queue.pop(), None);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(n log n)*** in average.
+    fn from_iter<T: IntoIterator<Item = (TKey, TValue, TPriority)>>(i: T) -> Self {
+        let iter = i.into_iter();
+        let (min_size, _) = iter.size_hint();
+
+        let mut heap_base: Vec<HeapEntry<TPriority>> = Vec::with_capacity(min_size);
+        let mut key_to_pos: Mediator<TKey, S, THeap::Alloc, TValue> =
+            Mediator::with_capacity_and_hasher_in(min_size, S::default(), THeap::Alloc::default());
+
+        for (key, value, priority) in iter {
+            insert_unordered(&mut heap_base, key_to_pos.entry(key), value, priority);
+        }
+        Self::from_unordered(heap_base, key_to_pos)
+    }
+}
+
+impl<TKey, TValue, TPriority, THeap, S> Extend<(TKey, TValue, TPriority)>
+    for KeyedValuePriorityQueue<TKey, TValue, TPriority, THeap, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    THeap: EditableHeap<TPriority>,
+    S: BuildHasher,
+{
+    /// Pushes all items of iterator to queue.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(k log n)*** in average where k is number of pushed items.
+    fn extend<T: IntoIterator<Item = (TKey, TValue, TPriority)>>(&mut self, iter: T) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for (key, value, priority) in iter {
+            self.push(key, value, priority);
+        }
+    }
+}
+
+/// This is unordered borrowing iterator over [`KeyedValuePriorityQueue`].
+///
+/// [`KeyedValuePriorityQueue`]: struct.KeyedValuePriorityQueue.html
+///
+/// ### Time complexity
+/// Overall complexity of iteration is ***O(n)***
+pub struct KeyedValuePriorityQueueBorrowIter<
+    'a,
+    TKey,
+    TValue,
+    TPriority,
+    S = RandomState,
+    A = Global,
+> where
+    TKey: 'a + Hash + Eq,
+    TPriority: 'a,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    heap_iterator: HeapSliceIter<'a, TPriority>,
+    key_to_pos: &'a Mediator<TKey, S, A, TValue>,
+}
+
+impl<'a, TKey, TValue, TPriority, S, A> Iterator
+    for KeyedValuePriorityQueueBorrowIter<'a, TKey, TValue, TPriority, S, A>
+where
+    TKey: 'a + Hash + Eq,
+    TValue: 'a,
+    TPriority: 'a,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    type Item = (&'a TKey, &'a TValue, &'a TPriority);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let key_to_pos = self.key_to_pos;
+        self.heap_iterator.next().map(|(outer_pos, priority)| {
+            let (key, _) = key_to_pos.get_index(outer_pos);
+            (key, key_to_pos.get_value(outer_pos), priority)
+        })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.heap_iterator.size_hint()
+    }
+
+    #[inline]
+    fn count(self) -> usize
+    where
+        Self: Sized,
+    {
+        self.heap_iterator.count()
+    }
+}
+
+#[cfg(feature = "rayon")]
+mod rayon_impls {
+    use super::{insert_unordered, make_hash, KeyedValuePriorityQueue};
+    use crate::heap_storage::HeapSlice;
+    use crate::heap_traits::{EditableHeap, HeapEntry};
+    use crate::mediator::Mediator;
+    use allocator_api2::alloc::{Allocator, Global};
+    use rayon::iter::plumbing::{Consumer, ProducerCallback, UnindexedConsumer};
+    use rayon::prelude::*;
+    use std::hash::{BuildHasher, Hash};
+
+    impl<TKey, TValue, TPriority, THeap, S> FromParallelIterator<(TKey, TValue, TPriority)>
+        for KeyedValuePriorityQueue<TKey, TValue, TPriority, THeap, S>
+    where
+        TKey: Hash + Eq + Send,
+        TValue: Send,
+        TPriority: Ord + Send,
+        THeap: EditableHeap<TPriority>,
+        THeap::Alloc: Default,
+        S: BuildHasher + Default + Sync,
+    {
+        /// Allows building queue from parallel iterator using `collect()`.
+        /// Keys are hashed in parallel, later duplicates replace value and priority
+        /// of earlier ones like in sequential `collect()`.
+        ///
+        /// ### Time complexity
+        ///
+        /// ***O(n log n)*** in average.
+        fn from_par_iter<I>(par_iter: I) -> Self
+        where
+            I: IntoParallelIterator<Item = (TKey, TValue, TPriority)>,
+        {
+            let mut key_to_pos: Mediator<TKey, S, THeap::Alloc, TValue> =
+                Mediator::with_capacity_and_hasher_in(0, S::default(), THeap::Alloc::default());
+            let hasher = key_to_pos.hasher();
+            // Collecting keeps original order of items
+            let items: Vec<_> = par_iter
+                .into_par_iter()
+                .map(|(key, value, priority)| (make_hash(hasher, &key), key, value, priority))
+                .collect();
+
+            key_to_pos.reserve(items.len());
+            let mut heap_base: Vec<HeapEntry<TPriority>> = Vec::with_capacity(items.len());
+            for (hash, key, value, priority) in items {
+                insert_unordered(
+                    &mut heap_base,
+                    key_to_pos.entry_hashed(hash, key),
+                    value,
+                    priority,
+                );
+            }
+            Self::from_unordered(heap_base, key_to_pos)
+        }
+    }
+
+    impl<TKey, TValue, TPriority, THeap, S> ParallelExtend<(TKey, TValue, TPriority)>
+        for KeyedValuePriorityQueue<TKey, TValue, TPriority, THeap, S>
+    where
+        TKey: Hash + Eq + Send,
+        TValue: Send,
+        TPriority: Ord + Send,
+        THeap: EditableHeap<TPriority>,
+        S: BuildHasher + Sync,
+    {
+        /// Pushes all items of parallel iterator to queue.
+        /// Keys are hashed in parallel and inserted in order of iterator.
+        ///
+        /// ### Time complexity
+        ///
+        /// ***O(k log n)*** in average where k is number of pushed items.
+        fn par_extend<I>(&mut self, par_iter: I)
+        where
+            I: IntoParallelIterator<Item = (TKey, TValue, TPriority)>,
+        {
+            let hasher = self.key_to_pos.hasher();
+            let items: Vec<_> = par_iter
+                .into_par_iter()
+                .map(|(key, value, priority)| (make_hash(hasher, &key), key, value, priority))
+                .collect();
+
+            self.reserve(items.len());
+            for (hash, key, value, priority) in items {
+                match self.entry_hashed(hash, key) {
+                    super::ValueEntry::Occupied(mut entry) => {
+                        *entry.get_value_mut() = value;
+                        entry.set_priority(priority);
+                    }
+                    super::ValueEntry::Vacant(entry) => {
+                        entry.insert(value, priority);
+                    }
+                }
+            }
+        }
+    }
+
+    impl<'a, TKey, TValue, TPriority, THeap, S> IntoParallelIterator
+        for &'a KeyedValuePriorityQueue<TKey, TValue, TPriority, THeap, S>
+    where
+        TKey: 'a + Hash + Eq + Sync,
+        TValue: 'a + Sync,
+        TPriority: 'a + Ord + Sync,
+        THeap: EditableHeap<TPriority>,
+        THeap::Alloc: Sync,
+        S: BuildHasher + Sync,
+    {
+        type Item = (&'a TKey, &'a TValue, &'a TPriority);
+        type Iter = KeyedValuePriorityQueueParIter<'a, TKey, TValue, TPriority, S, THeap::Alloc>;
+
+        fn into_par_iter(self) -> Self::Iter {
+            KeyedValuePriorityQueueParIter {
+                heap_data: self.heap.data(),
+                key_to_pos: &self.key_to_pos,
+            }
+        }
+    }
+
+    /// This is unordered parallel borrowing iterator over [`KeyedValuePriorityQueue`].
+    /// Returned by `par_iter` method when `rayon::prelude` is imported.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::KeyedValueBinaryPriorityQueue;
+    /// use rayon::prelude::*;
+    /// let queue: KeyedValueBinaryPriorityQueue<u64, u64, u64> =
+    ///     (0..100).map(|x| (x, x * 3, x * 2)).collect();
+    /// let sum: u64 = queue.par_iter().map(|(_, &value, _)| value).sum();
+    /// assert_eq!(sum, 14850);
+    /// ```
+    ///
+    /// ### Time complexity
+    /// Overall complexity of iteration is ***O(n)***
+    ///
+    /// [`KeyedValuePriorityQueue`]: struct.KeyedValuePriorityQueue.html
+    pub struct KeyedValuePriorityQueueParIter<'a, TKey, TValue, TPriority, S, A = Global>
+    where
+        TKey: 'a + Hash + Eq,
+        TPriority: 'a,
+        S: BuildHasher,
+        A: Allocator + Clone,
+    {
+        heap_data: HeapSlice<'a, TPriority>,
+        key_to_pos: &'a Mediator<TKey, S, A, TValue>,
+    }
+
+    impl<'a, TKey, TValue, TPriority, S, A>
+        KeyedValuePriorityQueueParIter<'a, TKey, TValue, TPriority, S, A>
+    where
+        TKey: 'a + Hash + Eq + Sync,
+        TValue: 'a + Sync,
+        TPriority: 'a + Sync,
+        S: BuildHasher + Sync,
+        A: Allocator + Clone + Sync,
+    {
+        fn inner(
+            self,
+        ) -> impl IndexedParallelIterator<Item = (&'a TKey, &'a TValue, &'a TPriority)> + 'a
+        {
+            let key_to_pos = self.key_to_pos;
+            let heap_data = self.heap_data;
+            (0..heap_data.len()).into_par_iter().map(move |position| {
+                let outer_pos = heap_data.outer_pos(position);
+                let (key, _) = key_to_pos.get_index(outer_pos);
+                (
+                    key,
+                    key_to_pos.get_value(outer_pos),
+                    heap_data.priority(position),
+                )
+            })
+        }
+    }
+
+    impl<'a, TKey, TValue, TPriority, S, A> ParallelIterator
+        for KeyedValuePriorityQueueParIter<'a, TKey, TValue, TPriority, S, A>
+    where
+        TKey: 'a + Hash + Eq + Sync,
+        TValue: 'a + Sync,
+        TPriority: 'a + Sync,
+        S: BuildHasher + Sync,
+        A: Allocator + Clone + Sync,
+    {
+        type Item = (&'a TKey, &'a TValue, &'a TPriority);
+
+        fn drive_unindexed<C>(self, consumer: C) -> C::Result
+        where
+            C: UnindexedConsumer<Self::Item>,
+        {
+            self.inner().drive_unindexed(consumer)
+        }
+
+        fn opt_len(&self) -> Option<usize> {
+            Some(self.heap_data.len())
+        }
+    }
+
+    impl<'a, TKey, TValue, TPriority, S, A> IndexedParallelIterator
+        for KeyedValuePriorityQueueParIter<'a, TKey, TValue, TPriority, S, A>
+    where
+        TKey: 'a + Hash + Eq + Sync,
+        TValue: 'a + Sync,
+        TPriority: 'a + Sync,
+        S: BuildHasher + Sync,
+        A: Allocator + Clone + Sync,
+    {
+        fn len(&self) -> usize {
+            self.heap_data.len()
+        }
+
+        fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+            self.inner().drive(consumer)
+        }
+
+        fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+            self.inner().with_producer(callback)
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+pub use self::rayon_impls::KeyedValuePriorityQueueParIter;
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyedValuePriorityQueue, ValueEntry};
+    use crate::editable_binary_heap::BinaryHeap;
+    use crate::editable_weak_heap::WeakHeap;
+    use crate::heap_traits::EditableHeap;
+
+    fn check_values<THeap: EditableHeap<i32>>()
+    where
+        THeap::Alloc: Default,
+    {
+        let mut queue: KeyedValuePriorityQueue<u32, String, i32, THeap> =
+            KeyedValuePriorityQueue::new();
+        for i in 0..20 {
+            assert_eq!(queue.push(i, i.to_string(), (i as i32 * 7) % 20), None);
+        }
+        assert_eq!(queue.push(3, "three".into(), 100), Some(("3".into(), 1)));
+        queue.get_value_mut(&5).unwrap().push('!');
+        assert_eq!(queue.remove(&7), Some(("7".into(), 9)));
+        assert_eq!(queue.set_priority(&5, -1), Ok(15));
+        assert!(queue.set_priority(&7, 0).is_err());
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.len(), 19);
+
+        assert_eq!(queue.peek(), Some((&3, &"three".to_string(), &100)));
+        let mut popped = Vec::new();
+        while let Some((key, value, priority)) = queue.pop() {
+            if key == 5 {
+                assert_eq!(value, "5!");
+            } else if key != 3 {
+                assert_eq!(value, key.to_string());
+            }
+            popped.push(priority);
+            assert_eq!(queue.validate(), Ok(()));
+        }
+        assert_eq!(popped.len(), 19);
+        assert!(popped.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    fn test_values_follow_keys() {
+        check_values::<BinaryHeap<i32>>();
+        check_values::<WeakHeap<i32>>();
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut queue: KeyedValuePriorityQueue<&str, Vec<u32>, u32, BinaryHeap<u32>> =
+            KeyedValuePriorityQueue::new();
+        match queue.entry("key") {
+            ValueEntry::Vacant(entry) => {
+                assert_eq!(entry.get_key(), &"key");
+                entry.insert(vec![1], 1).push(2);
+            }
+            ValueEntry::Occupied(_) => unreachable!(),
+        }
+        queue.push("other", vec![], 5);
+        match queue.entry("key") {
+            ValueEntry::Occupied(mut entry) => {
+                assert_eq!(entry.get_value(), &vec![1, 2]);
+                assert_eq!(entry.get_priority(), &1);
+                entry.get_value_mut().push(3);
+                assert_eq!(entry.set_priority(10), 1);
+            }
+            ValueEntry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.peek(), Some((&"key", &vec![1, 2, 3], &10)));
+        if let ValueEntry::Occupied(entry) = queue.entry("key") {
+            entry.into_value_mut().push(4);
+        }
+        match queue.entry("other") {
+            ValueEntry::Occupied(entry) => assert_eq!(entry.remove(), ("other", vec![], 5)),
+            ValueEntry::Vacant(_) => unreachable!(),
+        }
+        let mut items: Vec<_> = queue.iter().collect();
+        items.sort_unstable();
+        assert_eq!(items, vec![(&"key", &vec![1, 2, 3, 4], &10)]);
+    }
+
+    #[test]
+    fn test_collect_and_meld() {
+        let mut queue: KeyedValuePriorityQueue<u32, String, u32, WeakHeap<u32>> =
+            (0..10).map(|x| (x % 5, x.to_string(), x)).collect();
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue.get_value(&2), Some(&"7".to_string()));
+        queue.extend(vec![
+            (1, "one".to_string(), 20),
+            (11, "eleven".to_string(), 0),
+        ]);
+        assert_eq!(queue.validate(), Ok(()));
+
+        let other: KeyedValuePriorityQueue<u32, String, u32, WeakHeap<u32>> =
+            vec![(1, "first".to_string(), 1), (12, "twelve".to_string(), 12)]
+                .into_iter()
+                .collect();
+        queue.meld(other);
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.len(), 7);
+        assert_eq!(queue.pop(), Some((12, "twelve".to_string(), 12)));
+        assert_eq!(queue.get_value(&1), Some(&"first".to_string()));
+
+        if let Some(mut priority) = queue.get_mut(&1) {
+            assert_eq!(priority.get_key(), &1);
+            *priority = 100;
+        }
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.pop(), Some((1, "first".to_string(), 100)));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_rayon() {
+        use rayon::prelude::*;
+
+        let mut queue: KeyedValuePriorityQueue<u32, u32, u32, BinaryHeap<u32>> = (0..1000u32)
+            .into_par_iter()
+            .map(|x| (x % 100, x, x))
+            .collect();
+        assert_eq!(queue.len(), 100);
+        assert_eq!(queue.get_value(&3), Some(&903));
+        queue.par_extend((0..10u32).into_par_iter().map(|x| (x, 0, 0)));
+        assert_eq!(queue.get_priority(&3), Some(&0));
+        assert_eq!(queue.validate(), Ok(()));
+        let sum: u32 = queue.par_iter().map(|(_, &value, _)| value).sum();
+        assert_eq!(sum, queue.iter().map(|(_, &value, _)| value).sum());
+    }
+}
@@ -28,8 +28,7 @@
 //! [`KeyedPriorityQueue`]: struct.KeyedPriorityQueue.html
 //!
 //! ```
-//! use keyed_priority_queue::{KeyedBinaryPriorityQueue, Entry};
-//! use std::cmp::Reverse;
+//! use keyed_priority_queue::{define_priority, KeyedBinaryPriorityQueue, Entry};
 //! use std::collections::HashSet;
 //! use std::ops::Index;
 //!
@@ -83,23 +82,25 @@
 //!     // Already handled this points
 //!     let mut closed_set: HashSet<Position> = HashSet::new();
 //!     // Positions sortered by total cost and real cost.
-//!     // We prefer items with lower real cost if total ones are same.
-//!     #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
-//!     struct Cost {
-//!         total: u32,
-//!         real: u32,
+//!     // We prefer items with lower total cost
+//!     // and items with lower real cost if total ones are same.
+//!     define_priority! {
+//!         #[derive(Copy, Clone)]
+//!         struct Cost {
+//!             min total: u32,
+//!             min real: u32,
+//!         }
 //!     }
 //!     // Queue that contains all nodes that available for next step
-//!     // Min-queue required so Reverse struct used as priority.
-//!     let mut available = KeyedBinaryPriorityQueue::<Position, Reverse<Cost>>::new();
+//!     let mut available = KeyedBinaryPriorityQueue::<Position, Cost>::new();
 //!     available.push(
 //!         start,
-//!         Reverse(Cost {
+//!         Cost {
 //!             total: calc_heuristic(start),
 //!             real: 0,
-//!         }),
+//!         },
 //!     );
-//!     while let Some((current_pos, Reverse(current_cost))) = available.pop() {
+//!     while let Some((current_pos, current_cost)) = available.pop() {
 //!         // We have reached target
 //!         if current_pos == target {
 //!             return Some(current_cost.real);
@@ -117,11 +118,11 @@
 //!                 match available.entry(next) {
 //!                     Entry::Vacant(entry) => {
 //!                         // Add new position to queue
-//!                         entry.set_priority(Reverse(cost));
+//!                         entry.set_priority(cost);
 //!                     }
-//!                     Entry::Occupied(entry) if *entry.get_priority() < Reverse(cost) => {
+//!                     Entry::Occupied(entry) if *entry.get_priority() < cost => {
 //!                         // Have found better path to node in queue
-//!                         entry.set_priority(Reverse(cost));
+//!                         entry.set_priority(cost);
 //!                     }
 //!                     _ => { /* Have found worse path. */ }
 //!                 };
@@ -147,29 +148,325 @@
 //! ```
 //!
 
+#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+mod aging_keyed_priority_queue;
+#[cfg(feature = "algorithms")]
+pub mod algorithms;
+mod array_keyed_priority_queue;
+#[cfg(feature = "std")]
+mod bounded_keyed_priority_queue;
+#[cfg(feature = "std")]
+mod bucket_lists;
+#[cfg(feature = "sync")]
+mod concurrent_keyed_priority_queue;
+mod define_priority;
+#[cfg(feature = "std")]
 mod editable_binary_heap;
+#[cfg(feature = "std")]
+mod editable_blocked_heap;
+#[cfg(feature = "std")]
+mod editable_bucket_queue;
+#[cfg(feature = "std")]
+mod editable_calendar_queue;
+#[cfg(feature = "std")]
+mod editable_leftist_heap;
+#[cfg(feature = "std")]
+mod editable_radix_heap;
+#[cfg(feature = "std")]
 mod editable_weak_heap;
+mod errors;
+#[cfg(feature = "std")]
+mod heap_storage;
+#[cfg(feature = "std")]
 mod heap_traits;
+#[cfg(feature = "std")]
 mod keyed_priority_queue;
+#[cfg(feature = "std")]
+mod keyed_value_priority_queue;
+#[cfg(feature = "std")]
 mod mediator;
+#[cfg(feature = "async")]
+mod priority_channel;
+#[cfg(feature = "std")]
+mod timer_queue;
 
+pub use crate::errors::{InvariantError, SetPriorityNotFoundError, TryReserveError};
+#[cfg(feature = "std")]
 pub use crate::keyed_priority_queue::{
     Entry, KeyedPriorityQueue, KeyedPriorityQueueBorrowIter, KeyedPriorityQueueIterator,
-    OccupiedEntry, SetPriorityNotFoundError, VacantEntry,
+    OccupiedEntry, VacantEntry,
+};
+
+#[cfg(feature = "std")]
+pub use crate::aging_keyed_priority_queue::{Aged, Aging, AgingKeyedPriorityQueue, LinearAging};
+pub use crate::array_keyed_priority_queue::{
+    ArrayKeyedPriorityQueue, ArrayKeyedPriorityQueueIter, CapacityError,
+};
+#[cfg(feature = "std")]
+pub use crate::bounded_keyed_priority_queue::{BoundedKeyedPriorityQueue, BoundedPushResult};
+#[cfg(feature = "std")]
+pub use crate::keyed_value_priority_queue::{
+    KeyedValuePriorityQueue, KeyedValuePriorityQueueBorrowIter, OccupiedValueEntry, PriorityGuard,
+    VacantValueEntry, ValueEntry,
+};
+#[cfg(feature = "std")]
+pub use crate::timer_queue::{PopExpired, TimerQueue};
+
+#[cfg(feature = "rayon")]
+pub use crate::keyed_priority_queue::KeyedPriorityQueueParIter;
+#[cfg(feature = "rayon")]
+pub use crate::keyed_value_priority_queue::KeyedValuePriorityQueueParIter;
+
+#[cfg(feature = "sync")]
+pub use crate::concurrent_keyed_priority_queue::ConcurrentKeyedPriorityQueue;
+
+#[cfg(feature = "async")]
+pub use crate::priority_channel::{
+    priority_channel, priority_channel_with_hasher, Receiver, Recv, SendError, Sender,
 };
 
+#[cfg(feature = "std")]
 pub use crate::editable_binary_heap::BinaryHeap;
+#[cfg(feature = "std")]
+pub use crate::editable_blocked_heap::BlockedHeap;
+#[cfg(feature = "std")]
+pub use crate::editable_bucket_queue::{BucketPriority, BucketQueue};
+#[cfg(feature = "std")]
+pub use crate::editable_calendar_queue::{CalendarPriority, CalendarQueue};
+#[cfg(feature = "std")]
+pub use crate::editable_leftist_heap::LeftistHeap;
+#[cfg(feature = "std")]
+pub use crate::editable_radix_heap::{RadixHeap, RadixPriority};
+#[cfg(feature = "std")]
 pub use crate::editable_weak_heap::WeakHeap;
-pub use crate::heap_traits::EditableHeap;
+#[cfg(feature = "std")]
+pub use crate::heap_storage::{HeapStorage, SoaStorage};
+#[cfg(feature = "std")]
+pub use crate::heap_traits::{EditableHeap, HeapEntry};
 
+#[cfg(feature = "std")]
 pub type KeyedBinaryPriorityQueue<
     TKey,
     TPriority,
     RandomState = std::collections::hash_map::RandomState,
 > = KeyedPriorityQueue<TKey, TPriority, editable_binary_heap::BinaryHeap<TPriority>, RandomState>;
 
+#[cfg(feature = "std")]
 pub type KeyedWeakPriorityQueue<
     TKey,
     TPriority,
     RandomState = std::collections::hash_map::RandomState,
 > = KeyedPriorityQueue<TKey, TPriority, editable_weak_heap::WeakHeap<TPriority>, RandomState>;
+
+#[cfg(feature = "std")]
+pub type KeyedValueBinaryPriorityQueue<
+    TKey,
+    TValue,
+    TPriority,
+    RandomState = std::collections::hash_map::RandomState,
+> = KeyedValuePriorityQueue<
+    TKey,
+    TValue,
+    TPriority,
+    editable_binary_heap::BinaryHeap<TPriority>,
+    RandomState,
+>;
+
+#[cfg(feature = "std")]
+pub type KeyedValueWeakPriorityQueue<
+    TKey,
+    TValue,
+    TPriority,
+    RandomState = std::collections::hash_map::RandomState,
+> = KeyedValuePriorityQueue<
+    TKey,
+    TValue,
+    TPriority,
+    editable_weak_heap::WeakHeap<TPriority>,
+    RandomState,
+>;
+
+#[cfg(feature = "std")]
+/// Queue which keeps heap and keys in memory of allocator `A`.
+/// It is created by [`new_in`] and similar methods.
+///
+/// [`new_in`]: struct.KeyedPriorityQueue.html#method.new_in
+pub type KeyedBinaryPriorityQueueIn<
+    TKey,
+    TPriority,
+    A,
+    RandomState = std::collections::hash_map::RandomState,
+> = KeyedPriorityQueue<
+    TKey,
+    TPriority,
+    editable_binary_heap::BinaryHeap<TPriority, allocator_api2::vec::Vec<HeapEntry<TPriority>, A>>,
+    RandomState,
+>;
+
+#[cfg(feature = "std")]
+/// Queue which keeps heap and keys in memory of allocator `A`.
+/// It is created by [`new_in`] and similar methods.
+///
+/// [`new_in`]: struct.KeyedPriorityQueue.html#method.new_in
+pub type KeyedWeakPriorityQueueIn<
+    TKey,
+    TPriority,
+    A,
+    RandomState = std::collections::hash_map::RandomState,
+> = KeyedPriorityQueue<
+    TKey,
+    TPriority,
+    editable_weak_heap::WeakHeap<TPriority, allocator_api2::vec::Vec<HeapEntry<TPriority>, A>>,
+    RandomState,
+>;
+
+#[cfg(feature = "std")]
+/// Queue which keeps subtrees of heap in contiguous blocks to touch fewer memory pages.
+/// See [`BlockedHeap`] for details.
+///
+/// [`BlockedHeap`]: struct.BlockedHeap.html
+pub type KeyedBlockedPriorityQueue<
+    TKey,
+    TPriority,
+    RandomState = std::collections::hash_map::RandomState,
+> = KeyedPriorityQueue<TKey, TPriority, editable_blocked_heap::BlockedHeap<TPriority>, RandomState>;
+
+#[cfg(feature = "std")]
+/// Queue for monotone integer priorities like distances in Dijkstra algorithm.
+/// See [`RadixHeap`] for details.
+///
+/// [`RadixHeap`]: struct.RadixHeap.html
+pub type KeyedRadixPriorityQueue<
+    TKey,
+    TPriority,
+    RandomState = std::collections::hash_map::RandomState,
+> = KeyedPriorityQueue<TKey, TPriority, editable_radix_heap::RadixHeap<TPriority>, RandomState>;
+
+#[cfg(feature = "std")]
+/// Queue for priorities from small integer range like costs in grid pathfinding.
+/// See [`BucketQueue`] for details.
+///
+/// [`BucketQueue`]: struct.BucketQueue.html
+pub type KeyedBucketPriorityQueue<
+    TKey,
+    TPriority,
+    RandomState = std::collections::hash_map::RandomState,
+> = KeyedPriorityQueue<TKey, TPriority, editable_bucket_queue::BucketQueue<TPriority>, RandomState>;
+
+#[cfg(feature = "std")]
+/// Queue for events of discrete-event simulation keyed by their IDs.
+/// See [`CalendarQueue`] for details.
+///
+/// [`CalendarQueue`]: struct.CalendarQueue.html
+pub type KeyedCalendarPriorityQueue<
+    TKey,
+    TPriority,
+    RandomState = std::collections::hash_map::RandomState,
+> = KeyedPriorityQueue<
+    TKey,
+    TPriority,
+    editable_calendar_queue::CalendarQueue<TPriority>,
+    RandomState,
+>;
+
+#[cfg(feature = "std")]
+/// Queue which supports cheap merging with [`meld`].
+/// See [`LeftistHeap`] for details.
+///
+/// [`meld`]: struct.KeyedPriorityQueue.html#method.meld
+/// [`LeftistHeap`]: struct.LeftistHeap.html
+pub type KeyedLeftistPriorityQueue<
+    TKey,
+    TPriority,
+    RandomState = std::collections::hash_map::RandomState,
+> = KeyedPriorityQueue<TKey, TPriority, editable_leftist_heap::LeftistHeap<TPriority>, RandomState>;
+
+#[cfg(feature = "std")]
+pub type BoundedKeyedBinaryPriorityQueue<
+    TKey,
+    TPriority,
+    RandomState = std::collections::hash_map::RandomState,
+> = BoundedKeyedPriorityQueue<
+    TKey,
+    TPriority,
+    editable_binary_heap::BinaryHeap<std::cmp::Reverse<TPriority>>,
+    RandomState,
+>;
+
+#[cfg(feature = "std")]
+pub type BoundedKeyedWeakPriorityQueue<
+    TKey,
+    TPriority,
+    RandomState = std::collections::hash_map::RandomState,
+> = BoundedKeyedPriorityQueue<
+    TKey,
+    TPriority,
+    editable_weak_heap::WeakHeap<std::cmp::Reverse<TPriority>>,
+    RandomState,
+>;
+
+#[cfg(feature = "std")]
+pub type AgingKeyedBinaryPriorityQueue<
+    TKey,
+    TPriority,
+    TAging = LinearAging,
+    RandomState = std::collections::hash_map::RandomState,
+> = AgingKeyedPriorityQueue<
+    TKey,
+    TPriority,
+    TAging,
+    editable_binary_heap::BinaryHeap<Aged<TPriority, <TAging as Aging<TPriority>>::Anchored>>,
+    RandomState,
+>;
+
+#[cfg(feature = "std")]
+pub type AgingKeyedWeakPriorityQueue<
+    TKey,
+    TPriority,
+    TAging = LinearAging,
+    RandomState = std::collections::hash_map::RandomState,
+> = AgingKeyedPriorityQueue<
+    TKey,
+    TPriority,
+    TAging,
+    editable_weak_heap::WeakHeap<Aged<TPriority, <TAging as Aging<TPriority>>::Anchored>>,
+    RandomState,
+>;
+
+#[cfg(feature = "sync")]
+pub type ConcurrentKeyedBinaryPriorityQueue<
+    TKey,
+    TPriority,
+    RandomState = std::collections::hash_map::RandomState,
+> = ConcurrentKeyedPriorityQueue<
+    TKey,
+    TPriority,
+    editable_binary_heap::BinaryHeap<TPriority>,
+    RandomState,
+>;
+
+#[cfg(feature = "sync")]
+pub type ConcurrentKeyedWeakPriorityQueue<
+    TKey,
+    TPriority,
+    RandomState = std::collections::hash_map::RandomState,
+> = ConcurrentKeyedPriorityQueue<
+    TKey,
+    TPriority,
+    editable_weak_heap::WeakHeap<TPriority>,
+    RandomState,
+>;
+
+#[inline(always)]
+pub(crate) fn make_hash<Q: core::hash::Hash + ?Sized, S: core::hash::BuildHasher>(
+    hasher: &S,
+    key: &Q,
+) -> u64 {
+    use core::hash::Hasher;
+    let mut state = hasher.build_hasher();
+    key.hash(&mut state);
+    state.finish()
+}
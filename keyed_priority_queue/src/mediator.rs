@@ -1,118 +1,280 @@
 use std::borrow::Borrow;
 use std::hash::{BuildHasher, Hash};
 
-use indexmap::map::{IndexMap, OccupiedEntry as IMOccupiedEntry, VacantEntry as IMVacantEntry};
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::vec::Vec;
+use hashbrown::HashTable;
 
-use crate::heap_traits::HeapIndex;
+use crate::heap_traits::{index_from_repr, index_to_repr, HeapIndex, IndexRepr};
+use crate::keyed_priority_queue::TryReserveError;
+use crate::make_hash;
 
 /// Wrapper around possible outer vec index
 /// Used to avoid mux up with heap index
 /// And to make sure that `Mediator` indexed only with MediatorIndex
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
-pub struct MediatorIndex(pub(crate) usize);
+pub struct MediatorIndex(pub(crate) IndexRepr);
 
-/// This is wrapper over over indexmap that uses `MediatorIndex` as index.
-/// Also it centralized checking for panics
+impl MediatorIndex {
+    #[inline(always)]
+    pub(crate) fn new(index: usize) -> Self {
+        MediatorIndex(index_to_repr(index))
+    }
+
+    #[inline(always)]
+    pub(crate) fn get(self) -> usize {
+        index_from_repr(self.0)
+    }
+}
+
+/// Slot keeps key with its value and its position in heap.
+/// Keys never move between slots so heap can keep slot index
+/// without fixing it up when other keys are removed.
 #[derive(Clone, Debug)]
-pub(crate) struct Mediator<TKey: Hash + Eq, S: BuildHasher> {
-    map: IndexMap<TKey, HeapIndex, S>,
+struct Slot<TKey, TValue> {
+    heap_idx: HeapIndex,
+    // Saved to avoid rehashing keys when table grows
+    hash: u64,
+    // None if slot is vacant and listed in `free`
+    item: Option<(TKey, TValue)>,
 }
 
-#[inline(always)]
-fn with_copied_heap_index<'a, T>((k, &i): (&'a T, &HeapIndex)) -> (&'a T, HeapIndex) {
-    (k, i)
+/// This is store of keys which uses `MediatorIndex` as index.
+/// Also it centralized checking for panics
+/// All its memory is taken from allocator `A`.
+/// Every key carries value of type `TValue` which is `()` for plain queues.
+#[derive(Clone, Debug)]
+pub(crate) struct Mediator<
+    TKey: Hash + Eq,
+    S: BuildHasher,
+    A: Allocator + Clone = Global,
+    TValue = (),
+> {
+    // Indexes of slots searchable by key hash
+    table: HashTable<MediatorIndex, A>,
+    slots: Vec<Slot<TKey, TValue>, A>,
+    // Vacant slots which would be reused by next insertions
+    free: Vec<MediatorIndex, A>,
+    hasher: S,
 }
 
-pub(crate) struct VacantEntry<'a, TKey: 'a + Hash + Eq, S: BuildHasher> {
-    internal: IMVacantEntry<'a, TKey, HeapIndex>,
-    // look `insert` definition for this
-    map: *mut Mediator<TKey, S>,
+// Entries keep reference to whole mediator
+// because KeyedPriorityQueue needs to update other items after insertion or removal.
+pub(crate) struct VacantEntry<
+    'a,
+    TKey: 'a + Hash + Eq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+    TValue = (),
+> {
+    mediator: &'a mut Mediator<TKey, S, A, TValue>,
+    key: TKey,
+    // Saved to avoid hashing key second time on insertion
+    hash: u64,
 }
-pub(crate) struct OccupiedEntry<'a, TKey: 'a + Hash + Eq, S: BuildHasher> {
-    internal: IMOccupiedEntry<'a, TKey, HeapIndex>,
-    // look `insert` definition for this
-    map: *mut Mediator<TKey, S>,
+pub(crate) struct OccupiedEntry<
+    'a,
+    TKey: 'a + Hash + Eq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+    TValue = (),
+> {
+    mediator: &'a mut Mediator<TKey, S, A, TValue>,
+    index: MediatorIndex,
 }
 
-pub(crate) enum MediatorEntry<'a, TKey: 'a + Hash + Eq, S: BuildHasher> {
-    Vacant(VacantEntry<'a, TKey, S>),
-    Occupied(OccupiedEntry<'a, TKey, S>),
+pub(crate) enum MediatorEntry<
+    'a,
+    TKey: 'a + Hash + Eq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+    TValue = (),
+> {
+    Vacant(VacantEntry<'a, TKey, S, A, TValue>),
+    Occupied(OccupiedEntry<'a, TKey, S, A, TValue>),
 }
 
-impl<TKey, S> Mediator<TKey, S>
+// Used by table to get hashes of stored indexes during resize
+#[inline(always)]
+fn slot_hasher<TKey, TValue>(slots: &[Slot<TKey, TValue>]) -> impl Fn(&MediatorIndex) -> u64 + '_ {
+    move |&index| slots[index.get()].hash
+}
+
+impl<TKey, S, A, TValue> Mediator<TKey, S, A, TValue>
 where
     TKey: Hash + Eq,
     S: BuildHasher,
+    A: Allocator + Clone,
 {
-    pub(crate) fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+    pub(crate) fn with_capacity_and_hasher_in(capacity: usize, hasher: S, alloc: A) -> Self {
         Self {
-            map: IndexMap::with_capacity_and_hasher(capacity, hasher),
+            table: HashTable::with_capacity_in(capacity, alloc.clone()),
+            slots: Vec::with_capacity_in(capacity, alloc.clone()),
+            free: Vec::new_in(alloc),
+            hasher,
         }
     }
 
+    #[inline(always)]
+    pub(crate) fn allocator(&self) -> &A {
+        self.slots.allocator()
+    }
+
     #[inline(always)]
     pub(crate) fn reserve(&mut self, additional: usize) {
-        self.map.reserve(additional)
+        self.table.reserve(additional, slot_hasher(&self.slots));
+        self.slots
+            .reserve(additional.saturating_sub(self.free.len()));
+    }
+
+    #[inline(always)]
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.table
+            .try_reserve(additional, slot_hasher(&self.slots))
+            .map_err(|_| TryReserveError {})?;
+        self.slots
+            .try_reserve(additional.saturating_sub(self.free.len()))
+            .map_err(|_| TryReserveError {})
+    }
+
+    #[inline(always)]
+    pub(crate) fn capacity(&self) -> usize {
+        std::cmp::min(self.table.capacity(), self.slots.capacity())
+    }
+
+    /// Moves keys from the end into vacant slots so all vacant slots can be released.
+    /// Calls `relocated` with new index and heap position of every moved key
+    /// because heap keeps slot indexes and must be updated.
+    pub(crate) fn shrink_to<TRelocated: FnMut(MediatorIndex, HeapIndex)>(
+        &mut self,
+        min_capacity: usize,
+        mut relocated: TRelocated,
+    ) {
+        // Sorted in descending order so lowest vacant index is popped first
+        self.free.sort_unstable_by(|a, b| b.cmp(a));
+        while let Some(vacant) = self.free.pop() {
+            while self.slots.last().map_or(false, |slot| slot.item.is_none()) {
+                self.slots.pop();
+            }
+            if vacant.get() >= self.slots.len() {
+                continue;
+            }
+            // Last slot is occupied and lies after vacant one
+            let slot = self.slots.pop().expect("Vacant slot is before it");
+            let old_index = MediatorIndex::new(self.slots.len());
+            let (hash, heap_idx) = (slot.hash, slot.heap_idx);
+            self.slots[vacant.get()] = slot;
+            *self
+                .table
+                .find_mut(hash, |&stored| stored == old_index)
+                .expect("All occupied slots are in table") = vacant;
+            relocated(vacant, heap_idx);
+        }
+        self.free.shrink_to_fit();
+        self.slots.shrink_to(min_capacity);
+        self.table.shrink_to(min_capacity, slot_hasher(&self.slots));
     }
 
     #[inline(always)]
     pub(crate) fn len(&self) -> usize {
-        self.map.len()
+        self.table.len()
     }
 
     #[inline(always)]
     pub(crate) fn is_empty(&self) -> bool {
-        self.map.is_empty()
+        self.table.is_empty()
     }
 
     #[inline(always)]
     pub(crate) fn clear(&mut self) {
-        self.map.clear()
-    }
-
-    #[inline(always)]
-    pub(crate) fn get_index(&self, MediatorIndex(position): MediatorIndex) -> (&TKey, HeapIndex) {
-        self.map
-            .get_index(position)
-            .map(with_copied_heap_index)
-            .expect("All mediator indexes must be valid")
-    }
-
-    #[inline(always)]
-    pub(crate) fn entry(&mut self, key: TKey) -> MediatorEntry<TKey, S> {
-        // Pointer dereferenced only after internal entry dropped
-        // This unsafe pointer dark magic is required because you cannot handle
-        // enum that keep either Entry or Map inside:
-        // Example: https://play.rust-lang.org/?version=stable&mode=debug&edition=2018&gist=aee5555275572e385350a786127f91ed
-        //
-        // We need to acquire mutable reference to Mediator in KeyedPriorityQueue Entry API implementation to keep consistency
-        // but keeping multiple mutable references in entry disallowed by borrow checker.
-        // We keep IndexMap entry in Mediator entry and pointer to Mediator, and allow use second one only after dropping first.
-        // This references used in 3 places:
-        // 1. keyed_priority_queue::OccupiedEntry::set_priority
-        // 2. keyed_priority_queue::OccupiedEntry::remove
-        // 3. keyed_priority_queue::VacantEntry::set_priority
-        //
-        // Also after stabilisation of `polonius` (https://rust-lang.github.io/polonius/) we would be able
-        // to remove this pointer hack from OccupiedEntry and keep reference to Mediator and index in it instead.
-        let map = self as *mut _;
-        match self.map.entry(key) {
-            indexmap::map::Entry::Occupied(internal) => {
-                MediatorEntry::Occupied(OccupiedEntry { internal, map })
-            }
-            indexmap::map::Entry::Vacant(internal) => {
-                MediatorEntry::Vacant(VacantEntry { internal, map })
-            }
+        self.table.clear();
+        self.slots.clear();
+        self.free.clear();
+    }
+
+    #[inline(always)]
+    pub(crate) fn get_index(&self, index: MediatorIndex) -> (&TKey, HeapIndex) {
+        let slot = &self.slots[index.get()];
+        let (key, _) = slot
+            .item
+            .as_ref()
+            .expect("All mediator indexes must be valid");
+        (key, slot.heap_idx)
+    }
+
+    #[inline(always)]
+    pub(crate) fn get_value(&self, index: MediatorIndex) -> &TValue {
+        let (_, value) = self.slots[index.get()]
+            .item
+            .as_ref()
+            .expect("All mediator indexes must be valid");
+        value
+    }
+
+    #[inline(always)]
+    pub(crate) fn get_value_mut(&mut self, index: MediatorIndex) -> &mut TValue {
+        let (_, value) = self.slots[index.get()]
+            .item
+            .as_mut()
+            .expect("All mediator indexes must be valid");
+        value
+    }
+
+    #[inline(always)]
+    pub(crate) fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    #[inline(always)]
+    pub(crate) fn entry(&mut self, key: TKey) -> MediatorEntry<'_, TKey, S, A, TValue> {
+        let hash = make_hash(&self.hasher, &key);
+        self.entry_hashed(hash, key)
+    }
+
+    /// `hash` must be calculated by `make_hash` using hasher of this mediator
+    #[inline(always)]
+    pub(crate) fn entry_hashed(
+        &mut self,
+        hash: u64,
+        key: TKey,
+    ) -> MediatorEntry<'_, TKey, S, A, TValue> {
+        match self.find(hash, &key) {
+            Some(index) => MediatorEntry::Occupied(OccupiedEntry {
+                mediator: self,
+                index,
+            }),
+            None => MediatorEntry::Vacant(VacantEntry {
+                mediator: self,
+                key,
+                hash,
+            }),
         }
     }
 
+    #[inline(always)]
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<MediatorIndex>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slots = &self.slots;
+        self.table
+            .find(hash, |&index| {
+                slots[index.get()]
+                    .item
+                    .as_ref()
+                    .map_or(false, |(stored, _)| stored.borrow() == key)
+            })
+            .copied()
+    }
+
     #[inline(always)]
     pub(crate) fn get<Q>(&self, key: &Q) -> Option<HeapIndex>
     where
         TKey: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
-        self.map.get(key).copied()
+        self.get_full(key).map(|(_, _, heap_idx)| heap_idx)
     }
 
     #[inline(always)]
@@ -121,85 +283,256 @@ where
         TKey: Borrow<Q>,
         Q: Hash + Eq + ?Sized,
     {
-        self.map
-            .get_full(key)
-            .map(|(idx, key, &val)| (MediatorIndex(idx), key, val))
+        let index = self.find(make_hash(&self.hasher, key), key)?;
+        let (key, heap_idx) = self.get_index(index);
+        Some((index, key, heap_idx))
     }
 
+    /// Removes key and its value from their slot.
+    /// Other keys keep their indexes.
     #[inline(always)]
-    pub(crate) fn swap_remove_index(
-        &mut self,
-        MediatorIndex(index): MediatorIndex,
-    ) -> (TKey, HeapIndex) {
-        self.map
-            .swap_remove_index(index)
-            .expect("All mediator indexes must be valid")
+    pub(crate) fn remove_index(&mut self, index: MediatorIndex) -> (TKey, TValue, HeapIndex) {
+        let slot = &mut self.slots[index.get()];
+        let (key, value) = slot
+            .item
+            .take()
+            .expect("All mediator indexes must be valid");
+        let heap_idx = slot.heap_idx;
+        let hash = slot.hash;
+        self.table
+            .find_entry(hash, |&stored| stored == index)
+            .expect("All occupied slots are in table")
+            .remove();
+        self.free.push(index);
+        (key, value, heap_idx)
     }
 
     #[inline(always)]
-    pub(crate) fn get_index_mut(&mut self, MediatorIndex(index): MediatorIndex) -> &mut HeapIndex {
-        self.map
-            .get_index_mut(index)
-            .expect("All mediator indexes must be valid")
-            .1
+    pub(crate) fn get_index_mut(&mut self, index: MediatorIndex) -> &mut HeapIndex {
+        let slot = &mut self.slots[index.get()];
+        debug_assert!(slot.item.is_some(), "All mediator indexes must be valid");
+        &mut slot.heap_idx
+    }
+
+    /// Returns indexes of all stored keys along with their heap positions
+    pub(crate) fn indexes(&self) -> impl Iterator<Item = (MediatorIndex, HeapIndex)> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.item.is_some())
+            .map(|(index, slot)| (MediatorIndex::new(index), slot.heap_idx))
     }
 
-    #[cfg(test)]
-    pub(crate) fn iter(&self) -> impl Iterator<Item = (&TKey, HeapIndex)> {
-        self.map.iter().map(with_copied_heap_index)
+    /// Consumes mediator returning all stored keys and values along with their heap positions
+    pub(crate) fn into_entries(self) -> impl Iterator<Item = (TKey, TValue, HeapIndex)> {
+        self.slots.into_iter().filter_map(|slot| {
+            let heap_idx = slot.heap_idx;
+            slot.item.map(|(key, value)| (key, value, heap_idx))
+        })
     }
 }
 
-impl<'a, TKey: 'a + Hash + Eq, S: BuildHasher> VacantEntry<'a, TKey, S> {
-    // Safety: make sure that nobody uses original mutable reference to mediator
-    // when returned pointer are used
-    // And the pointer never available longer than `Mediator` instance which created the VacantEntry
-    // See `Mediator::entry` and KeyedPriorityQueue's `remove` and `set_priority` entry methods.
+impl<'a, TKey, S, A, TValue> VacantEntry<'a, TKey, S, A, TValue>
+where
+    TKey: 'a + Hash + Eq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Inserts key with its value and returns mediator back to allow updating other items
     #[inline]
-    pub(crate) unsafe fn insert(
+    pub(crate) fn insert(
         self,
-        value: HeapIndex,
-    ) -> (&'a mut Mediator<TKey, S>, MediatorIndex) {
-        let map = self.map;
-        let result_index = MediatorIndex(self.internal.index());
-        {
-            self.internal.insert(value);
-        }
-        let mediator = map.as_mut().expect("Validated in entry method");
-        (mediator, result_index)
+        heap_idx: HeapIndex,
+        value: TValue,
+    ) -> (&'a mut Mediator<TKey, S, A, TValue>, MediatorIndex) {
+        let VacantEntry {
+            mediator,
+            key,
+            hash,
+        } = self;
+        let slot = Slot {
+            heap_idx,
+            hash,
+            item: Some((key, value)),
+        };
+        let index = match mediator.free.pop() {
+            Some(index) => {
+                mediator.slots[index.get()] = slot;
+                index
+            }
+            None => {
+                // Checked before pushing so overflow of index doesn't leave orphan slot
+                let index = MediatorIndex::new(mediator.slots.len());
+                mediator.slots.push(slot);
+                index
+            }
+        };
+        let slots = &mediator.slots;
+        mediator
+            .table
+            .insert_unique(hash, index, slot_hasher(slots));
+        (mediator, index)
+    }
+
+    /// Puts key with its value into slot of `index` instead of stored key.
+    /// Slot keeps its heap position so heap needs no fix-ups.
+    /// Returns mediator back along with replaced key and value.
+    #[inline]
+    pub(crate) fn replace_index(
+        self,
+        index: MediatorIndex,
+        value: TValue,
+    ) -> (&'a mut Mediator<TKey, S, A, TValue>, TKey, TValue) {
+        let VacantEntry {
+            mediator,
+            key,
+            hash,
+        } = self;
+        let slot = &mut mediator.slots[index.get()];
+        let old_hash = std::mem::replace(&mut slot.hash, hash);
+        let (old_key, old_value) = slot
+            .item
+            .replace((key, value))
+            .expect("All mediator indexes must be valid");
+        mediator
+            .table
+            .find_entry(old_hash, |&stored| stored == index)
+            .expect("All occupied slots are in table")
+            .remove();
+        let slots = &mediator.slots;
+        mediator
+            .table
+            .insert_unique(hash, index, slot_hasher(slots));
+        (mediator, old_key, old_value)
     }
 
     #[inline]
     pub(crate) fn get_key(&self) -> &TKey {
-        self.internal.key()
+        &self.key
     }
 
     #[inline]
-    pub(crate) fn index(&self) -> MediatorIndex {
-        MediatorIndex(self.internal.index())
+    pub(crate) fn into_key(self) -> TKey {
+        self.key
     }
 }
 
-impl<'a, TKey: 'a + Hash + Eq, S: BuildHasher> OccupiedEntry<'a, TKey, S> {
+impl<'a, TKey, S, A, TValue> OccupiedEntry<'a, TKey, S, A, TValue>
+where
+    TKey: 'a + Hash + Eq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
     #[inline]
     pub(crate) fn get_heap_idx(&self) -> HeapIndex {
-        *self.internal.get()
+        self.mediator.get_index(self.index).1
     }
 
     #[inline]
     pub(crate) fn get_key(&self) -> &TKey {
-        self.internal.key()
+        self.mediator.get_index(self.index).0
     }
 
-    // Safety: make sure that nobody uses original mutable reference to mediator
-    // when returned reference are used
-    // And the pointer never available longer than `Mediator` instance which created the VacantEntry
-    // See `Mediator::entry` and KeyedPriorityQueue's `set_priority` entry method.
     #[inline]
-    pub(crate) unsafe fn transform_to_map(self) -> &'a mut Mediator<TKey, S> {
-        let map = self.map;
-        std::mem::drop(self);
-        let mediator = map.as_mut().expect("Validated in entry method");
-        mediator
+    pub(crate) fn get_value(&self) -> &TValue {
+        self.mediator.get_value(self.index)
+    }
+
+    #[inline]
+    pub(crate) fn get_value_mut(&mut self) -> &mut TValue {
+        self.mediator.get_value_mut(self.index)
+    }
+
+    /// Returns mediator back to allow updating other items
+    #[inline]
+    pub(crate) fn into_mediator(self) -> (&'a mut Mediator<TKey, S, A, TValue>, MediatorIndex) {
+        (self.mediator, self.index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mediator, MediatorEntry, MediatorIndex};
+    use crate::heap_traits::HeapIndex;
+    use allocator_api2::alloc::Global;
+    use std::collections::hash_map::RandomState;
+
+    fn insert(mediator: &mut Mediator<u32, RandomState>, key: u32) -> MediatorIndex {
+        match mediator.entry(key) {
+            MediatorEntry::Vacant(entry) => entry.insert(HeapIndex::new(key as usize), ()).1,
+            MediatorEntry::Occupied(_) => panic!("Key {} is already inserted", key),
+        }
+    }
+
+    #[test]
+    fn test_slots_are_stable() {
+        let mut mediator = Mediator::with_capacity_and_hasher_in(0, RandomState::default(), Global);
+        let indexes: Vec<_> = (0..10).map(|key| insert(&mut mediator, key)).collect();
+        assert_eq!(mediator.remove_index(indexes[3]), (3, (), HeapIndex(3)));
+        assert_eq!(mediator.remove_index(indexes[9]), (9, (), HeapIndex(9)));
+        assert_eq!(mediator.len(), 8);
+        // Removal doesn't move other keys
+        for key in (0..9).filter(|&key| key != 3) {
+            let (index, _, heap_idx) = mediator.get_full(&key).unwrap();
+            assert_eq!(index, indexes[key as usize]);
+            assert_eq!(mediator.get_index(index), (&key, heap_idx));
+        }
+        assert_eq!(mediator.get(&3), None);
+        // Vacant slots are reused
+        let reused = insert(&mut mediator, 100);
+        assert!(reused == indexes[3] || reused == indexes[9]);
+        assert_eq!(mediator.indexes().count(), 9);
+    }
+
+    #[test]
+    fn test_shrink_compacts_slots() {
+        let mut mediator = Mediator::with_capacity_and_hasher_in(0, RandomState::default(), Global);
+        let indexes: Vec<_> = (0..100).map(|key| insert(&mut mediator, key)).collect();
+        for &index in indexes[1..99].iter() {
+            mediator.remove_index(index);
+        }
+        let mut relocations = Vec::new();
+        mediator.shrink_to(0, |index, heap_idx| relocations.push((index, heap_idx)));
+        // Surviving key from the end is moved into the lowest vacant slot
+        assert_eq!(relocations, vec![(MediatorIndex(1), HeapIndex(99))]);
+        assert!(mediator.capacity() >= 2);
+        assert!(mediator.capacity() < 100);
+        assert_eq!(
+            mediator.get_full(&0).map(|(index, _, _)| index),
+            Some(indexes[0])
+        );
+        assert_eq!(
+            mediator.get_full(&99),
+            Some((MediatorIndex(1), &99, HeapIndex(99)))
+        );
+        assert_eq!(mediator.indexes().count(), 2);
+
+        // New keys are appended after compacted ones
+        assert_eq!(insert(&mut mediator, 200), MediatorIndex(2));
+        assert_eq!(mediator.get(&200), Some(HeapIndex(200)));
+    }
+
+    #[test]
+    fn test_replace_index() {
+        let mut mediator = Mediator::with_capacity_and_hasher_in(0, RandomState::default(), Global);
+        let indexes: Vec<_> = (0..10).map(|key| insert(&mut mediator, key)).collect();
+        let replaced = match mediator.entry(100) {
+            MediatorEntry::Vacant(entry) => entry.replace_index(indexes[4], ()).1,
+            MediatorEntry::Occupied(_) => unreachable!(),
+        };
+        assert_eq!(replaced, 4);
+        assert_eq!(mediator.len(), 10);
+        assert_eq!(mediator.get(&4), None);
+        // New key takes slot and heap position of replaced one
+        assert_eq!(
+            mediator.get_full(&100),
+            Some((indexes[4], &100, HeapIndex(4)))
+        );
+        // Table stays consistent after it grows
+        for key in 10..100 {
+            insert(&mut mediator, key);
+        }
+        assert_eq!(mediator.get(&100), Some(HeapIndex(4)));
+        assert_eq!(mediator.get(&4), None);
     }
 }
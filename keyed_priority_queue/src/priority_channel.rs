@@ -0,0 +1,635 @@
+use crate::editable_binary_heap::BinaryHeap;
+use crate::keyed_priority_queue::{KeyedPriorityQueue, SetPriorityNotFoundError};
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display};
+use std::future::Future;
+use std::hash::{BuildHasher, Hash};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+/// Creates multi-producer multi-consumer channel which delivers items
+/// with the highest priority first.
+///
+/// Senders can change priority of queued items or cancel them by key.
+///
+/// Channel doesn't depend on any executor and can be used with any of them.
+///
+/// Available only with `async` feature.
+///
+/// ### Examples
+///
+///
+/// ```
+/// use keyed_priority_queue::priority_channel;
+/// # futures::executor::block_on(async {
+/// let (sender, receiver) = priority_channel();
+/// sender.send("low", 1).unwrap();
+/// sender.send("high", 10).unwrap();
+/// sender.send("bumped", 2).unwrap();
+/// sender.send("cancelled", 20).unwrap();
+///
+/// sender.reprioritize(&"bumped", 15).unwrap();
+/// sender.cancel(&"cancelled");
+/// drop(sender);
+///
+/// assert_eq!(receiver.recv().await, Some(("bumped", 15)));
+/// assert_eq!(receiver.recv().await, Some(("high", 10)));
+/// assert_eq!(receiver.recv().await, Some(("low", 1)));
+/// // All senders are dropped and channel is empty
+/// assert_eq!(receiver.recv().await, None);
+/// # });
+/// ```
+pub fn priority_channel<TKey, TPriority>() -> (
+    Sender<TKey, TPriority, RandomState>,
+    Receiver<TKey, TPriority, RandomState>,
+)
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+{
+    priority_channel_with_hasher(RandomState::default())
+}
+
+/// Creates priority channel which uses specific Hasher for keys.
+///
+/// See [`priority_channel`] for details.
+///
+/// [`priority_channel`]: fn.priority_channel.html
+pub fn priority_channel_with_hasher<TKey, TPriority, S>(
+    hasher: S,
+) -> (Sender<TKey, TPriority, S>, Receiver<TKey, TPriority, S>)
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    let shared = Arc::new(Mutex::new(State {
+        queue: KeyedPriorityQueue::with_hasher(hasher),
+        wakers: VecDeque::new(),
+        next_waker_id: 0,
+        senders: 1,
+        receivers: 1,
+    }));
+    (
+        Sender {
+            shared: Arc::clone(&shared),
+        },
+        Receiver { shared },
+    )
+}
+
+struct State<TKey, TPriority, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    queue: KeyedPriorityQueue<TKey, TPriority, BinaryHeap<TPriority>, S>,
+    // Wakers of pending `Recv` futures with their ids in order of registration.
+    // Notified futures are removed from it.
+    wakers: VecDeque<(usize, Waker)>,
+    next_waker_id: usize,
+    senders: usize,
+    receivers: usize,
+}
+
+type Shared<TKey, TPriority, S> = Arc<Mutex<State<TKey, TPriority, S>>>;
+
+/// Sending half of priority channel.
+///
+/// Can be cloned to send items from many places.
+/// When last sender is dropped, receivers get `None` after queued items run out.
+pub struct Sender<TKey, TPriority, S = RandomState>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    shared: Shared<TKey, TPriority, S>,
+}
+
+/// Receiving half of priority channel.
+///
+/// Can be cloned to consume items by many tasks.
+/// Each item is delivered only to one receiver.
+pub struct Receiver<TKey, TPriority, S = RandomState>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    shared: Shared<TKey, TPriority, S>,
+}
+
+impl<TKey, TPriority, S> Sender<TKey, TPriority, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    /// Adds new item to channel or replaces priority of already queued key.
+    /// Returns old priority if key was queued.
+    ///
+    /// New item wakes one waiting receiver, update of queued key wakes nobody.
+    ///
+    /// Returns [`SendError`] with passed item if all receivers are dropped.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(log n)*** in average.
+    ///
+    /// [`SendError`]: struct.SendError.html
+    pub fn send(
+        &self,
+        key: TKey,
+        priority: TPriority,
+    ) -> Result<Option<TPriority>, SendError<(TKey, TPriority)>> {
+        let mut state = lock(&self.shared);
+        if state.receivers == 0 {
+            return Err(SendError((key, priority)));
+        }
+        let old = state.queue.push(key, priority);
+        let waker = match old {
+            Some(_) => None,
+            None => state.wakers.pop_front(),
+        };
+        drop(state);
+        wake_one(waker);
+        Ok(old)
+    }
+
+    /// Changes priority of queued item.
+    /// Returns old priority if succeeds or [`SetPriorityNotFoundError`]
+    /// if item is missing, e.g. it was already received.
+    ///
+    /// ### Time complexity
+    ///
+    /// In best case ***O(1)***, in average costs ***O(log n)***.
+    ///
+    /// [`SetPriorityNotFoundError`]: struct.SetPriorityNotFoundError.html
+    pub fn reprioritize<Q>(
+        &self,
+        key: &Q,
+        priority: TPriority,
+    ) -> Result<TPriority, SetPriorityNotFoundError>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        lock(&self.shared).queue.set_priority(key, priority)
+    }
+
+    /// Removes queued item so no receiver would get it.
+    /// Returns its priority if item was queued.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(log n)*** in average.
+    pub fn cancel<Q>(&self, key: &Q) -> Option<TPriority>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        lock(&self.shared).queue.remove(key)
+    }
+
+    /// Returns true if all receivers are dropped.
+    pub fn is_closed(&self) -> bool {
+        lock(&self.shared).receivers == 0
+    }
+}
+
+impl<TKey, TPriority, S> Clone for Sender<TKey, TPriority, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    fn clone(&self) -> Self {
+        lock(&self.shared).senders += 1;
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<TKey, TPriority, S> Drop for Sender<TKey, TPriority, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        let mut state = lock(&self.shared);
+        state.senders -= 1;
+        if state.senders == 0 {
+            // Waiting receivers need to know that channel is closed
+            let wakers = std::mem::take(&mut state.wakers);
+            drop(state);
+            wake_all(wakers);
+        }
+    }
+}
+
+impl<TKey, TPriority, S> Receiver<TKey, TPriority, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    /// Receives item with the highest priority.
+    /// Waits if channel is empty.
+    ///
+    /// Returns `None` if channel is empty and all senders are dropped.
+    pub fn recv(&self) -> Recv<'_, TKey, TPriority, S> {
+        Recv {
+            receiver: self,
+            waker_id: None,
+        }
+    }
+
+    /// Receives item with the highest priority if channel is not empty.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(log n)*** in average.
+    pub fn try_recv(&self) -> Option<(TKey, TPriority)> {
+        lock(&self.shared).queue.pop()
+    }
+
+    /// Get the number of queued items.
+    pub fn len(&self) -> usize {
+        lock(&self.shared).queue.len()
+    }
+
+    /// Returns true if there are no queued items.
+    pub fn is_empty(&self) -> bool {
+        lock(&self.shared).queue.is_empty()
+    }
+}
+
+impl<TKey, TPriority, S> Clone for Receiver<TKey, TPriority, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    fn clone(&self) -> Self {
+        lock(&self.shared).receivers += 1;
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<TKey, TPriority, S> Drop for Receiver<TKey, TPriority, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        let mut state = lock(&self.shared);
+        state.receivers -= 1;
+        if state.receivers == 0 {
+            // Nobody would receive queued items anymore
+            state.queue.clear();
+        }
+    }
+}
+
+/// Future returned by [`Receiver::recv`].
+///
+/// [`Receiver::recv`]: struct.Receiver.html#method.recv
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Recv<'a, TKey, TPriority, S = RandomState>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    receiver: &'a Receiver<TKey, TPriority, S>,
+    waker_id: Option<usize>,
+}
+
+impl<'a, TKey, TPriority, S> Future for Recv<'a, TKey, TPriority, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    type Output = Option<(TKey, TPriority)>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = lock(&this.receiver.shared);
+        if let Some(item) = state.queue.pop() {
+            unregister(&mut state, this.waker_id.take());
+            return Poll::Ready(Some(item));
+        }
+        if state.senders == 0 {
+            unregister(&mut state, this.waker_id.take());
+            return Poll::Ready(None);
+        }
+        let registered = this.waker_id.and_then(|id| {
+            state
+                .wakers
+                .iter_mut()
+                .find(|(waker_id, _)| *waker_id == id)
+        });
+        match registered {
+            Some((_, waker)) => {
+                if !waker.will_wake(cx.waker()) {
+                    *waker = cx.waker().clone();
+                }
+            }
+            None => {
+                let id = state.next_waker_id;
+                state.next_waker_id = state.next_waker_id.wrapping_add(1);
+                state.wakers.push_back((id, cx.waker().clone()));
+                this.waker_id = Some(id);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<'a, TKey, TPriority, S> Drop for Recv<'a, TKey, TPriority, S>
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        let id = match self.waker_id.take() {
+            Some(id) => id,
+            None => return,
+        };
+        let mut state = lock(&self.receiver.shared);
+        let registered = state.wakers.iter().any(|(waker_id, _)| *waker_id == id);
+        if registered {
+            unregister(&mut state, Some(id));
+            return;
+        }
+        // This future was notified about new item but wouldn't take it
+        // so notification is passed to the next waiting one
+        let waker = if state.queue.is_empty() {
+            None
+        } else {
+            state.wakers.pop_front()
+        };
+        drop(state);
+        wake_one(waker);
+    }
+}
+
+/// This is error type for [`Sender::send`].
+/// Contains item which wasn't sent because all receivers are dropped.
+///
+/// [`Sender::send`]: struct.Sender.html#method.send
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Default)]
+pub struct SendError<T>(pub T);
+
+impl<T> Debug for SendError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.write_str("SendError { .. }")
+    }
+}
+
+impl<T> Display for SendError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.write_str("Sending to priority channel without receivers")
+    }
+}
+
+impl<T> std::error::Error for SendError<T> {}
+
+fn unregister<TKey, TPriority, S>(state: &mut State<TKey, TPriority, S>, waker_id: Option<usize>)
+where
+    TKey: Hash + Eq,
+    TPriority: Ord,
+    S: BuildHasher,
+{
+    if let Some(id) = waker_id {
+        state.wakers.retain(|(waker_id, _)| *waker_id != id);
+    }
+}
+
+// Wakers are called after unlocking state
+// to avoid contention with woken tasks.
+fn wake_all(wakers: VecDeque<(usize, Waker)>) {
+    for (_, waker) in wakers {
+        waker.wake();
+    }
+}
+
+// Only one receiver can take new item so only one is woken.
+// Notified future which is dropped without polling passes notification on.
+fn wake_one(waker: Option<(usize, Waker)>) {
+    if let Some((_, waker)) = waker {
+        waker.wake();
+    }
+}
+
+// Queue stays consistent if comparison of priorities panics while lock is held
+// so poisoning is ignored. It also keeps `Drop` impls from panicking during unwinding.
+#[inline]
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{priority_channel, SendError};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn poll_once<F: Future + Unpin>(future: &mut F, waker: &Waker) -> Poll<F::Output> {
+        Pin::new(future).poll(&mut Context::from_waker(waker))
+    }
+
+    #[test]
+    fn test_recv_order() {
+        let (sender, receiver) = priority_channel();
+        for x in 0..10 {
+            assert_eq!(sender.send(x, x % 4), Ok(None));
+        }
+        assert_eq!(sender.send(9, 10), Ok(Some(1)));
+        assert_eq!(sender.reprioritize(&0, 5), Ok(0));
+        assert_eq!(sender.cancel(&3), Some(3));
+        assert_eq!(receiver.len(), 9);
+        drop(sender);
+
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(counter);
+        let mut priorities = Vec::new();
+        while let Poll::Ready(Some((_, p))) = poll_once(&mut receiver.recv(), &waker) {
+            priorities.push(p);
+        }
+        assert_eq!(priorities, vec![10, 5, 3, 2, 2, 1, 1, 0, 0]);
+        assert_eq!(poll_once(&mut receiver.recv(), &waker), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_wake_on_send() {
+        let (sender, receiver) = priority_channel();
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(Arc::clone(&counter));
+
+        let mut first = receiver.recv();
+        let mut second = receiver.recv();
+        assert_eq!(poll_once(&mut first, &waker), Poll::Pending);
+        assert_eq!(poll_once(&mut second, &waker), Poll::Pending);
+        // Repeated poll must not register waker twice
+        assert_eq!(poll_once(&mut first, &waker), Poll::Pending);
+
+        // Only one receiver is woken for new item
+        sender.send("job", 1).unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        // Update of queued key wakes nobody
+        assert_eq!(sender.send("job", 2), Ok(Some(1)));
+        assert_eq!(sender.reprioritize(&"job", 3), Ok(2));
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            poll_once(&mut second, &waker),
+            Poll::Ready(Some(("job", 3)))
+        );
+        assert_eq!(poll_once(&mut first, &waker), Poll::Pending);
+
+        drop(sender);
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+        assert_eq!(poll_once(&mut first, &waker), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_dropped_recv_unregisters() {
+        let (sender, receiver) = priority_channel::<i32, i32>();
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(Arc::clone(&counter));
+        {
+            let mut recv = receiver.recv();
+            assert_eq!(poll_once(&mut recv, &waker), Poll::Pending);
+        }
+        sender.send(1, 1).unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_dropped_notified_recv_passes_wake() {
+        let (sender, receiver) = priority_channel();
+        let first_counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let second_counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let first_waker = Waker::from(Arc::clone(&first_counter));
+        let second_waker = Waker::from(Arc::clone(&second_counter));
+
+        let mut first = receiver.recv();
+        let mut second = receiver.recv();
+        assert_eq!(poll_once(&mut first, &first_waker), Poll::Pending);
+        assert_eq!(poll_once(&mut second, &second_waker), Poll::Pending);
+
+        sender.send(1, 1).unwrap();
+        assert_eq!(first_counter.0.load(Ordering::SeqCst), 1);
+        assert_eq!(second_counter.0.load(Ordering::SeqCst), 0);
+        // Item isn't lost when woken future is cancelled
+        drop(first);
+        assert_eq!(second_counter.0.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            poll_once(&mut second, &second_waker),
+            Poll::Ready(Some((1, 1)))
+        );
+    }
+
+    #[test]
+    fn test_panicking_priority() {
+        use std::cmp::Ordering as CmpOrdering;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        #[derive(Debug, PartialEq, Eq)]
+        struct Priority(bool);
+
+        impl PartialOrd for Priority {
+            fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for Priority {
+            fn cmp(&self, other: &Self) -> CmpOrdering {
+                if self.0 || other.0 {
+                    panic!("Comparison panicked");
+                }
+                CmpOrdering::Equal
+            }
+        }
+
+        let (sender, receiver) = priority_channel();
+        sender.send(1, Priority(false)).unwrap();
+        let result = catch_unwind(AssertUnwindSafe(|| sender.send(2, Priority(true))));
+        assert!(result.is_err());
+        // Channel remains usable after poisoning
+        assert_eq!(receiver.len(), 2);
+        assert!(!sender.is_closed());
+        drop(sender);
+        drop(receiver);
+    }
+
+    #[test]
+    fn test_closed() {
+        let (sender, receiver) = priority_channel();
+        let second_receiver = receiver.clone();
+        drop(receiver);
+        assert!(!sender.is_closed());
+        sender.send(1, 1).unwrap();
+        drop(second_receiver);
+        assert!(sender.is_closed());
+        assert_eq!(sender.send(2, 2), Err(SendError((2, 2))));
+        assert_eq!(sender.cancel(&1), None);
+    }
+
+    #[test]
+    fn test_multithreaded() {
+        let (sender, receiver) = priority_channel();
+        let received = std::thread::scope(|s| {
+            let consumers: Vec<_> = (0..4)
+                .map(|_| {
+                    let receiver = receiver.clone();
+                    s.spawn(move || {
+                        let mut count = 0;
+                        while futures::executor::block_on(receiver.recv()).is_some() {
+                            count += 1;
+                        }
+                        count
+                    })
+                })
+                .collect();
+            for t in 0..4 {
+                let sender = sender.clone();
+                s.spawn(move || {
+                    for i in 0..250 {
+                        sender.send(t * 250 + i, i).unwrap();
+                    }
+                });
+            }
+            drop(sender);
+            consumers
+                .into_iter()
+                .map(|c| c.join().unwrap())
+                .sum::<usize>()
+        });
+        assert_eq!(received, 1000);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::editable_binary_heap::BinaryHeap;
+use crate::keyed_priority_queue::{KeyedPriorityQueue, SetPriorityNotFoundError};
+use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+use std::iter::FusedIterator;
+use std::time::Instant;
+
+/// A queue of timers where each key has its own deadline.
+///
+/// Timers with the earliest deadline are expired first.
+/// Deadline of the key can be moved at any moment, e.g. to reset timeout of connection
+/// when it receives data.
+///
+/// # Examples
+///
+/// ```
+/// use keyed_priority_queue::TimerQueue;
+/// use std::time::{Duration, Instant};
+///
+/// let start = Instant::now();
+/// let mut timers = TimerQueue::new();
+/// timers.schedule("first", start + Duration::from_secs(1));
+/// timers.schedule("second", start + Duration::from_secs(2));
+/// timers.schedule("third", start + Duration::from_secs(3));
+///
+/// // Connection got a packet so we move its timeout
+/// timers.reschedule(&"first", start + Duration::from_secs(4)).unwrap();
+/// timers.cancel(&"third");
+///
+/// assert_eq!(timers.next_deadline(), Some(start + Duration::from_secs(2)));
+/// let expired: Vec<_> = timers.pop_expired(start + Duration::from_secs(3)).collect();
+/// assert_eq!(expired, vec!["second"]);
+/// assert_eq!(timers.next_deadline(), Some(start + Duration::from_secs(4)));
+/// ```
+#[derive(Clone)]
+pub struct TimerQueue<TKey, S = RandomState>
+where
+    TKey: Hash + Eq,
+    S: BuildHasher,
+{
+    queue: KeyedPriorityQueue<TKey, Reverse<Instant>, BinaryHeap<Reverse<Instant>>, S>,
+}
+
+impl<TKey: Hash + Eq> TimerQueue<TKey, RandomState> {
+    /// Creates an empty timer queue.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::TimerQueue;
+    /// let mut timers = TimerQueue::new();
+    /// timers.schedule("Key", std::time::Instant::now());
+    /// ```
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_capacity_and_hasher(0, RandomState::default())
+    }
+
+    /// Creates an empty timer queue and allocates memory for `capacity` timers.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::default())
+    }
+}
+
+impl<TKey: Hash + Eq, S: BuildHasher> TimerQueue<TKey, S> {
+    /// Creates an empty timer queue with specific Hasher.
+    #[inline]
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::with_capacity_and_hasher(0, hasher)
+    }
+
+    /// Creates an empty timer queue with allocated memory and specific Hasher.
+    #[inline]
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        Self {
+            queue: KeyedPriorityQueue::with_capacity_and_hasher(capacity, hasher),
+        }
+    }
+
+    /// Sets deadline for key.
+    /// Returns old deadline if key was already scheduled, in that case key is not replaced.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(log n)*** in average.
+    #[inline]
+    pub fn schedule(&mut self, key: TKey, at: Instant) -> Option<Instant> {
+        self.queue.push(key, Reverse(at)).map(|Reverse(old)| old)
+    }
+
+    /// Moves deadline of already scheduled key.
+    /// Returns old deadline if succeeds or [`SetPriorityNotFoundError`].
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::{SetPriorityNotFoundError, TimerQueue};
+    /// use std::time::{Duration, Instant};
+    /// let now = Instant::now();
+    /// let mut timers = TimerQueue::new();
+    /// timers.schedule("Key", now);
+    /// assert_eq!(timers.reschedule(&"Key", now + Duration::from_secs(1)), Ok(now));
+    /// assert_eq!(timers.reschedule(&"Missing", now), Err(SetPriorityNotFoundError{}));
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// In best case ***O(1)***, in average costs ***O(log n)***.
+    ///
+    /// [`SetPriorityNotFoundError`]: struct.SetPriorityNotFoundError.html
+    #[inline]
+    pub fn reschedule<Q>(
+        &mut self,
+        key: &Q,
+        at: Instant,
+    ) -> Result<Instant, SetPriorityNotFoundError>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.queue
+            .set_priority(key, Reverse(at))
+            .map(|Reverse(old)| old)
+    }
+
+    /// Removes timer of the key.
+    /// Returns its deadline if key was scheduled.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(log n)*** in average.
+    #[inline]
+    pub fn cancel<Q>(&mut self, key: &Q) -> Option<Instant>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.queue.remove(key).map(|Reverse(at)| at)
+    }
+
+    /// Get deadline of the key.
+    ///
+    /// ### Time complexity
+    ///
+    /// ***O(1)*** in average.
+    #[inline]
+    pub fn deadline<Q>(&self, key: &Q) -> Option<Instant>
+    where
+        TKey: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.queue.get_priority(key).map(|&Reverse(at)| at)
+    }
+
+    /// Get the earliest deadline.
+    /// Useful to choose how long to sleep.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.queue.peek().map(|(_, &Reverse(at))| at)
+    }
+
+    /// Returns iterator which removes and yields keys with deadlines not later than `now`
+    /// in order of their deadlines.
+    ///
+    /// Timers are removed lazily so if iterator is dropped early,
+    /// remaining expired timers stay in the queue.
+    ///
+    /// ### Examples
+    ///
+    ///
+    /// ```
+    /// use keyed_priority_queue::TimerQueue;
+    /// use std::time::{Duration, Instant};
+    /// let now = Instant::now();
+    /// let mut timers = TimerQueue::new();
+    /// for i in 0..5 {
+    ///     timers.schedule(i, now + Duration::from_secs(i));
+    /// }
+    /// let expired: Vec<_> = timers.pop_expired(now + Duration::from_secs(2)).collect();
+    /// assert_eq!(expired, vec![0, 1, 2]);
+    /// assert_eq!(timers.len(), 2);
+    /// ```
+    ///
+    /// ### Time complexity
+    ///
+    /// Each iteration costs ***O(log n)***.
+    #[inline]
+    pub fn pop_expired(&mut self, now: Instant) -> PopExpired<'_, TKey, S> {
+        PopExpired { timers: self, now }
+    }
+
+    /// Get the number of scheduled timers.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns true if there are no scheduled timers.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(1)***
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Cancels all timers.
+    ///
+    /// ### Time complexity
+    ///
+    /// Always ***O(n)***
+    #[inline]
+    pub fn clear(&mut self) {
+        self.queue.clear()
+    }
+
+    /// Returns iterator over keys and their deadlines in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&TKey, Instant)> + '_ {
+        self.queue.iter().map(|(key, &Reverse(at))| (key, at))
+    }
+}
+
+impl<TKey: Hash + Eq, S: BuildHasher + Default> Default for TimerQueue<TKey, S> {
+    #[inline]
+    fn default() -> Self {
+        Self::with_capacity_and_hasher(0, S::default())
+    }
+}
+
+impl<TKey: Hash + Eq + Debug, S: BuildHasher> Debug for TimerQueue<TKey, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// This is iterator which removes expired timers.
+/// Returned by [`TimerQueue::pop_expired`].
+///
+/// [`TimerQueue::pop_expired`]: struct.TimerQueue.html#method.pop_expired
+pub struct PopExpired<'a, TKey, S = RandomState>
+where
+    TKey: Hash + Eq,
+    S: BuildHasher,
+{
+    timers: &'a mut TimerQueue<TKey, S>,
+    now: Instant,
+}
+
+impl<'a, TKey: Hash + Eq, S: BuildHasher> Iterator for PopExpired<'a, TKey, S> {
+    type Item = TKey;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.timers.next_deadline()? > self.now {
+            return None;
+        }
+        self.timers.queue.pop().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.timers.len()))
+    }
+}
+
+// Expired timers cannot appear while we hold mutable reference
+impl<'a, TKey: Hash + Eq, S: BuildHasher> FusedIterator for PopExpired<'a, TKey, S> {}
+
+#[cfg(test)]
+mod tests {
+    use super::TimerQueue;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_schedule() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut timers = TimerQueue::new();
+        assert_eq!(timers.next_deadline(), None);
+        assert_eq!(timers.schedule("a", at(5)), None);
+        assert_eq!(timers.schedule("b", at(3)), None);
+        assert_eq!(timers.schedule("a", at(1)), Some(at(5)));
+        assert_eq!(timers.next_deadline(), Some(at(1)));
+        assert_eq!(timers.reschedule(&"a", at(10)), Ok(at(1)));
+        assert_eq!(timers.next_deadline(), Some(at(3)));
+        assert_eq!(timers.deadline(&"a"), Some(at(10)));
+        assert_eq!(timers.cancel(&"b"), Some(at(3)));
+        assert_eq!(timers.cancel(&"b"), None);
+        assert_eq!(timers.next_deadline(), Some(at(10)));
+        assert_eq!(timers.len(), 1);
+    }
+
+    #[test]
+    fn test_pop_expired() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut timers = TimerQueue::new();
+        for i in (0..10).rev() {
+            timers.schedule(i, at(i));
+        }
+        assert_eq!(timers.pop_expired(start).collect::<Vec<_>>(), vec![0]);
+        // Dropped iterator keeps other expired timers
+        assert_eq!(timers.pop_expired(at(5)).next(), Some(1));
+        assert_eq!(timers.len(), 8);
+        assert_eq!(
+            timers.pop_expired(at(5)).collect::<Vec<_>>(),
+            vec![2, 3, 4, 5]
+        );
+        assert_eq!(timers.pop_expired(at(5)).next(), None);
+        assert_eq!(timers.pop_expired(at(100)).count(), 4);
+        assert!(timers.is_empty());
+        assert_eq!(timers.pop_expired(at(100)).next(), None);
+    }
+}
@@ -0,0 +1,132 @@
+//! Checks that queues created by `new_in` keep all their memory in given allocator.
+
+use allocator_api2::alloc::{AllocError, Allocator, Global, Layout};
+use keyed_priority_queue::{
+    EditableHeap, KeyedBinaryPriorityQueueIn, KeyedPriorityQueue, KeyedWeakPriorityQueueIn,
+};
+use std::alloc::{GlobalAlloc, System};
+use std::cell::Cell;
+use std::ptr::NonNull;
+
+// Counts allocations of current thread so tests running in parallel don't interfere
+struct CountingGlobal;
+
+thread_local! {
+    static GLOBAL_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingGlobal {
+    unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        let _ = GLOBAL_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingGlobal = CountingGlobal;
+
+fn global_allocations() -> usize {
+    GLOBAL_ALLOCATIONS.with(Cell::get)
+}
+
+/// Arena-like allocator which tracks amount of memory in use.
+/// It takes memory from global allocator, so its allocations are counted there too.
+#[derive(Default)]
+struct Arena {
+    in_use: Cell<usize>,
+    allocations: Cell<usize>,
+}
+
+#[derive(Clone, Copy)]
+struct ArenaAlloc<'a>(&'a Arena);
+
+unsafe impl Allocator for ArenaAlloc<'_> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = Global.allocate(layout)?;
+        self.0.in_use.set(self.0.in_use.get() + layout.size());
+        // Global doesn't touch global allocator for zero-sized layouts
+        if layout.size() != 0 {
+            self.0.allocations.set(self.0.allocations.get() + 1);
+        }
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.0.in_use.set(self.0.in_use.get() - layout.size());
+        Global.deallocate(ptr, layout)
+    }
+}
+
+fn check_queue<THeap: EditableHeap<u32>>(
+    arena: &Arena,
+    mut queue: KeyedPriorityQueue<u32, u32, THeap>,
+) {
+    let before = global_allocations() - arena.allocations.get();
+    for i in 0..1000 {
+        queue.push(i, (i * 7) % 1000);
+    }
+    for i in (0..1000).step_by(3) {
+        queue.remove(&i);
+    }
+    queue.shrink_to_fit();
+    queue.set_priority(&1, 5000).unwrap();
+    assert_eq!(queue.pop(), Some((1, 5000)));
+    assert_eq!(global_allocations() - arena.allocations.get(), before);
+    assert!(queue.validate().is_ok());
+}
+
+#[test]
+fn test_binary_queue_in_arena() {
+    let arena = Arena::default();
+    let queue: KeyedBinaryPriorityQueueIn<u32, u32, ArenaAlloc> =
+        KeyedBinaryPriorityQueueIn::new_in(ArenaAlloc(&arena));
+    check_queue(&arena, queue);
+    assert_eq!(arena.in_use.get(), 0);
+}
+
+#[test]
+fn test_weak_queue_in_arena() {
+    let arena = Arena::default();
+    let queue: KeyedWeakPriorityQueueIn<u32, u32, ArenaAlloc> =
+        KeyedWeakPriorityQueueIn::with_capacity_in(10, ArenaAlloc(&arena));
+    assert!(arena.in_use.get() > 0);
+    check_queue(&arena, queue);
+    assert_eq!(arena.in_use.get(), 0);
+}
+
+#[test]
+fn test_memory_is_released_with_queue() {
+    let arena = Arena::default();
+    let mut queue: KeyedBinaryPriorityQueueIn<u32, u32, ArenaAlloc> =
+        KeyedBinaryPriorityQueueIn::new_in(ArenaAlloc(&arena));
+    for i in 0..100 {
+        queue.push(i, i);
+    }
+    assert!(arena.in_use.get() > 0);
+    let cloned = queue.clone();
+    drop(queue);
+    assert_eq!(cloned.iter().count(), 100);
+    drop(cloned);
+    assert_eq!(arena.in_use.get(), 0);
+}
+
+#[test]
+fn test_shrink_releases_memory_of_removed_keys() {
+    let arena = Arena::default();
+    let mut queue: KeyedBinaryPriorityQueueIn<u32, u32, ArenaAlloc> =
+        KeyedBinaryPriorityQueueIn::new_in(ArenaAlloc(&arena));
+    for i in 0..10_000 {
+        queue.push(i, i);
+    }
+    // Surviving key was in the last slot before shrinking
+    for i in 0..9_999 {
+        queue.remove(&i);
+    }
+    queue.shrink_to_fit();
+    assert!(arena.in_use.get() < 1024, "{} bytes", arena.in_use.get());
+    assert_eq!(queue.pop(), Some((9_999, 9_999)));
+}
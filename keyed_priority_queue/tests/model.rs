@@ -0,0 +1,279 @@
+//! Runs random sequences of operations against queues
+//! and compares results with simple `BTreeMap` based model.
+
+use allocator_api2::alloc::Global;
+use allocator_api2::vec::Vec as AllocVec;
+use keyed_priority_queue::{
+    ArrayKeyedPriorityQueue, BinaryHeap, BlockedHeap, BucketQueue, CalendarQueue, EditableHeap,
+    Entry, HeapEntry, KeyedPriorityQueue, LeftistHeap, RadixHeap, SoaStorage, WeakHeap,
+};
+use proptest::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+// Small ranges to get a lot of collisions of keys and priorities
+type Key = u8;
+type Priority = u8;
+
+#[derive(Debug, Clone)]
+enum Operation {
+    Push(Key, Priority),
+    Pop,
+    EntrySet(Key, Priority),
+    EntryRemove(Key),
+    SetPriority(Key, Priority),
+    GetMut(Key, Priority),
+    Remove(Key),
+    Clear,
+    FromIter(Vec<(Key, Priority)>),
+    Meld(Vec<(Key, Priority)>),
+}
+
+fn operation() -> impl Strategy<Value = Operation> {
+    let key = 0..24u8;
+    let priority = 0..16u8;
+    prop_oneof![
+        4 => (key.clone(), priority.clone()).prop_map(|(k, p)| Operation::Push(k, p)),
+        3 => Just(Operation::Pop),
+        2 => (key.clone(), priority.clone()).prop_map(|(k, p)| Operation::EntrySet(k, p)),
+        1 => key.clone().prop_map(Operation::EntryRemove),
+        2 => (key.clone(), priority.clone()).prop_map(|(k, p)| Operation::SetPriority(k, p)),
+        1 => (key.clone(), priority.clone()).prop_map(|(k, p)| Operation::GetMut(k, p)),
+        2 => key.clone().prop_map(Operation::Remove),
+        1 => Just(Operation::Clear),
+        1 => proptest::collection::vec((key.clone(), priority.clone()), 0..32)
+            .prop_map(Operation::FromIter),
+        1 => proptest::collection::vec((key, priority), 0..32).prop_map(Operation::Meld),
+    ]
+}
+
+// Integer backends order priorities by `Reverse` so they get converted priorities
+fn check_against_model<P, THeap>(operations: &[Operation], convert: fn(Priority) -> P)
+where
+    P: Ord + Copy + Debug,
+    THeap: EditableHeap<P>,
+    THeap::Alloc: Default,
+{
+    let mut queue: KeyedPriorityQueue<Key, P, THeap> = KeyedPriorityQueue::new();
+    let mut model: BTreeMap<Key, P> = BTreeMap::new();
+
+    for operation in operations {
+        match operation.clone() {
+            Operation::Push(key, priority) => {
+                let priority = convert(priority);
+                assert_eq!(queue.push(key, priority), model.insert(key, priority));
+            }
+            Operation::Pop => match queue.pop() {
+                Some((key, priority)) => {
+                    // Any of the keys with the biggest priority can be returned
+                    assert_eq!(Some(&priority), model.values().max());
+                    assert_eq!(model.remove(&key), Some(priority));
+                }
+                None => assert!(model.is_empty()),
+            },
+            Operation::EntrySet(key, priority) => match (queue.entry(key), convert(priority)) {
+                (Entry::Occupied(entry), priority) => {
+                    assert_eq!(entry.get_key(), &key);
+                    assert_eq!(
+                        Some(entry.set_priority(priority)),
+                        model.insert(key, priority)
+                    );
+                }
+                (Entry::Vacant(entry), priority) => {
+                    assert_eq!(entry.get_key(), &key);
+                    entry.set_priority(priority);
+                    assert_eq!(model.insert(key, priority), None);
+                }
+            },
+            Operation::EntryRemove(key) => match queue.entry(key) {
+                Entry::Occupied(entry) => {
+                    let (removed_key, priority) = entry.remove();
+                    assert_eq!(removed_key, key);
+                    assert_eq!(model.remove(&key), Some(priority));
+                }
+                Entry::Vacant(_) => assert!(!model.contains_key(&key)),
+            },
+            Operation::SetPriority(key, priority) => match (model.get_mut(&key), convert(priority))
+            {
+                (Some(expected), priority) => {
+                    assert_eq!(queue.set_priority(&key, priority), Ok(*expected));
+                    *expected = priority;
+                }
+                (None, priority) => assert!(queue.set_priority(&key, priority).is_err()),
+            },
+            Operation::GetMut(key, priority) => match queue.get_mut(&key) {
+                Some(mut guard) => {
+                    assert_eq!(Some(&*guard), model.get(&key));
+                    *guard = convert(priority);
+                    model.insert(key, convert(priority));
+                }
+                None => assert!(!model.contains_key(&key)),
+            },
+            Operation::Remove(key) => {
+                assert_eq!(queue.remove(&key), model.remove(&key));
+            }
+            Operation::Clear => {
+                queue.clear();
+                model.clear();
+            }
+            Operation::FromIter(items) => {
+                let items = items
+                    .into_iter()
+                    .map(|(key, priority)| (key, convert(priority)));
+                queue = items.clone().collect();
+                // Later duplicates overwrite earlier ones like in `HashMap`
+                model = items.collect();
+            }
+            Operation::Meld(items) => {
+                let items = items
+                    .into_iter()
+                    .map(|(key, priority)| (key, convert(priority)));
+                queue.meld(items.clone().collect());
+                // Priorities of other queue win like in `extend`
+                model.extend(items);
+            }
+        }
+
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.len(), model.len());
+        assert_eq!(queue.peek().map(|(_, &p)| p), model.values().max().cloned());
+        for (key, priority) in model.iter() {
+            assert_eq!(queue.get_priority(key), Some(priority));
+        }
+    }
+
+    // Consuming iterator must return items in decreasing order of priority
+    let mut rest: Vec<(Key, P)> = queue.into_iter().collect();
+    assert!(rest.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    rest.sort_unstable();
+    assert_eq!(rest, model.into_iter().collect::<Vec<_>>());
+}
+
+// Array queue has no Entry API and rejects new keys when full,
+// so operations are mapped to its methods and model keeps the same capacity
+fn check_array_against_model(operations: &[Operation]) {
+    const CAPACITY: usize = 16;
+    let mut queue: ArrayKeyedPriorityQueue<Key, Priority, CAPACITY> =
+        ArrayKeyedPriorityQueue::new();
+    let mut model: BTreeMap<Key, Priority> = BTreeMap::new();
+
+    let push = |queue: &mut ArrayKeyedPriorityQueue<Key, Priority, CAPACITY>,
+                model: &mut BTreeMap<Key, Priority>,
+                key,
+                priority| {
+        if model.len() == CAPACITY && !model.contains_key(&key) {
+            assert!(queue.push(key, priority).is_err());
+        } else {
+            assert_eq!(queue.push(key, priority), Ok(model.insert(key, priority)));
+        }
+    };
+
+    for operation in operations {
+        match operation.clone() {
+            Operation::Push(key, priority) | Operation::EntrySet(key, priority) => {
+                push(&mut queue, &mut model, key, priority)
+            }
+            Operation::Pop => match queue.pop() {
+                Some((key, priority)) => {
+                    assert_eq!(Some(&priority), model.values().max());
+                    assert_eq!(model.remove(&key), Some(priority));
+                }
+                None => assert!(model.is_empty()),
+            },
+            Operation::SetPriority(key, priority) | Operation::GetMut(key, priority) => {
+                match model.get_mut(&key) {
+                    Some(expected) => {
+                        assert_eq!(queue.set_priority(&key, priority), Ok(*expected));
+                        *expected = priority;
+                    }
+                    None => assert!(queue.set_priority(&key, priority).is_err()),
+                }
+            }
+            Operation::Remove(key) | Operation::EntryRemove(key) => {
+                assert_eq!(
+                    queue.remove_entry(&key),
+                    model.remove(&key).map(|priority| (key, priority))
+                );
+            }
+            Operation::Clear => {
+                queue.clear();
+                model.clear();
+            }
+            Operation::FromIter(items) => {
+                queue.clear();
+                model.clear();
+                for (key, priority) in items {
+                    push(&mut queue, &mut model, key, priority);
+                }
+            }
+            Operation::Meld(items) => {
+                for (key, priority) in items {
+                    push(&mut queue, &mut model, key, priority);
+                }
+            }
+        }
+
+        assert_eq!(queue.validate(), Ok(()));
+        assert_eq!(queue.len(), model.len());
+        assert_eq!(queue.peek().map(|(_, &p)| p), model.values().max().cloned());
+        for (key, priority) in model.iter() {
+            assert_eq!(queue.get_priority(key), Some(priority));
+        }
+    }
+
+    let mut rest: Vec<(Key, Priority)> = queue.iter().map(|(&k, &p)| (k, p)).collect();
+    rest.sort_unstable();
+    assert_eq!(rest, model.into_iter().collect::<Vec<_>>());
+}
+
+proptest! {
+    // Miri is too slow to run a lot of cases
+    #![proptest_config(ProptestConfig::with_cases(if cfg!(miri) { 8 } else { 512 }))]
+
+    #[test]
+    fn binary_heap_matches_model(operations in proptest::collection::vec(operation(), 0..256)) {
+        check_against_model::<_, BinaryHeap<Priority>>(&operations, |x| x);
+        check_against_model::<_, BinaryHeap<Priority, SoaStorage<Priority>>>(&operations, |x| x);
+        check_against_model::<_, BinaryHeap<Priority, AllocVec<HeapEntry<Priority>, Global>>>(&operations, |x| x);
+    }
+
+    #[test]
+    fn weak_heap_matches_model(operations in proptest::collection::vec(operation(), 0..256)) {
+        check_against_model::<_, WeakHeap<Priority>>(&operations, |x| x);
+        check_against_model::<_, WeakHeap<Priority, SoaStorage<Priority>>>(&operations, |x| x);
+        check_against_model::<_, WeakHeap<Priority, AllocVec<HeapEntry<Priority>, Global>>>(&operations, |x| x);
+    }
+
+    #[test]
+    fn blocked_heap_matches_model(operations in proptest::collection::vec(operation(), 0..256)) {
+        check_against_model::<_, BlockedHeap<Priority>>(&operations, |x| x);
+        // Small blocks to cross a lot of block boundaries
+        check_against_model::<_, BlockedHeap<Priority, 2>>(&operations, |x| x);
+    }
+
+    #[test]
+    fn leftist_heap_matches_model(operations in proptest::collection::vec(operation(), 0..256)) {
+        check_against_model::<_, LeftistHeap<Priority>>(&operations, |x| x);
+    }
+
+    #[test]
+    fn radix_heap_matches_model(operations in proptest::collection::vec(operation(), 0..256)) {
+        check_against_model::<_, RadixHeap<Reverse<Priority>>>(&operations, Reverse);
+    }
+
+    #[test]
+    fn bucket_queue_matches_model(operations in proptest::collection::vec(operation(), 0..256)) {
+        check_against_model::<_, BucketQueue<Reverse<Priority>>>(&operations, Reverse);
+    }
+
+    #[test]
+    fn calendar_queue_matches_model(operations in proptest::collection::vec(operation(), 0..256)) {
+        check_against_model::<_, CalendarQueue<Reverse<u64>>>(&operations, |x| Reverse(u64::from(x)));
+    }
+
+    #[test]
+    fn array_queue_matches_model(operations in proptest::collection::vec(operation(), 0..256)) {
+        check_array_against_model(&operations);
+    }
+}